@@ -0,0 +1,197 @@
+use std::path::Path;
+use std::process;
+use desktop_minifb::font_file::{self, SheetLayout};
+use desktop_minifb::image::Image;
+use desktop_minifb::pixel_font::PixelFont;
+use desktop_minifb::widget;
+use desktop_minifb::widget::text_widget::TextWidget;
+use desktop_minifb::widget::{Color, Widget};
+
+const USAGE: &str = "usage:
+  fontc compile INPUT OUTPUT [options]
+      Convert a BDF font, a binary font or a PNG sheet of characters into a binary font.
+      Sheet options:
+        --cell WxH        size of a cell of the sheet (required for sheets)
+        --chars TEXT      the characters in the cells, in order
+        --first CHAR      the character in the first cell, the others follow in code point
+                          order (default: space)
+        --baseline N      rows of a cell above the baseline (default: the cell height)
+        --proportional    trim the characters instead of using whole cells
+        --size N          size of the font in points (default: 12)
+  fontc specimen FONT OUTPUT.png [--text TEXT]... [--scale N]
+      Draw all characters of a font and some sample text into a PNG image.
+  fontc info FONT
+      Print the metrics of a font.";
+///Sample text drawn when none is given.
+const PANGRAM: &str = "The quick brown fox jumps over the lazy dog. 0123456789";
+///Characters per row of a specimen, and the space around everything drawn on it.
+const SPECIMEN_COLUMNS: usize = 16;
+const SPECIMEN_MARGIN: usize = 8;
+
+fn main(){
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(message) = run(&args){
+        eprintln!("fontc: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String>{
+    match args.first().map(|a| a.as_str()){
+        Some("compile") if args.len() >= 3 => compile(&args[1], &args[2], &args[3..]),
+        Some("specimen") if args.len() >= 3 => specimen(&args[1], &args[2], &args[3..]),
+        Some("info") if args.len() == 2 => info(&args[1]),
+        _ => Err(String::from(USAGE))
+    }
+}
+
+///Get the value of an option, which follows it.
+fn option_value(options: &[String], i: usize) -> Result<&str, String>{
+    options.get(i + 1).map(|v| v.as_str()).ok_or_else(|| format!("{} needs a value", options[i]))
+}
+
+fn parse_number(value: &str) -> Result<usize, String>{
+    value.parse::<usize>().map_err(|_| format!("'{}' is not a number", value))
+}
+
+fn load_font(path: &str) -> Result<PixelFont, String>{
+    PixelFont::load(Path::new(path)).map_err(|e| format!("can't read {}: {}", path, e))
+}
+
+fn compile(input: &str, output: &str, options: &[String]) -> Result<(), String>{
+    let mut cell = None;
+    let mut chars = None;
+    let mut first = ' ';
+    let mut baseline = None;
+    let mut proportional = false;
+    let mut size_in_pts = 12;
+    let mut i = 0;
+    while i < options.len(){
+        match options[i].as_str(){
+            "--cell" => {
+                let value = option_value(options, i)?;
+                let (w, h) = value.split_once('x').ok_or_else(|| format!("expected a cell size like 8x16, got '{}'", value))?;
+                cell = Some((parse_number(w)?, parse_number(h)?));
+                i += 1;
+            },
+            "--chars" => {
+                chars = Some(option_value(options, i)?.chars().collect::<Vec<char>>());
+                i += 1;
+            },
+            "--first" => {
+                let value = option_value(options, i)?;
+                let mut value_chars = value.chars();
+                first = match (value_chars.next(), value_chars.next()){
+                    (Some(c), None) => c,
+                    _ => return Err(format!("expected a single character, got '{}'", value))
+                };
+                i += 1;
+            },
+            "--baseline" => {
+                baseline = Some(parse_number(option_value(options, i)?)?);
+                i += 1;
+            },
+            "--size" => {
+                size_in_pts = parse_number(option_value(options, i)?)? as u32;
+                i += 1;
+            },
+            "--proportional" => proportional = true,
+            other => return Err(format!("unknown option '{}'\n{}", other, USAGE))
+        }
+        i += 1;
+    }
+    let is_font = [".bdf", ".mpf"].iter().any(|e| input.to_ascii_lowercase().ends_with(e));
+    let font = if is_font{
+        load_font(input)?
+    } else {
+        let image = Image::load(Path::new(input)).map_err(|e| format!("can't read {}: {}", input, e))?;
+        let (cell_width, cell_height) = cell.ok_or("sheets need the size of their cells, see --cell")?;
+        //without a list, the cells hold consecutive characters until the sheet is full
+        let chars = chars.unwrap_or_else(|| {
+            let count = (image.width / cell_width.max(1)) * (image.height / cell_height.max(1));
+            (first as u32..).filter_map(char::from_u32).take(count).collect()
+        });
+        let layout = SheetLayout{
+            cell_width,
+            cell_height,
+            chars,
+            baseline: baseline.unwrap_or(cell_height).min(cell_height),
+            proportional,
+            size_in_pts,
+        };
+        font_file::from_sheet(&image, &layout).map_err(|e| format!("can't read {}: {}", input, e))?
+    };
+    font.save(Path::new(output)).map_err(|e| format!("can't write {}: {}", output, e))?;
+    println!("{}: {} characters, {} bytes", output, font.get_charset().len(), font.to_bytes().len());
+    Ok(())
+}
+
+fn info(path: &str) -> Result<(), String>{
+    let font = load_font(path)?;
+    println!("size: {} pt", font.get_size());
+    println!("ascent: {}, descent: {}, line height: {}", font.get_ascent(), font.get_descent(), font.get_line_height());
+    println!("characters: {}", font.get_charset().len());
+    println!("kerning pairs: {}", font.get_kerning_pairs().len());
+    println!("ligatures: {}", font.get_ligatures().len());
+    println!("replacement character: U+{:04X}", font.get_replacement_char() as u32);
+    Ok(())
+}
+
+fn specimen(path: &str, output: &str, options: &[String]) -> Result<(), String>{
+    let mut lines = vec![];
+    let mut scale = 1;
+    let mut i = 0;
+    while i < options.len(){
+        match options[i].as_str(){
+            "--text" => lines.push(String::from(option_value(options, i)?)),
+            "--scale" => scale = parse_number(option_value(options, i)?)?.max(1),
+            other => return Err(format!("unknown option '{}'\n{}", other, USAGE))
+        }
+        i += 2;
+    }
+    if lines.is_empty(){
+        lines.push(String::from(PANGRAM));
+    }
+    let font = load_font(path)?;
+    let charset = font.get_charset();
+    //every character gets a cell as wide as the widest one, with its baseline drawn in
+    let cell_width = charset.values().map(|g| g.get_advance().max(g.left_bearing.max(0) as usize + g.get_width())).max().unwrap_or(0) + 4;
+    let cell_height = font.get_line_height() + 4;
+    let rows = charset.len().div_ceil(SPECIMEN_COLUMNS);
+    let texts: Vec<TextWidget> = lines.iter()
+        .map(|l| TextWidget::new(Box::new(font.clone()), false, l, Color::black(), Color::white()))
+        .collect();
+    let text_width = texts.iter().map(|t| t.get_min_bounds().width).max().unwrap_or(0);
+    let grid_width = SPECIMEN_COLUMNS.min(charset.len()) * cell_width;
+    let width = grid_width.max(text_width) + SPECIMEN_MARGIN * 2;
+    let height = rows * cell_height + texts.len() * (font.get_line_height() + 2) + SPECIMEN_MARGIN * 3;
+    let grid = Color::rgb(192, 192, 192);
+    let baseline = Color::rgb(255, 160, 160);
+    let mut pixels = vec![<[u8; 4]>::from(Color::white()); width * height];
+    for (i, glyph) in charset.values().enumerate(){
+        let x = SPECIMEN_MARGIN + i % SPECIMEN_COLUMNS * cell_width;
+        let y = SPECIMEN_MARGIN + i / SPECIMEN_COLUMNS * cell_height;
+        for dx in 0..cell_width{
+            pixels[y * width + x + dx] = grid.into();
+            pixels[(y + cell_height - 1) * width + x + dx] = grid.into();
+            pixels[(y + 2 + font.get_ascent()) * width + x + dx] = baseline.into();
+        }
+        for dy in 0..cell_height{
+            pixels[(y + dy) * width + x] = grid.into();
+            pixels[(y + dy) * width + x + cell_width - 1] = grid.into();
+        }
+        pixels = widget::draw_coverage_at(
+            ((x + 2) as isize + glyph.left_bearing, (y + 2 + font.get_ascent()) as isize - glyph.ascent),
+            pixels, (width, height),
+            glyph.get_pixels(), (glyph.get_width(), glyph.get_height()),
+            Color::black(), 1.0);
+    }
+    let mut y = SPECIMEN_MARGIN * 2 + rows * cell_height;
+    for text in texts.iter(){
+        pixels = text.draw_onto(pixels, width, height, SPECIMEN_MARGIN as isize, y as isize);
+        y += font.get_line_height() + 2;
+    }
+    let image = Image::new(width, height, pixels);
+    image.scaled(width * scale, height * scale).save_png(Path::new(output))
+        .map_err(|e| format!("can't write {}: {}", output, e))
+}
@@ -0,0 +1,50 @@
+///Content that can be stored on the clipboard.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ClipboardContent{
+    Text(String),
+    ///An image as a buffer of RGBA pixels, row by row.
+    Image{width: usize, height: usize, pixels: Vec<[u8; 4]>},
+}
+
+///The desktop-wide clipboard, shared by all widgets through MainWidget. Holds a single piece of
+/// content; storing something new replaces what was there before.
+#[derive(Default)]
+pub struct Clipboard{
+    content: Option<ClipboardContent>,
+}
+
+impl Clipboard{
+    ///Create an empty clipboard.
+    pub fn new() -> Self{
+        Clipboard{
+            content: None,
+        }
+    }
+    ///Replace the content of the clipboard.
+    pub fn set(&mut self, content: ClipboardContent){
+        self.content = Some(content);
+    }
+    ///Put the given text on the clipboard.
+    pub fn set_text(&mut self, text: &str){
+        self.set(ClipboardContent::Text(String::from(text)));
+    }
+    ///Get the content of the clipboard, if there is any.
+    pub fn get(&self) -> Option<&ClipboardContent>{
+        self.content.as_ref()
+    }
+    ///Get the content of the clipboard if it is text.
+    pub fn get_text(&self) -> Option<&str>{
+        match &self.content{
+            Some(ClipboardContent::Text(t)) => Some(t),
+            _ => None
+        }
+    }
+    ///Returns true if the clipboard holds nothing.
+    pub fn is_empty(&self) -> bool{
+        self.content.is_none()
+    }
+    ///Remove the content of the clipboard.
+    pub fn clear(&mut self){
+        self.content = None;
+    }
+}
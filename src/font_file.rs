@@ -0,0 +1,335 @@
+use std::io;
+use crate::image::{invalid_data, Image};
+use crate::pixel_font::{FontPixel, PixelFont, PixelFontChar};
+
+///The four bytes every binary pixel font starts with.
+pub const MAGIC: [u8; 4] = *b"MPFN";
+///Version of the format written by encode, the only one decode reads.
+pub const VERSION: u8 = 1;
+///Ways a bitmap can be stored: a bit per pixel (most significant bit first), runs of pixels
+/// without and with ink taking turns, or a byte of coverage per pixel for anti-aliased glyphs.
+const BITMAP_BITS: u8 = 0;
+const BITMAP_RUNS: u8 = 1;
+const BITMAP_COVERAGE: u8 = 2;
+///Sizes of the header and of the entries of the tables after it.
+const HEADER_SIZE: usize = 28;
+const GLYPH_ENTRY_SIZE: usize = 24;
+
+//The format, all numbers little-endian:
+//header: MAGIC, VERSION, a reserved byte, size in points (u16), ascent (u16), descent (u16),
+// replacement character (u32), then the number of glyphs, kerning pairs and ligatures (u32 each).
+//glyph table: character (u32), width, height (u16), left bearing (i16), right offset (u16),
+// ascent (i16), bitmap storage (u8), a reserved byte, offset of the bitmap from the start of the
+// bitmaps and its length in bytes (u32).
+//kerning table: left and right character (u32), adjustment (i16).
+//ligatures: character (u32), length of the sequence in bytes (u8), the sequence in UTF-8.
+//bitmaps, one after the other.
+
+///Pack the pixels of a glyph in the smallest of the storage methods. Returns the method and the
+/// bytes.
+fn encode_bitmap(pixels: &[FontPixel]) -> (u8, Vec<u8>){
+    if pixels.iter().any(|p| p.get_alpha() != 0 && p.get_alpha() != 255){
+        return (BITMAP_COVERAGE, pixels.iter().map(|p| p.get_alpha()).collect())
+    }
+    let mut bits = vec![0u8; pixels.len().div_ceil(8)];
+    for (i, p) in pixels.iter().enumerate(){
+        if p.get_alpha() != 0{
+            bits[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    //runs longer than a byte go on after an empty run of the other kind
+    let mut runs = vec![];
+    let mut ink = false;
+    let mut i = 0;
+    while i < pixels.len(){
+        let mut run = 0;
+        while i < pixels.len() && run < 255 && (pixels[i].get_alpha() != 0) == ink{
+            run += 1;
+            i += 1;
+        }
+        runs.push(run as u8);
+        ink = !ink;
+    }
+    if runs.len() < bits.len() {(BITMAP_RUNS, runs)} else {(BITMAP_BITS, bits)}
+}
+
+///Unpack the given number of pixels of a glyph.
+fn decode_bitmap(method: u8, data: &[u8], count: usize) -> io::Result<Vec<FontPixel>>{
+    let pixels = match method{
+        BITMAP_BITS => {
+            if data.len() < count.div_ceil(8){
+                return Err(invalid_data("glyph bitmap is cut off"))
+            }
+            (0..count).map(|i| FontPixel::new(if data[i / 8] & (0x80 >> (i % 8)) != 0 {255u8} else {0u8})).collect()
+        },
+        BITMAP_RUNS => {
+            let mut pixels = Vec::with_capacity(count);
+            for (i, run) in data.iter().enumerate(){
+                let alpha = if i % 2 == 1 {255u8} else {0u8};
+                pixels.extend(std::iter::repeat_n(FontPixel::new(alpha), *run as usize));
+            }
+            pixels
+        },
+        BITMAP_COVERAGE => data.iter().map(|a| FontPixel::new(*a)).collect(),
+        _ => return Err(invalid_data("unknown glyph bitmap storage"))
+    };
+    if pixels.len() != count{
+        return Err(invalid_data("glyph bitmap doesn't match its size"))
+    }
+    Ok(pixels)
+}
+
+///Write a font in the binary format.
+pub fn encode(font: &PixelFont) -> Vec<u8>{
+    let charset = font.get_charset();
+    let kerning = font.get_kerning_pairs();
+    let ligatures = font.get_ligatures();
+    let mut out = Vec::with_capacity(HEADER_SIZE + charset.len() * (GLYPH_ENTRY_SIZE + 32));
+    out.extend_from_slice(&MAGIC);
+    out.extend([VERSION, 0u8]);
+    out.extend((font.get_size().min(u16::MAX as u32) as u16).to_le_bytes());
+    out.extend((font.get_ascent() as u16).to_le_bytes());
+    out.extend((font.get_descent() as u16).to_le_bytes());
+    out.extend((font.get_replacement_char() as u32).to_le_bytes());
+    out.extend((charset.len() as u32).to_le_bytes());
+    out.extend((kerning.len() as u32).to_le_bytes());
+    out.extend((ligatures.len() as u32).to_le_bytes());
+    let mut bitmaps = vec![];
+    for (c, glyph) in charset.iter(){
+        let (method, data) = encode_bitmap(glyph.get_pixels());
+        out.extend((*c as u32).to_le_bytes());
+        out.extend((glyph.get_width() as u16).to_le_bytes());
+        out.extend((glyph.get_height() as u16).to_le_bytes());
+        out.extend((glyph.left_bearing as i16).to_le_bytes());
+        out.extend((glyph.right_offset as u16).to_le_bytes());
+        out.extend((glyph.ascent as i16).to_le_bytes());
+        out.extend([method, 0u8]);
+        out.extend((bitmaps.len() as u32).to_le_bytes());
+        out.extend((data.len() as u32).to_le_bytes());
+        bitmaps.extend(data);
+    }
+    for ((left, right), adjustment) in kerning.iter(){
+        out.extend((*left as u32).to_le_bytes());
+        out.extend((*right as u32).to_le_bytes());
+        out.extend((*adjustment as i16).to_le_bytes());
+    }
+    for (sequence, glyph) in ligatures.iter(){
+        out.extend((*glyph as u32).to_le_bytes());
+        out.push(sequence.len().min(255) as u8);
+        out.extend_from_slice(&sequence.as_bytes()[..sequence.len().min(255)]);
+    }
+    out.extend(bitmaps);
+    out
+}
+
+///Reads the numbers of a binary font, failing at its end.
+struct Reader<'a>{
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a>{
+    fn read(&mut self, count: usize) -> io::Result<&'a [u8]>{
+        let bytes = self.bytes.get(self.position..self.position + count).ok_or_else(|| invalid_data("font file is cut off"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+    fn read_u8(&mut self) -> io::Result<u8>{
+        Ok(self.read(1)?[0])
+    }
+    fn read_u16(&mut self) -> io::Result<u16>{
+        let b = self.read(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    fn read_i16(&mut self) -> io::Result<i16>{
+        Ok(self.read_u16()? as i16)
+    }
+    fn read_u32(&mut self) -> io::Result<u32>{
+        let b = self.read(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn read_char(&mut self) -> io::Result<char>{
+        char::from_u32(self.read_u32()?).ok_or_else(|| invalid_data("invalid character in font file"))
+    }
+}
+
+///Read a font in the binary format. The whole file is checked, a broken one is an error rather
+/// than a font with characters missing.
+pub fn decode(bytes: &[u8]) -> io::Result<PixelFont>{
+    if !bytes.starts_with(&MAGIC){
+        return Err(invalid_data("not a binary pixel font"))
+    }
+    let mut reader = Reader{bytes, position: MAGIC.len()};
+    if reader.read_u8()? != VERSION{
+        return Err(invalid_data("unsupported pixel font version"))
+    }
+    reader.read_u8()?;
+    let size_in_pts = reader.read_u16()? as u32;
+    let ascent = reader.read_u16()? as usize;
+    let descent = reader.read_u16()? as usize;
+    let mut font = PixelFont::new(size_in_pts, ascent, descent);
+    font.set_replacement_char(reader.read_char()?);
+    let glyph_count = reader.read_u32()? as usize;
+    let kerning_count = reader.read_u32()? as usize;
+    let ligature_count = reader.read_u32()? as usize;
+    //the glyphs are read after the tables, once the start of the bitmaps is known
+    let glyph_table = reader.read(glyph_count.checked_mul(GLYPH_ENTRY_SIZE).ok_or_else(|| invalid_data("font file is cut off"))?)?;
+    for _ in 0..kerning_count{
+        let (left, right) = (reader.read_char()?, reader.read_char()?);
+        font.set_kerning(left, right, reader.read_i16()? as isize);
+    }
+    for _ in 0..ligature_count{
+        let glyph = reader.read_char()?;
+        let length = reader.read_u8()? as usize;
+        let sequence = std::str::from_utf8(reader.read(length)?).map_err(|_| invalid_data("invalid ligature in font file"))?;
+        font.add_ligature(sequence, glyph);
+    }
+    let bitmaps = &bytes[reader.position..];
+    let mut table = Reader{bytes: glyph_table, position: 0};
+    for _ in 0..glyph_count{
+        let c = table.read_char()?;
+        let (width, height) = (table.read_u16()? as usize, table.read_u16()? as usize);
+        let left_bearing = table.read_i16()? as isize;
+        let right_offset = table.read_u16()? as usize;
+        let glyph_ascent = table.read_i16()? as isize;
+        let method = table.read_u8()?;
+        table.read_u8()?;
+        let (offset, length) = (table.read_u32()? as usize, table.read_u32()? as usize);
+        let data = bitmaps.get(offset..offset + length).ok_or_else(|| invalid_data("glyph bitmap is cut off"))?;
+        let mut glyph = PixelFontChar::new(width, height, decode_bitmap(method, data, width * height)?);
+        glyph.left_bearing = left_bearing;
+        glyph.right_offset = right_offset;
+        glyph.ascent = glyph_ascent;
+        font.set_char(c, glyph);
+    }
+    Ok(font)
+}
+
+///How the characters of a font are laid out on an image, for from_sheet.
+#[derive(Clone, Debug)]
+pub struct SheetLayout{
+    ///Size of the cells, which are read left to right, top to bottom.
+    pub cell_width: usize,
+    pub cell_height: usize,
+    ///The characters in the cells, in order.
+    pub chars: Vec<char>,
+    ///Rows of a cell above the baseline.
+    pub baseline: usize,
+    ///Trim the empty columns left and right of every character and space them a pixel apart,
+    /// instead of using the whole cell as the advance. Empty cells become a space half a cell
+    /// wide.
+    pub proportional: bool,
+    pub size_in_pts: u32,
+}
+
+///Read a font from an image of a grid of characters, drawn dark on a light background. Grey
+/// pixels become partial coverage; transparent ones are left empty.
+pub fn from_sheet(image: &Image, layout: &SheetLayout) -> io::Result<PixelFont>{
+    let (cell_width, cell_height) = (layout.cell_width, layout.cell_height);
+    if cell_width == 0 || cell_height == 0{
+        return Err(invalid_data("cells must not be empty"))
+    }
+    let columns = image.width / cell_width;
+    if columns == 0 || layout.chars.len().div_ceil(columns) * cell_height > image.height{
+        return Err(invalid_data(&format!("the sheet has no room for {} cells of {}x{}", layout.chars.len(), cell_width, cell_height)))
+    }
+    let mut font = PixelFont::new(layout.size_in_pts, layout.baseline, cell_height.saturating_sub(layout.baseline));
+    for (i, c) in layout.chars.iter().enumerate(){
+        let (left, top) = (i % columns * cell_width, i / columns * cell_height);
+        let coverage = |x: usize, y: usize| -> u8{
+            let [r, g, b, a] = image.pixels[(top + y) * image.width + left + x];
+            let luminance = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+            ((255 - luminance) * a as u32 / 255) as u8
+        };
+        let inked: Vec<usize> = (0..cell_width).filter(|x| (0..cell_height).any(|y| coverage(*x, y) > 0)).collect();
+        let (first, last) = match (layout.proportional, inked.first(), inked.last()){
+            (false, _, _) => (0, cell_width),
+            (true, Some(first), Some(last)) => (*first, *last + 1),
+            (true, _, _) => (0, 0),
+        };
+        let width = last - first;
+        let mut pixels = Vec::with_capacity(width * cell_height);
+        for y in 0..cell_height{
+            for x in first..last{
+                pixels.push(FontPixel::new(coverage(x, y)));
+            }
+        }
+        let mut glyph = PixelFontChar::new(width, cell_height, pixels);
+        glyph.ascent = layout.baseline as isize;
+        if layout.proportional{
+            glyph.right_offset = if width == 0 {cell_width / 2} else {1};
+        }
+        font.set_char(*c, glyph);
+    }
+    Ok(font)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn glyph(width: usize, height: usize, alpha: impl Fn(usize, usize) -> u8) -> PixelFontChar{
+        let pixels = (0..width * height).map(|i| FontPixel::new(alpha(i % width, i / width))).collect();
+        PixelFontChar::new(width, height, pixels)
+    }
+
+    ///A font with a glyph for each storage method, kerning and a ligature.
+    fn test_font() -> PixelFont{
+        let mut font = PixelFont::new(12, 9, 3);
+        //a checkerboard has as many runs as pixels
+        font.set_char('b', glyph(8, 8, |x, y| if (x + y) % 2 == 0 {255} else {0}));
+        //a single dot after more than 255 empty pixels needs runs split by empty ones
+        let mut dot = glyph(30, 30, |x, y| if x == 29 && y == 29 {255} else {0});
+        dot.left_bearing = -2;
+        dot.right_offset = 3;
+        dot.ascent = 20;
+        font.set_char('r', dot);
+        font.set_char('c', glyph(4, 3, |x, y| (x * 60 + y * 10) as u8));
+        font.set_char('\u{fb01}', glyph(5, 9, |x, _| if x == 2 {255} else {0}));
+        font.set_kerning('b', 'r', -1);
+        font.set_kerning('r', 'c', 2);
+        font.add_ligature("fi", '\u{fb01}');
+        font.set_replacement_char('c');
+        font
+    }
+
+    #[test]
+    fn glyphs_use_the_smallest_storage(){
+        let font = test_font();
+        let method = |c| encode_bitmap(font.get_char(c).unwrap().get_pixels()).0;
+        assert_eq!(method('b'), BITMAP_BITS);
+        assert_eq!(method('r'), BITMAP_RUNS);
+        assert_eq!(method('c'), BITMAP_COVERAGE);
+        let (_, runs) = encode_bitmap(font.get_char('r').unwrap().get_pixels());
+        assert_eq!(runs, [255, 0, 255, 0, 255, 0, 134, 1]);
+    }
+
+    #[test]
+    fn fonts_survive_encoding(){
+        let font = test_font();
+        let decoded = decode(&encode(&font)).unwrap();
+        assert!(decoded == font);
+        assert_eq!(decoded.get_kerning('b', 'r'), -1);
+        assert_eq!(decoded.get_ligatures().get("fi"), Some(&'\u{fb01}'));
+        assert_eq!(decoded.get_replacement_char(), 'c');
+    }
+
+    #[test]
+    fn cut_off_files_are_errors(){
+        let bytes = encode(&test_font());
+        for length in 0..bytes.len(){
+            assert!(decode(&bytes[..length]).is_err(), "decoded {} of {} bytes", length, bytes.len());
+        }
+    }
+
+    #[test]
+    fn other_files_are_errors(){
+        let mut bytes = encode(&test_font());
+        bytes[0] = b'X';
+        assert!(decode(&bytes).is_err());
+        let mut bytes = encode(&test_font());
+        bytes[MAGIC.len()] = VERSION + 1;
+        assert!(decode(&bytes).is_err());
+        assert!(decode(b"STARTFONT 2.1\n").is_err());
+    }
+}
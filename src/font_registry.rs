@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use crate::pixel_font::PixelFont;
+
+///Name of the family of the built-in font.
+pub const SYSTEM_FAMILY: &str = "System";
+
+///How FontRegistry picks a font for a size it doesn't have.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SizeMatch{
+    ///Use the closest size as it is, so the text keeps crisp but is bigger or smaller than asked.
+    Nearest,
+    ///Scale (nearest neighbour) the best size to the one asked for, preferring sizes the asked one
+    /// is a whole multiple of.
+    Scaled,
+}
+
+///Collection of fonts by family and size, with a list of families that provide the characters a
+/// font lacks.
+#[derive(Clone)]
+pub struct FontRegistry{
+    families: BTreeMap<String, BTreeMap<u32, PixelFont>>,
+    fallbacks: Vec<String>,
+}
+
+impl Default for FontRegistry{
+    ///A registry holding the built-in font as the System family, which is also the fallback.
+    fn default() -> Self{
+        let mut registry = FontRegistry::new();
+        registry.add_font(SYSTEM_FAMILY, PixelFont::default());
+        registry.add_fallback(SYSTEM_FAMILY);
+        registry
+    }
+}
+
+impl FontRegistry{
+    ///Create an empty registry.
+    pub fn new() -> Self{
+        FontRegistry{
+            families: BTreeMap::new(),
+            fallbacks: vec![],
+        }
+    }
+    ///Add a font to the given family, under its own size. Replaces a font of the same size.
+    pub fn add_font(&mut self, family: &str, font: PixelFont){
+        self.families.entry(String::from(family)).or_default().insert(font.get_size(), font);
+    }
+    ///Remove a family with all of its sizes. Returns false if there was no such family.
+    pub fn remove_family(&mut self, family: &str) -> bool{
+        self.fallbacks.retain(|f| f != family);
+        self.families.remove(family).is_some()
+    }
+    ///Get the names of all families, sorted.
+    pub fn get_families(&self) -> Vec<&str>{
+        self.families.keys().map(|f| f.as_str()).collect()
+    }
+    ///Get the sizes the given family has, smallest first. Empty if the family doesn't exist.
+    pub fn get_sizes(&self, family: &str) -> Vec<u32>{
+        self.families.get(family).map(|sizes| sizes.keys().cloned().collect()).unwrap_or_default()
+    }
+    ///Add a family to the end of the fallback list. Characters missing from a font are taken from
+    /// the first fallback family that has them.
+    pub fn add_fallback(&mut self, family: &str){
+        if !self.fallbacks.iter().any(|f| f == family){
+            self.fallbacks.push(String::from(family));
+        }
+    }
+    ///Get the fallback families, in the order they are searched.
+    pub fn get_fallbacks(&self) -> &[String]{
+        &self.fallbacks
+    }
+    ///Get the font of the given family in exactly the given size, if it was added.
+    pub fn get_exact(&self, family: &str, size: u32) -> Option<&PixelFont>{
+        self.families.get(family)?.get(&size)
+    }
+    ///Get the font of the given family whose size is closest to the given one. On a tie the larger
+    /// size wins.
+    pub fn get_nearest(&self, family: &str, size: u32) -> Option<&PixelFont>{
+        self.families.get(family)?.iter()
+            .min_by_key(|(s, _)| (s.abs_diff(size), u32::MAX - **s))
+            .map(|(_, font)| font)
+    }
+    ///Get the font of the given family in the given size, matching sizes the family lacks as
+    /// asked. Characters the font lacks are not filled in, see get_font for that.
+    pub fn get_family_font(&self, family: &str, size: u32, size_match: SizeMatch) -> Option<PixelFont>{
+        if let Some(font) = self.get_exact(family, size){
+            return Some(font.clone())
+        }
+        match size_match{
+            SizeMatch::Nearest => self.get_nearest(family, size).cloned(),
+            SizeMatch::Scaled => {
+                let sizes = self.families.get(family)?;
+                //a whole factor scales every pixel into a square, otherwise take the closest size
+                let source = sizes.iter().rev()
+                    .find(|(s, _)| size.is_multiple_of(**s))
+                    .map(|(_, font)| font)
+                    .or_else(|| self.get_nearest(family, size))?;
+                Some(source.scaled(size))
+            }
+        }
+    }
+    ///Get the font of the given family in the given size, with the characters it lacks taken from
+    /// the fallback families (matched to the same size). Falls back entirely if the family doesn't
+    /// exist; returns None only if neither it nor any fallback does.
+    pub fn get_font(&self, family: &str, size: u32, size_match: SizeMatch) -> Option<PixelFont>{
+        let mut families = self.fallbacks.iter().map(|f| f.as_str()).filter(|f| *f != family);
+        let mut font = match self.get_family_font(family, size, size_match){
+            Some(font) => font,
+            None => families.by_ref().find_map(|f| self.get_family_font(f, size, size_match))?
+        };
+        for fallback in families{
+            if let Some(fallback) = self.get_family_font(fallback, size, size_match){
+                font.add_missing_chars(&fallback);
+            }
+        }
+        Some(font)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::pixel_font::{FontPixel, PixelFontChar};
+
+    ///A font of the given size with a square glyph as tall as the size for each given character.
+    fn font(size: u32, chars: &str) -> PixelFont{
+        let mut font = PixelFont::new(size, size as usize, 0);
+        let side = size as usize;
+        for c in chars.chars(){
+            font.set_char(c, PixelFontChar::new(side, side, vec![FontPixel::new(255u8); side * side]));
+        }
+        font
+    }
+
+    fn registry() -> FontRegistry{
+        let mut registry = FontRegistry::new();
+        for size in [9, 12, 15]{
+            registry.add_font("Sans", font(size, "ab"));
+        }
+        registry.add_font("Symbols", font(12, "b*"));
+        registry
+    }
+
+    #[test]
+    fn nearest_size_prefers_the_larger_on_a_tie(){
+        let registry = registry();
+        assert_eq!(registry.get_sizes("Sans"), [9, 12, 15]);
+        assert_eq!(registry.get_nearest("Sans", 10).unwrap().get_size(), 9);
+        assert_eq!(registry.get_nearest("Sans", 14).unwrap().get_size(), 15);
+        assert_eq!(registry.get_nearest("Sans", 40).unwrap().get_size(), 15);
+        //10 and 14 are both 2 away from 12
+        let mut registry = registry;
+        registry.add_font("Serif", font(10, "a"));
+        registry.add_font("Serif", font(14, "a"));
+        assert_eq!(registry.get_nearest("Serif", 12).unwrap().get_size(), 14);
+        assert!(registry.get_nearest("Mono", 12).is_none());
+    }
+
+    #[test]
+    fn scaling_prefers_whole_factors(){
+        let registry = registry();
+        //30 is a multiple of 15, the nearest size that divides it
+        let scaled = registry.get_family_font("Sans", 30, SizeMatch::Scaled).unwrap();
+        assert_eq!(scaled.get_size(), 30);
+        assert_eq!(scaled.get_char('a').unwrap().get_width(), 30);
+        //27 is only a multiple of 9, even though 15 is closer
+        let scaled = registry.get_family_font("Sans", 27, SizeMatch::Scaled).unwrap();
+        assert_eq!(scaled.get_ascent(), 27);
+        //without a whole factor the nearest size is scaled
+        let scaled = registry.get_family_font("Sans", 16, SizeMatch::Scaled).unwrap();
+        assert_eq!(scaled.get_char('a').unwrap().get_width(), 16);
+        //matching the nearest size leaves the font as it is
+        assert_eq!(registry.get_family_font("Sans", 16, SizeMatch::Nearest).unwrap().get_size(), 15);
+    }
+
+    #[test]
+    fn missing_characters_come_from_the_fallbacks(){
+        let mut registry = registry();
+        registry.add_fallback("Symbols");
+        let font = registry.get_font("Sans", 12, SizeMatch::Nearest).unwrap();
+        assert!(font.has_char('a') && font.has_char('*'));
+        //characters the font has are kept
+        assert_eq!(font.get_char('b').unwrap().get_width(), 12);
+        //the fallback is matched to the size asked for
+        let font = registry.get_font("Sans", 24, SizeMatch::Scaled).unwrap();
+        assert_eq!(font.get_char('*').unwrap().get_width(), 24);
+        //an unknown family falls back entirely
+        let font = registry.get_font("Mono", 12, SizeMatch::Nearest).unwrap();
+        assert!(font.has_char('*') && !font.has_char('a'));
+        registry.remove_family("Symbols");
+        assert!(registry.get_font("Mono", 12, SizeMatch::Nearest).is_none());
+    }
+}
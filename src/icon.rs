@@ -0,0 +1,327 @@
+use std::io;
+use std::path::Path;
+use crate::image::{invalid_data, Image};
+use crate::widget;
+
+///Size of small icons, as used in lists and menus.
+pub const SMALL_ICON_SIZE: usize = 16;
+///Size of large icons, as used on the desktop and in alerts.
+pub const LARGE_ICON_SIZE: usize = 32;
+
+const FOLDER_SMALL: [&str; SMALL_ICON_SIZE] = [
+    "                ",
+    "                ",
+    "                ",
+    " #####          ",
+    "#.....#         ",
+    "#......#######  ",
+    "#.............# ",
+    "############### ",
+    "#.............# ",
+    "#.............# ",
+    "#.............# ",
+    "#.............# ",
+    "#.............# ",
+    "#.............# ",
+    "############### ",
+    "                ",
+];
+
+const DOCUMENT_SMALL: [&str; SMALL_ICON_SIZE] = [
+    "  #########     ",
+    "  #.......##    ",
+    "  #.......#.#   ",
+    "  #.......#..#  ",
+    "  #.......####  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  #..........#  ",
+    "  ############  ",
+    "                ",
+];
+
+const FOLDER_LARGE: [&str; LARGE_ICON_SIZE] = [
+    "                                ",
+    "                                ",
+    "                                ",
+    "                                ",
+    "                                ",
+    "                                ",
+    "  ##########                    ",
+    " #..........#                   ",
+    " #...........#################  ",
+    " #............................# ",
+    " #............................# ",
+    " ############################## ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " #............................# ",
+    " ############################## ",
+    "                                ",
+    "                                ",
+    "                                ",
+    "                                ",
+];
+
+const DOCUMENT_LARGE: [&str; LARGE_ICON_SIZE] = [
+    "                                ",
+    "     ###############            ",
+    "     #.............##           ",
+    "     #.............#.#          ",
+    "     #.............#..#         ",
+    "     #.............#...#        ",
+    "     #.............#....#       ",
+    "     #.............#.....#      ",
+    "     #.............########     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     #....................#     ",
+    "     ######################     ",
+    "                                ",
+];
+
+///How an icon is drawn.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum IconState{
+    Normal,
+    ///Selected icons, like those on the desktop, are drawn darkened.
+    Selected,
+    ///Highlighted icons, like one being dropped on or under the mouse in a menu, are drawn
+    /// inverted.
+    Highlighted,
+}
+
+///A square icon image of one size, with a mask telling which pixels belong to the icon, like the
+/// image and mask planes of a classic ICN# resource.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct IconImage{
+    size: usize,
+    pixels: Vec<[u8; 4]>,
+    mask: Vec<bool>,
+}
+
+impl IconImage{
+    ///Create an icon image from its pixels and mask, both size*size long. Panics if they aren't.
+    pub fn new(size: usize, pixels: Vec<[u8; 4]>, mask: Vec<bool>) -> Self{
+        assert!(pixels.len() == size * size && mask.len() == size * size, "icon data doesn't match the icon size");
+        IconImage{
+            size,
+            pixels,
+            mask,
+        }
+    }
+    ///Create an icon image from rows of text: '#' is black, '.' is white and anything else is
+    /// outside the mask. The size is the number of rows.
+    pub fn from_bitmap(rows: &[&str]) -> Self{
+        let size = rows.len();
+        let mut pixels = Vec::with_capacity(size * size);
+        let mut mask = Vec::with_capacity(size * size);
+        for row in rows{
+            let mut chars = row.chars();
+            for _ in 0..size{
+                let (pixel, inside) = match chars.next(){
+                    Some('#') => ([0u8, 0u8, 0u8, 255u8], true),
+                    Some('.') => ([255u8; 4], true),
+                    _ => ([255u8; 4], false)
+                };
+                pixels.push(pixel);
+                mask.push(inside);
+            }
+        }
+        IconImage::new(size, pixels, mask)
+    }
+    ///Create an icon image from a decoded image file. Square images are used as they are, with
+    /// the mask taken from the alpha channel if there is one, or else made from everything not
+    /// reachable from the edges through white pixels. Images twice as high as wide hold the icon
+    /// in the top half and the mask in the bottom half, black meaning inside, like ICN#.
+    pub fn from_image(image: &Image) -> io::Result<Self>{
+        let size = image.width;
+        if size == 0{
+            return Err(invalid_data("icon image is empty"))
+        }
+        if image.height == size * 2{
+            let (pixels, mask_pixels) = image.pixels.split_at(size * size);
+            let mask = mask_pixels.iter().map(|p| p[3] > 127 && (p[0] as u32 + p[1] as u32 + p[2] as u32) < 384).collect();
+            return Ok(IconImage::new(size, pixels.to_vec(), mask))
+        }
+        if image.height != size{
+            return Err(invalid_data("icon image must be square, or twice as high as wide with a mask"))
+        }
+        let mask = if image.pixels.iter().any(|p| p[3] < 255){
+            image.pixels.iter().map(|p| p[3] > 127).collect()
+        } else {
+            silhouette_mask(&image.pixels, size)
+        };
+        let pixels = image.pixels.iter().map(|p| [p[0], p[1], p[2], 255u8]).collect();
+        Ok(IconImage::new(size, pixels, mask))
+    }
+    ///Get the width and height of the image.
+    pub fn get_size(&self) -> usize{
+        self.size
+    }
+    ///Get the mask, true for the pixels that belong to the icon.
+    pub fn get_mask(&self) -> &[bool]{
+        &self.mask
+    }
+    ///Scale the image to another size, nearest neighbour.
+    pub fn scaled(&self, size: usize) -> IconImage{
+        if size == self.size{
+            return self.clone()
+        }
+        let source = |i: usize| (i / size * self.size / size) * self.size + (i % size) * self.size / size;
+        IconImage{
+            size,
+            pixels: (0..size * size).map(|i| self.pixels[source(i)]).collect(),
+            mask: (0..size * size).map(|i| self.mask[source(i)]).collect(),
+        }
+    }
+    ///Render the icon in the given state. Pixels outside the mask are fully transparent, so the
+    /// result should be drawn with widget::draw_masked_at.
+    pub fn render(&self, state: IconState) -> Vec<[u8; 4]>{
+        self.pixels.iter().zip(self.mask.iter()).map(|(p, inside)| {
+            if !inside{
+                return [0u8; 4]
+            }
+            match state{
+                IconState::Normal => *p,
+                IconState::Selected => [p[0] / 2, p[1] / 2, p[2] / 2, 255u8],
+                IconState::Highlighted => [255 - p[0], 255 - p[1], 255 - p[2], 255u8],
+            }
+        }).collect()
+    }
+}
+
+///Make a mask of everything that can't be reached from the edges of the image through white
+/// pixels, so holes in an outline drawn in black stay part of the icon.
+fn silhouette_mask(pixels: &[[u8; 4]], size: usize) -> Vec<bool>{
+    let is_white = |i: usize| pixels[i][0] == 255 && pixels[i][1] == 255 && pixels[i][2] == 255;
+    let mut outside = vec![false; size * size];
+    let mut stack: Vec<usize> = (0..size * size)
+        .filter(|i| i / size == 0 || i / size == size - 1 || i % size == 0 || i % size == size - 1)
+        .collect();
+    while let Some(i) = stack.pop(){
+        if outside[i] || !is_white(i){
+            continue
+        }
+        outside[i] = true;
+        let (x, y) = (i % size, i / size);
+        if x > 0 {stack.push(i - 1)}
+        if x + 1 < size {stack.push(i + 1)}
+        if y > 0 {stack.push(i - size)}
+        if y + 1 < size {stack.push(i + size)}
+    }
+    outside.iter().map(|o| !o).collect()
+}
+
+///An icon family: the same icon at the small and large sizes. A missing size is scaled from the
+/// other one.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Icon{
+    small: Option<IconImage>,
+    large: Option<IconImage>,
+}
+
+impl Icon{
+    ///Create an icon without any images.
+    pub fn new() -> Self{
+        Icon::default()
+    }
+    ///Create an icon from the images of one or both sizes.
+    pub fn from_images(images: Vec<IconImage>) -> io::Result<Self>{
+        let mut icon = Icon::new();
+        for image in images{
+            icon.set_image(image)?;
+        }
+        Ok(icon)
+    }
+    ///Load an icon from image files, one per size (see IconImage::from_image).
+    pub fn load(paths: &[&Path]) -> io::Result<Self>{
+        let mut icon = Icon::new();
+        for path in paths{
+            icon.set_image(IconImage::from_image(&Image::load(path)?)?)?;
+        }
+        Ok(icon)
+    }
+    ///Set the image of the size it has, which must be SMALL_ICON_SIZE or LARGE_ICON_SIZE.
+    pub fn set_image(&mut self, image: IconImage) -> io::Result<()>{
+        match image.get_size(){
+            SMALL_ICON_SIZE => self.small = Some(image),
+            LARGE_ICON_SIZE => self.large = Some(image),
+            _ => return Err(invalid_data("icons must be 16x16 or 32x32"))
+        }
+        Ok(())
+    }
+    ///Get the image of the given size, scaled from the other size if it is missing. Returns None
+    /// if the icon has no images.
+    pub fn get_image(&self, size: usize) -> Option<IconImage>{
+        let (exact, other) = if size <= SMALL_ICON_SIZE {(&self.small, &self.large)} else {(&self.large, &self.small)};
+        exact.as_ref().or(other.as_ref()).map(|i| i.scaled(size))
+    }
+    ///Render the icon at the given size and state, transparent outside the mask.
+    pub fn render(&self, size: usize, state: IconState) -> Option<Vec<[u8; 4]>>{
+        self.get_image(size).map(|i| i.render(state))
+    }
+    ///Draw the icon onto a buffer of the given (width, height) with its top left corner at the given
+    /// (x, y) position, honoring the mask. Parts outside the buffer are cut off.
+    pub fn draw_onto(
+        &self,
+        buf: Vec<[u8; 4]>, buf_size: (usize, usize),
+        position: (isize, isize),
+        size: usize, state: IconState
+    ) -> Vec<[u8; 4]>{
+        match self.render(size, state){
+            Some(icon) => widget::draw_masked_at(position, buf, buf_size, &icon, (size, size)),
+            None => buf
+        }
+    }
+    ///The built-in folder icon.
+    pub fn folder() -> Self{
+        Icon{
+            small: Some(IconImage::from_bitmap(&FOLDER_SMALL)),
+            large: Some(IconImage::from_bitmap(&FOLDER_LARGE)),
+        }
+    }
+    ///The built-in icon of documents and other files.
+    pub fn document() -> Self{
+        Icon{
+            small: Some(IconImage::from_bitmap(&DOCUMENT_SMALL)),
+            large: Some(IconImage::from_bitmap(&DOCUMENT_LARGE)),
+        }
+    }
+}
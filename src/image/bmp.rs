@@ -0,0 +1,201 @@
+use std::io;
+use crate::image::{invalid_data, Image};
+
+///Size of the BITMAPFILEHEADER every BMP file starts with.
+const FILE_HEADER_SIZE: usize = 14;
+///Size of the old OS/2 BITMAPCOREHEADER, which has 16-bit sizes and 3-byte palette entries.
+const CORE_HEADER_SIZE: usize = 12;
+///Size of the BITMAPINFOHEADER. Later versions are longer and start the same way.
+const INFO_HEADER_SIZE: usize = 40;
+///Compression types: none, and none with the colour channels given by bit masks.
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+fn read_u16(bytes: &[u8], offset: usize) -> io::Result<u16>{
+    bytes.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid_data("BMP file is cut off"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32>{
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid_data("BMP file is cut off"))
+}
+
+///Extracts a colour channel from a pixel value with a bit mask, scaled to 8 bits.
+#[derive(Copy, Clone)]
+struct ChannelMask{
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl ChannelMask{
+    fn new(mask: u32) -> Self{
+        let shift = if mask == 0 {0} else {mask.trailing_zeros()};
+        ChannelMask{
+            mask,
+            shift,
+            max: if mask == 0 {0} else {((1u64 << (mask >> shift).count_ones()) - 1) as u32},
+        }
+    }
+    ///Get the channel of the given pixel value, or the default for channels missing in the file.
+    fn get(&self, value: u32, default: u8) -> u8{
+        if self.max == 0{
+            return default
+        }
+        (((value & self.mask) >> self.shift) as u64 * 255 / self.max as u64) as u8
+    }
+}
+
+///Decode an uncompressed BMP image with 1, 4, 8, 16, 24 or 32 bits per pixel.
+pub fn decode_bmp(bytes: &[u8]) -> io::Result<Image>{
+    if !bytes.starts_with(b"BM"){
+        return Err(invalid_data("not a BMP file"))
+    }
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let header_size = read_u32(bytes, FILE_HEADER_SIZE)? as usize;
+    let (width, height, bits, compression) = if header_size == CORE_HEADER_SIZE{
+        (read_u16(bytes, 18)? as i64, read_u16(bytes, 20)? as i64, read_u16(bytes, 24)?, BI_RGB)
+    } else if header_size >= INFO_HEADER_SIZE{
+        (read_u32(bytes, 18)? as i32 as i64, read_u32(bytes, 22)? as i32 as i64, read_u16(bytes, 28)?, read_u32(bytes, 30)?)
+    } else {
+        return Err(invalid_data("unsupported BMP header"))
+    };
+    //a negative height means the rows are stored top-down instead of bottom-up
+    let top_down = height < 0;
+    let (width, height) = (width.max(0) as usize, height.unsigned_abs() as usize);
+    let count = width.checked_mul(height).ok_or_else(|| invalid_data("BMP image too large"))?;
+    if !matches!(bits, 1 | 4 | 8 | 16 | 24 | 32){
+        return Err(invalid_data("unsupported BMP bit depth"))
+    }
+    if compression != BI_RGB && !(compression == BI_BITFIELDS && (bits == 16 || bits == 32)){
+        return Err(invalid_data("compressed BMP files are not supported"))
+    }
+    //the bit masks follow the info header, or are part of the longer header versions
+    let masks_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE;
+    let masks = if compression == BI_BITFIELDS{
+        let alpha = if header_size >= 56 {read_u32(bytes, masks_offset + 12)?} else {0};
+        [read_u32(bytes, masks_offset)?, read_u32(bytes, masks_offset + 4)?, read_u32(bytes, masks_offset + 8)?, alpha]
+    } else if bits == 16{
+        [0x7c00, 0x03e0, 0x001f, 0]
+    } else {
+        [0x00ff0000, 0x0000ff00, 0x000000ff, 0]
+    };
+    let masks = masks.map(ChannelMask::new);
+    let palette = if bits <= 8{
+        let entry_size = if header_size == CORE_HEADER_SIZE {3} else {4};
+        let mut palette_offset = FILE_HEADER_SIZE + header_size;
+        if header_size == INFO_HEADER_SIZE && compression == BI_BITFIELDS{
+            palette_offset += 12;
+        }
+        let used = if header_size >= INFO_HEADER_SIZE {read_u32(bytes, 46)? as usize} else {0};
+        let entries = if used == 0 || used > 1 << bits {1 << bits} else {used};
+        (0..entries)
+            .map(|i| bytes.get(palette_offset + i * entry_size..palette_offset + i * entry_size + 3)
+                .map(|b| [b[2], b[1], b[0], 255u8]))
+            .collect::<Option<Vec<[u8; 4]>>>()
+            .ok_or_else(|| invalid_data("BMP palette is cut off"))?
+    } else {
+        vec![]
+    };
+    //rows are padded to a multiple of four bytes
+    let row_bytes = width.checked_mul(bits as usize).ok_or_else(|| invalid_data("BMP image too large"))?.div_ceil(32) * 4;
+    //checked before making room for the pixels, the size fields could be anything
+    let data_size = row_bytes.checked_mul(height).ok_or_else(|| invalid_data("BMP image too large"))?;
+    if bytes.len().saturating_sub(data_offset) < data_size{
+        return Err(invalid_data("BMP image data is cut off"))
+    }
+    let mut pixels = Vec::with_capacity(count);
+    for y in 0..height{
+        let stored_row = if top_down {y} else {height - 1 - y};
+        let start = data_offset + stored_row * row_bytes;
+        let row = bytes.get(start..start + row_bytes).ok_or_else(|| invalid_data("BMP image data is cut off"))?;
+        for x in 0..width{
+            pixels.push(match bits{
+                1 | 4 | 8 => {
+                    let bit = x * bits as usize;
+                    let index = (row[bit / 8] >> (8 - bits as usize - bit % 8)) & ((1u16 << bits) - 1) as u8;
+                    palette.get(index as usize).copied().unwrap_or([0u8, 0u8, 0u8, 255u8])
+                },
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255u8],
+                _ => {
+                    let value = if bits == 16{
+                        u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]) as u32
+                    } else {
+                        u32::from_le_bytes([row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]])
+                    };
+                    [masks[0].get(value, 0), masks[1].get(value, 0), masks[2].get(value, 0), masks[3].get(value, 255)]
+                }
+            });
+        }
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///Build a BMP file with a BITMAPINFOHEADER around the given palette and pixel data.
+    fn bmp(width: i32, height: i32, bits: u16, palette: &[[u8; 4]], data: &[u8]) -> Vec<u8>{
+        let data_offset = FILE_HEADER_SIZE + INFO_HEADER_SIZE + palette.len() * 4;
+        let mut out = b"BM".to_vec();
+        out.extend(((data_offset + data.len()) as u32).to_le_bytes());
+        out.extend([0u8; 4]);
+        out.extend((data_offset as u32).to_le_bytes());
+        out.extend((INFO_HEADER_SIZE as u32).to_le_bytes());
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.extend(1u16.to_le_bytes());
+        out.extend(bits.to_le_bytes());
+        out.extend(BI_RGB.to_le_bytes());
+        out.extend([0u8; 12]);
+        out.extend((palette.len() as u32).to_le_bytes());
+        out.extend([0u8; 4]);
+        for entry in palette{
+            out.extend([entry[2], entry[1], entry[0], 0u8]);
+        }
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[test]
+    fn true_colour_rows_are_bottom_up_and_padded(){
+        let data = [
+            1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 0u8, 0u8,
+            7u8, 8u8, 9u8, 10u8, 11u8, 12u8, 0u8, 0u8,
+        ];
+        let image = decode_bmp(&bmp(2, 2, 24, &[], &data)).unwrap();
+        assert_eq!(image.pixels, [[9u8, 8u8, 7u8, 255u8], [12u8, 11u8, 10u8, 255u8], [3u8, 2u8, 1u8, 255u8], [6u8, 5u8, 4u8, 255u8]]);
+        //a negative height stores the rows top-down
+        let image = decode_bmp(&bmp(2, -2, 24, &[], &data)).unwrap();
+        assert_eq!(image.pixels[0], [3u8, 2u8, 1u8, 255u8]);
+    }
+
+    #[test]
+    fn palette_images_are_decoded(){
+        let palette = [[255u8, 0u8, 0u8, 255u8], [0u8, 0u8, 255u8, 255u8]];
+        let image = decode_bmp(&bmp(3, 1, 1, &palette, &[0b0100_0000, 0u8, 0u8, 0u8])).unwrap();
+        assert_eq!(image.pixels, [palette[0], palette[1], palette[0]]);
+        let image = decode_bmp(&bmp(2, 1, 8, &palette, &[1u8, 0u8, 0u8, 0u8])).unwrap();
+        assert_eq!(image.pixels, [palette[1], palette[0]]);
+    }
+
+    #[test]
+    fn cut_off_files_are_errors(){
+        let file = bmp(2, 2, 24, &[], &[0u8; 16]);
+        for length in 0..file.len(){
+            assert!(decode_bmp(&file[..length]).is_err(), "{} bytes", length);
+        }
+    }
+
+    #[test]
+    fn oversized_headers_are_errors(){
+        //the sizes must be checked against the data before making room for the pixels
+        assert!(decode_bmp(&bmp(0x7fffffff, 0x7fffffff, 24, &[], &[])).is_err());
+        assert!(decode_bmp(&bmp(0x7fffffff, -0x7fffffff, 32, &[], &[0u8; 64])).is_err());
+        assert!(decode_bmp(&bmp(100000, 100000, 1, &[[0u8; 4]; 2], &[])).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::image::inflate::{adler32, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+///Size of the window matches are searched in, the most deflate allows.
+const WINDOW_SIZE: usize = 32768;
+///Shortest and longest match deflate can encode.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+///Number of bits of the hash of the three bytes a match starts with.
+const HASH_BITS: usize = 15;
+
+///Writes the bits of a deflate stream, least significant bit of each byte first.
+struct BitWriter{
+    out: Vec<u8>,
+    ///Bits not yet written, and how many of them there are.
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter{
+    ///Write a number in the given number of bits, least significant bit first.
+    fn write_bits(&mut self, value: u32, count: u32){
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8{
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+    ///Write a Huffman code, which is stored most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32){
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+    ///Write a symbol of the fixed literal/length code.
+    fn write_literal_length(&mut self, symbol: u32){
+        match symbol{
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+    ///Write the length and distance of a match.
+    fn write_match(&mut self, length: usize, distance: usize){
+        let code = LENGTH_BASE.iter().rposition(|base| *base as usize <= length).unwrap();
+        self.write_literal_length(257 + code as u32);
+        self.write_bits((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+        let code = DISTANCE_BASE.iter().rposition(|base| *base as usize <= distance).unwrap();
+        self.write_code(code as u32, 5);
+        self.write_bits((distance - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+    }
+    fn finish(mut self) -> Vec<u8>{
+        if self.count > 0{
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+fn hash(data: &[u8], i: usize) -> usize{
+    let key = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+    (key.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+}
+
+///Compress data into a raw deflate stream (RFC 1951), as a single block with the fixed Huffman
+/// codes. Matches are found greedily with the last position of every hash, which is quick and
+/// does well on the long runs of images with few colours.
+pub fn deflate(data: &[u8]) -> Vec<u8>{
+    let mut writer = BitWriter{out: Vec::with_capacity(data.len() / 4), bits: 0, count: 0};
+    //final block, fixed codes
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+    let mut i = 0;
+    while i < data.len(){
+        let mut length = 0;
+        let mut distance = 0;
+        if i + MIN_MATCH <= data.len(){
+            let h = hash(data, i);
+            let candidate = last_seen[h];
+            last_seen[h] = i;
+            if candidate != usize::MAX && i - candidate <= WINDOW_SIZE{
+                let max = MAX_MATCH.min(data.len() - i);
+                while length < max && data[candidate + length] == data[i + length]{
+                    length += 1;
+                }
+                distance = i - candidate;
+            }
+        }
+        if length >= MIN_MATCH{
+            writer.write_match(length, distance);
+            //remember the positions inside the match too, for later matches
+            for j in i + 1..(i + length).min(data.len().saturating_sub(MIN_MATCH - 1)){
+                last_seen[hash(data, j)] = j;
+            }
+            i += length;
+        } else {
+            writer.write_literal_length(data[i] as u32);
+            i += 1;
+        }
+    }
+    writer.write_literal_length(256);
+    writer.finish()
+}
+
+///Compress data into a zlib stream (RFC 1950).
+pub fn zlib_compress(data: &[u8]) -> Vec<u8>{
+    //deflate with a 32K window, default level; the header is a multiple of 31
+    let mut out = vec![0x78u8, 0x9cu8];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
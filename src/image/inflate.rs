@@ -0,0 +1,234 @@
+use std::io;
+use crate::image::invalid_data;
+
+///Base lengths and extra bits of the length codes 257..285.
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+///Base distances and extra bits of the distance codes 0..29.
+pub(super) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+pub(super) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+///Order the code length code lengths of dynamic blocks are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+///Longest Huffman code allowed by deflate.
+const MAX_CODE_LENGTH: usize = 15;
+
+///Reads the bits of a deflate stream, least significant bit of each byte first.
+struct BitReader<'a>{
+    data: &'a [u8],
+    ///Position in bits.
+    position: usize,
+}
+
+impl<'a> BitReader<'a>{
+    fn read_bit(&mut self) -> io::Result<u32>{
+        let byte = *self.data.get(self.position / 8).ok_or_else(|| invalid_data("compressed data is cut off"))?;
+        let bit = (byte >> (self.position % 8)) & 1;
+        self.position += 1;
+        Ok(bit as u32)
+    }
+    ///Read a number of the given number of bits, least significant bit first.
+    fn read_bits(&mut self, count: u32) -> io::Result<u32>{
+        let mut value = 0;
+        for i in 0..count{
+            value |= self.read_bit()? << i;
+        }
+        Ok(value)
+    }
+    ///Skip to the start of the next byte.
+    fn align(&mut self){
+        self.position = self.position.div_ceil(8) * 8;
+    }
+    ///Decode a symbol with the given Huffman code.
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16>{
+        //canonical codes of each length are consecutive, starting right after the codes one bit shorter
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..=MAX_CODE_LENGTH{
+            code |= self.read_bit()? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - first < count{
+                return Ok(huffman.symbols[(index + code - first) as usize])
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid Huffman code in compressed data"))
+    }
+}
+
+///A canonical Huffman code: the number of codes of each length and the symbols ordered by code.
+struct Huffman{
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman{
+    ///Build the code from the code length of every symbol, 0 meaning the symbol is not used.
+    fn new(lengths: &[u8]) -> Self{
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in lengths{
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH{
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate(){
+            if length != 0{
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman{
+            counts,
+            symbols,
+        }
+    }
+}
+
+///Decompress a raw deflate stream (RFC 1951).
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>>{
+    let mut reader = BitReader{data, position: 0};
+    let mut out = Vec::with_capacity(data.len() * 4);
+    loop{
+        let last = reader.read_bits(1)? == 1;
+        match reader.read_bits(2)?{
+            0 => {
+                reader.align();
+                let start = reader.position / 8;
+                let header = data.get(start..start + 4).ok_or_else(|| invalid_data("compressed data is cut off"))?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize{
+                    return Err(invalid_data("invalid stored block in compressed data"))
+                }
+                let block = data.get(start + 4..start + 4 + length).ok_or_else(|| invalid_data("compressed data is cut off"))?;
+                out.extend_from_slice(block);
+                reader.position = (start + 4 + length) * 8;
+            },
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (symbol, length) in lengths.iter_mut().enumerate(){
+                    *length = match symbol{
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8
+                    };
+                }
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5u8; 30]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            },
+            _ => return Err(invalid_data("invalid block type in compressed data"))
+        }
+        if last{
+            return Ok(out)
+        }
+    }
+}
+
+///Read the literal/length and distance codes at the start of a dynamic block.
+fn read_dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)>{
+    let literal_count = reader.read_bits(5)? as usize + 257;
+    let distance_count = reader.read_bits(5)? as usize + 1;
+    let code_length_count = reader.read_bits(4)? as usize + 4;
+    let mut code_length_lengths = [0u8; 19];
+    for &symbol in CODE_LENGTH_ORDER.iter().take(code_length_count){
+        code_length_lengths[symbol] = reader.read_bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths);
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count{
+        let (value, repeat) = match reader.decode(&code_lengths)?{
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or_else(|| invalid_data("invalid code lengths in compressed data"))?;
+                (previous, 3 + reader.read_bits(2)?)
+            },
+            17 => (0, 3 + reader.read_bits(3)?),
+            _ => (0, 11 + reader.read_bits(7)?)
+        };
+        for _ in 0..repeat{
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count{
+        return Err(invalid_data("invalid code lengths in compressed data"))
+    }
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+///Decode the symbols of a compressed block up to its end of block symbol.
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> io::Result<()>{
+    loop{
+        let symbol = reader.decode(literals)? as usize;
+        match symbol{
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length = LENGTH_BASE[index] as usize + reader.read_bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = reader.decode(distances)? as usize;
+                if index >= DISTANCE_BASE.len(){
+                    return Err(invalid_data("invalid distance in compressed data"))
+                }
+                let distance = DISTANCE_BASE[index] as usize + reader.read_bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > out.len(){
+                    return Err(invalid_data("distance too far back in compressed data"))
+                }
+                //the copy may overlap the bytes it produces, so it goes byte by byte
+                let start = out.len() - distance;
+                for i in 0..length{
+                    out.push(out[start + i]);
+                }
+            },
+            _ => return Err(invalid_data("invalid length in compressed data"))
+        }
+    }
+}
+
+///Decompress a zlib stream (RFC 1950), checking its header and checksum.
+pub fn zlib_decompress(data: &[u8]) -> io::Result<Vec<u8>>{
+    if data.len() < 6{
+        return Err(invalid_data("compressed data is cut off"))
+    }
+    let (method, flags) = (data[0], data[1]);
+    if method & 0x0f != 8 || !(method as u16 * 256 + flags as u16).is_multiple_of(31){
+        return Err(invalid_data("invalid zlib header"))
+    }
+    if flags & 0x20 != 0{
+        return Err(invalid_data("zlib preset dictionaries are not supported"))
+    }
+    let out = inflate(&data[2..])?;
+    let expected = u32::from_be_bytes([data[data.len() - 4], data[data.len() - 3], data[data.len() - 2], data[data.len() - 1]]);
+    if adler32(&out) != expected{
+        return Err(invalid_data("zlib checksum mismatch"))
+    }
+    Ok(out)
+}
+
+///Compute the Adler-32 checksum of zlib streams.
+pub(super) fn adler32(data: &[u8]) -> u32{
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552){
+        for &byte in chunk{
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
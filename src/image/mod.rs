@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+pub use bmp::decode_bmp;
+pub use png::{decode_png, encode_png};
+pub use pnm::decode_pnm;
+
+mod bmp;
+mod deflate;
+mod inflate;
+mod png;
+mod pnm;
+
+///Black and white, for the 1-bit look.
+pub const MONO_PALETTE: [[u8; 4]; 2] = [[0u8, 0u8, 0u8, 255u8], [255u8, 255u8, 255u8, 255u8]];
+///The 16 colours of the classic Macintosh system palette.
+pub const CLASSIC_PALETTE: [[u8; 4]; 16] = [
+    [255u8, 255u8, 255u8, 255u8],
+    [252u8, 243u8, 5u8, 255u8],
+    [255u8, 100u8, 2u8, 255u8],
+    [221u8, 8u8, 6u8, 255u8],
+    [242u8, 8u8, 132u8, 255u8],
+    [70u8, 0u8, 165u8, 255u8],
+    [0u8, 0u8, 212u8, 255u8],
+    [2u8, 171u8, 234u8, 255u8],
+    [31u8, 183u8, 20u8, 255u8],
+    [0u8, 100u8, 18u8, 255u8],
+    [86u8, 44u8, 5u8, 255u8],
+    [144u8, 113u8, 58u8, 255u8],
+    [192u8, 192u8, 192u8, 255u8],
+    [128u8, 128u8, 128u8, 255u8],
+    [64u8, 64u8, 64u8, 255u8],
+    [0u8, 0u8, 0u8, 255u8],
+];
+
+///An image decoded from a file, as RGBA pixels row by row.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Image{
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+impl Image{
+    ///Create an image of the given size from RGBA pixels. Panics if the number of pixels doesn't
+    /// match the size.
+    pub fn new(width: usize, height: usize, pixels: Vec<[u8; 4]>) -> Self{
+        assert_eq!(pixels.len(), width * height, "pixel count doesn't match the image size");
+        Image{
+            width,
+            height,
+            pixels,
+        }
+    }
+    ///Read and decode an image file. The format is recognized by the contents, not the file name.
+    pub fn load(path: &Path) -> io::Result<Image>{
+        Image::decode(&fs::read(path)?)
+    }
+    ///Decode an image file held in memory. Supported formats: PBM, PGM and PPM (plain and raw),
+    /// uncompressed BMP and PNG.
+    pub fn decode(bytes: &[u8]) -> io::Result<Image>{
+        match bytes{
+            [b'P', b'1'..=b'6', ..] => decode_pnm(bytes),
+            [b'B', b'M', ..] => decode_bmp(bytes),
+            _ if bytes.starts_with(&png::PNG_SIGNATURE) => decode_png(bytes),
+            _ => Err(invalid_data("unknown image format"))
+        }
+    }
+    ///Write the image to a PNG file.
+    pub fn save_png(&self, path: &Path) -> io::Result<()>{
+        fs::write(path, encode_png(self))
+    }
+    ///Scale the image to the given size, nearest neighbour.
+    pub fn scaled(&self, width: usize, height: usize) -> Image{
+        if width == self.width && height == self.height{
+            return self.clone()
+        }
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height{
+            let row = y * self.height / height * self.width;
+            for x in 0..width{
+                pixels.push(self.pixels[row + x * self.width / width]);
+            }
+        }
+        Image::new(width, height, pixels)
+    }
+    ///Reduce the image to the colours of the given palette with Floyd-Steinberg dithering: the
+    /// error of every pixel is spread to its neighbours right and below. Alpha is kept as it is.
+    pub fn dithered(&self, palette: &[[u8; 4]]) -> Image{
+        if palette.is_empty(){
+            return self.clone()
+        }
+        //error carried to the pixels of the current and the next row
+        let mut errors = vec![[0i32; 3]; self.width * 2 + 2];
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for y in 0..self.height{
+            let (current, next) = errors.split_at_mut(self.width + 1);
+            for e in next.iter_mut(){
+                *e = [0i32; 3];
+            }
+            for x in 0..self.width{
+                let pixel = self.pixels[y * self.width + x];
+                let wanted = [0, 1, 2].map(|c| (pixel[c] as i32 + current[x][c] / 16).clamp(0, 255));
+                let chosen = nearest_in_palette(wanted, palette);
+                for c in 0..3{
+                    let error = wanted[c] - chosen[c] as i32;
+                    current[x + 1][c] += error * 7;
+                    if x > 0{
+                        next[x - 1][c] += error * 3;
+                    }
+                    next[x][c] += error * 5;
+                    next[x + 1][c] += error;
+                }
+                pixels.push([chosen[0], chosen[1], chosen[2], pixel[3]]);
+            }
+            let (current, next) = errors.split_at_mut(self.width + 1);
+            current.copy_from_slice(next);
+        }
+        Image::new(self.width, self.height, pixels)
+    }
+}
+
+///Create the error returned for broken or unsupported image files.
+pub(crate) fn invalid_data(message: &str) -> io::Error{
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+///Find the palette colour closest to the given one, by squared distance.
+fn nearest_in_palette(color: [i32; 3], palette: &[[u8; 4]]) -> [u8; 4]{
+    *palette.iter().min_by_key(|p| (0..3).map(|c| (p[c] as i32 - color[c]).pow(2)).sum::<i32>()).unwrap()
+}
@@ -0,0 +1,365 @@
+use std::io;
+use crate::image::deflate::zlib_compress;
+use crate::image::inflate::zlib_decompress;
+use crate::image::{invalid_data, Image};
+
+///The eight bytes every PNG file starts with.
+pub(crate) const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+///Start column, start row, column step and row step of the seven passes of Adam7 interlacing.
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+///The fields of the IHDR chunk and the colours of the PLTE and tRNS chunks.
+struct PngHeader{
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+    palette: Vec<[u8; 4]>,
+    ///Sample values drawn transparent for greyscale and truecolour images without alpha.
+    transparent: Option<[u16; 3]>,
+}
+
+impl PngHeader{
+    ///Number of samples per pixel.
+    fn get_channels(&self) -> usize{
+        match self.color_type{
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1
+        }
+    }
+    ///Number of bytes of a row of the given number of pixels, without the filter type byte.
+    fn get_row_bytes(&self, width: usize) -> usize{
+        (width * self.get_channels() * self.bit_depth as usize).div_ceil(8)
+    }
+    ///Get the number of columns and rows of an interlace pass.
+    fn get_pass_size(&self, (start_x, start_y, step_x, step_y): (usize, usize, usize, usize)) -> (usize, usize){
+        ((self.width + step_x - start_x - 1) / step_x, (self.height + step_y - start_y - 1) / step_y)
+    }
+    ///Get the size of the decompressed image data: the rows of every pass, each with its filter
+    /// type byte. None if it doesn't fit in memory.
+    fn get_data_size(&self) -> Option<usize>{
+        let passes: &[(usize, usize, usize, usize)] = if self.interlaced {&ADAM7_PASSES} else {&[(0, 0, 1, 1)]};
+        passes.iter().try_fold(0usize, |size, &pass| {
+            let (width, height) = self.get_pass_size(pass);
+            if width == 0 || height == 0{
+                return Some(size)
+            }
+            let row_bytes = width.checked_mul(self.get_channels() * self.bit_depth as usize)?.div_ceil(8);
+            size.checked_add(row_bytes.checked_add(1)?.checked_mul(height)?)
+        })
+    }
+    ///Get the sample with the given index from an unfiltered row.
+    fn get_sample(&self, row: &[u8], index: usize) -> u16{
+        match self.bit_depth{
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            depth => {
+                let bit = index * depth as usize;
+                let shift = 8 - depth as usize - bit % 8;
+                ((row[bit / 8] >> shift) & ((1u8 << depth) - 1)) as u16
+            }
+        }
+    }
+    ///Get the colour of the pixel with the given index from an unfiltered row.
+    fn get_pixel(&self, row: &[u8], index: usize) -> [u8; 4]{
+        let channels = self.get_channels();
+        let max = (1u32 << self.bit_depth) - 1;
+        let scale = |sample: u16| (sample as u32 * 255 / max) as u8;
+        let sample = |channel: usize| self.get_sample(row, index * channels + channel);
+        match self.color_type{
+            0 => {
+                let grey = sample(0);
+                let alpha = if self.transparent.is_some_and(|t| t[0] == grey) {0u8} else {255u8};
+                let v = scale(grey);
+                [v, v, v, alpha]
+            },
+            2 => {
+                let rgb = [sample(0), sample(1), sample(2)];
+                let alpha = if self.transparent == Some(rgb) {0u8} else {255u8};
+                [scale(rgb[0]), scale(rgb[1]), scale(rgb[2]), alpha]
+            },
+            3 => self.palette.get(sample(0) as usize).copied().unwrap_or([0u8, 0u8, 0u8, 255u8]),
+            4 => {
+                let v = scale(sample(0));
+                [v, v, v, scale(sample(1))]
+            },
+            _ => [scale(sample(0)), scale(sample(1)), scale(sample(2)), scale(sample(3))]
+        }
+    }
+}
+
+///Undo the filter of a row, given the unfiltered row above it (all zeroes for the first row).
+fn unfilter_row(filter: u8, row: &mut [u8], previous: &[u8], pixel_bytes: usize) -> io::Result<()>{
+    for i in 0..row.len(){
+        let left = if i >= pixel_bytes {row[i - pixel_bytes]} else {0};
+        let up = previous[i];
+        let up_left = if i >= pixel_bytes {previous[i - pixel_bytes]} else {0};
+        let predicted = match filter{
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => {
+                let p = left as i16 + up as i16 - up_left as i16;
+                let (pa, pb, pc) = ((p - left as i16).abs(), (p - up as i16).abs(), (p - up_left as i16).abs());
+                if pa <= pb && pa <= pc {left} else if pb <= pc {up} else {up_left}
+            },
+            _ => return Err(invalid_data("invalid PNG filter type"))
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+///Decode the filtered rows of an interlace pass (the whole image for images without interlacing)
+/// starting at the given offset of the decompressed data, and put the pixels at their place in the
+/// image. Returns the offset after the rows.
+fn decode_pass(
+    header: &PngHeader, data: &[u8], offset: usize, pixels: &mut [[u8; 4]],
+    pass: (usize, usize, usize, usize)
+) -> io::Result<usize>{
+    let (start_x, start_y, step_x, step_y) = pass;
+    let (width, height) = header.get_pass_size(pass);
+    if width == 0 || height == 0{
+        return Ok(offset)
+    }
+    let row_bytes = header.get_row_bytes(width);
+    let pixel_bytes = (header.get_channels() * header.bit_depth as usize).div_ceil(8);
+    let mut previous = vec![0u8; row_bytes];
+    let mut offset = offset;
+    for y in 0..height{
+        let filter = *data.get(offset).ok_or_else(|| invalid_data("PNG image data is cut off"))?;
+        let mut row = data.get(offset + 1..offset + 1 + row_bytes)
+            .ok_or_else(|| invalid_data("PNG image data is cut off"))?.to_vec();
+        unfilter_row(filter, &mut row, &previous, pixel_bytes)?;
+        for x in 0..width{
+            pixels[(start_y + y * step_y) * header.width + start_x + x * step_x] = header.get_pixel(&row, x);
+        }
+        previous = row;
+        offset += 1 + row_bytes;
+    }
+    Ok(offset)
+}
+
+///Decode a PNG image of any colour type and bit depth, interlaced or not.
+pub fn decode_png(bytes: &[u8]) -> io::Result<Image>{
+    if !bytes.starts_with(&PNG_SIGNATURE){
+        return Err(invalid_data("not a PNG file"))
+    }
+    let mut header: Option<PngHeader> = None;
+    let mut compressed = vec![];
+    let mut position = PNG_SIGNATURE.len();
+    loop{
+        let chunk_header = bytes.get(position..position + 8).ok_or_else(|| invalid_data("PNG file is cut off"))?;
+        let length = u32::from_be_bytes([chunk_header[0], chunk_header[1], chunk_header[2], chunk_header[3]]) as usize;
+        let kind = &chunk_header[4..8];
+        //the chunk ends with its CRC, which isn't checked but has to be there
+        if bytes.len() < position + 12 + length{
+            return Err(invalid_data("PNG file is cut off"))
+        }
+        let data = &bytes[position + 8..position + 8 + length];
+        position += 12 + length;
+        if kind == b"IHDR"{
+            if data.len() < 13{
+                return Err(invalid_data("invalid PNG header"))
+            }
+            let (bit_depth, color_type) = (data[8], data[9]);
+            let valid = match color_type{
+                0 => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+                3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+                2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+                _ => false
+            };
+            if !valid || data[10] != 0 || data[11] != 0 || data[12] > 1{
+                return Err(invalid_data("unsupported PNG format"))
+            }
+            header = Some(PngHeader{
+                width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize,
+                height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize,
+                bit_depth,
+                color_type,
+                interlaced: data[12] == 1,
+                palette: vec![],
+                transparent: None,
+            });
+            continue
+        }
+        let header = match header.as_mut(){
+            Some(h) => h,
+            None => return Err(invalid_data("PNG file doesn't start with a header"))
+        };
+        match kind{
+            b"PLTE" => {
+                header.palette = data.chunks_exact(3).map(|c| [c[0], c[1], c[2], 255u8]).collect();
+            },
+            b"tRNS" => {
+                if header.color_type == 3{
+                    for (entry, &alpha) in header.palette.iter_mut().zip(data.iter()){
+                        entry[3] = alpha;
+                    }
+                } else if data.len() >= 6 && header.color_type == 2{
+                    let sample = |i: usize| u16::from_be_bytes([data[i * 2], data[i * 2 + 1]]);
+                    header.transparent = Some([sample(0), sample(1), sample(2)]);
+                } else if data.len() >= 2 && header.color_type == 0{
+                    header.transparent = Some([u16::from_be_bytes([data[0], data[1]]), 0, 0]);
+                }
+            },
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let header = header.ok_or_else(|| invalid_data("PNG file has no header"))?;
+    if compressed.is_empty(){
+        return Err(invalid_data("PNG file has no image data"))
+    }
+    let count = header.width.checked_mul(header.height).ok_or_else(|| invalid_data("PNG image too large"))?;
+    let data_size = header.get_data_size().ok_or_else(|| invalid_data("PNG image too large"))?;
+    let data = zlib_decompress(&compressed)?;
+    //checked before making room for the pixels, the header could claim any size
+    if data.len() < data_size{
+        return Err(invalid_data("PNG image data is cut off"))
+    }
+    let mut pixels = vec![[0u8; 4]; count];
+    if header.interlaced{
+        let mut offset = 0;
+        for pass in ADAM7_PASSES{
+            offset = decode_pass(&header, &data, offset, &mut pixels, pass)?;
+        }
+    } else {
+        decode_pass(&header, &data, 0, &mut pixels, (0, 0, 1, 1))?;
+    }
+    Ok(Image::new(header.width, header.height, pixels))
+}
+
+///Compute the CRC-32 of a chunk, over its type and data.
+fn crc32(bytes: &[u8]) -> u32{
+    let mut crc = 0xffffffffu32;
+    for &byte in bytes{
+        crc ^= byte as u32;
+        for _ in 0..8{
+            crc = if crc & 1 != 0 {(crc >> 1) ^ 0xedb88320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+///Append a chunk with its length and CRC.
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]){
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+///Encode an image as an 8-bit RGBA PNG without interlacing.
+pub fn encode_png(image: &Image) -> Vec<u8>{
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    //bit depth, colour type RGBA, compression, filter and interlace method
+    header.extend([8u8, 6u8, 0u8, 0u8, 0u8]);
+    //every row starts with filter type 0, the rows are left as they are
+    let mut raw = Vec::with_capacity(image.height * (image.width * 4 + 1));
+    for row in image.pixels.chunks(image.width.max(1)){
+        raw.push(0u8);
+        for pixel in row{
+            raw.extend_from_slice(pixel);
+        }
+    }
+    let mut out = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///Build a PNG file from the IHDR fields after the size and the decompressed image data.
+    fn png(width: u32, height: u32, format: [u8; 5], data: &[u8]) -> Vec<u8>{
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend(height.to_be_bytes());
+        header.extend(format);
+        let mut out = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &header);
+        write_chunk(&mut out, b"IDAT", &zlib_compress(data));
+        write_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    fn test_image() -> Image{
+        let pixels = (0..7 * 5).map(|i| [i as u8 * 7, 255 - i as u8, (i % 3) as u8 * 100, (i * 11 % 256) as u8]).collect();
+        Image::new(7, 5, pixels)
+    }
+
+    #[test]
+    fn encoded_images_are_decoded_unchanged(){
+        let image = test_image();
+        assert_eq!(decode_png(&encode_png(&image)).unwrap(), image);
+        let empty = Image::new(0, 0, vec![]);
+        assert!(decode_png(&encode_png(&empty)).is_ok());
+    }
+
+    #[test]
+    fn interlaced_passes_are_put_in_place(){
+        //8-bit greyscale with Adam7 interlacing, every pass with unfiltered rows
+        let grey = |x: usize, y: usize| (y * 10 + x) as u8;
+        let mut data = vec![];
+        for (start_x, start_y, step_x, step_y) in ADAM7_PASSES{
+            let columns: Vec<usize> = (start_x..5).step_by(step_x).collect();
+            if columns.is_empty(){
+                continue
+            }
+            for y in (start_y..3).step_by(step_y){
+                data.push(0u8);
+                data.extend(columns.iter().map(|&x| grey(x, y)));
+            }
+        }
+        let image = decode_png(&png(5, 3, [8u8, 0u8, 0u8, 0u8, 1u8], &data)).unwrap();
+        for (i, pixel) in image.pixels.iter().enumerate(){
+            let v = grey(i % 5, i / 5);
+            assert_eq!(*pixel, [v, v, v, 255u8]);
+        }
+    }
+
+    #[test]
+    fn filters_are_undone(){
+        //one row of each filter type, all giving the same pixels as the unfiltered first row
+        let row = [10u8, 20u8, 30u8];
+        let mut data = vec![0u8, 10u8, 20u8, 30u8];
+        data.extend([1u8, 10u8, 10u8, 10u8]);
+        data.extend([2u8, 0u8, 0u8, 0u8]);
+        data.extend([3u8, 5u8, 5u8, 5u8]);
+        data.extend([4u8, 0u8, 0u8, 0u8]);
+        let image = decode_png(&png(3, 5, [8u8, 0u8, 0u8, 0u8, 0u8], &data)).unwrap();
+        for (i, pixel) in image.pixels.iter().enumerate(){
+            assert_eq!(pixel[0], row[i % 3], "pixel {}", i);
+        }
+    }
+
+    #[test]
+    fn cut_off_files_are_errors(){
+        let file = encode_png(&test_image());
+        for length in 0..file.len(){
+            assert!(decode_png(&file[..length]).is_err(), "{} bytes", length);
+        }
+        //a stream that is complete but holds too few rows
+        assert!(decode_png(&png(3, 5, [8u8, 0u8, 0u8, 0u8, 0u8], &[0u8; 12])).is_err());
+    }
+
+    #[test]
+    fn oversized_headers_are_errors(){
+        //the header is checked against the image data before making room for the pixels
+        assert!(decode_png(&png(100000, 100000, [8u8, 6u8, 0u8, 0u8, 0u8], &[0u8; 5])).is_err());
+        assert!(decode_png(&png(u32::MAX, u32::MAX, [16u8, 6u8, 0u8, 0u8, 1u8], &[0u8; 5])).is_err());
+    }
+}
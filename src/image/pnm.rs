@@ -0,0 +1,183 @@
+use std::io;
+use crate::image::{invalid_data, Image};
+
+///Reads the whitespace separated header fields of PNM files, skipping comments.
+struct PnmReader<'a>{
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PnmReader<'a>{
+    fn skip_whitespace(&mut self){
+        while let Some(&b) = self.bytes.get(self.position){
+            if b == b'#'{
+                while self.position < self.bytes.len() && self.bytes[self.position] != b'\n'{
+                    self.position += 1;
+                }
+            } else if b.is_ascii_whitespace(){
+                self.position += 1;
+            } else {
+                break
+            }
+        }
+    }
+    ///Read a decimal number.
+    fn read_number(&mut self) -> io::Result<usize>{
+        self.skip_whitespace();
+        let start = self.position;
+        while self.position < self.bytes.len() && self.bytes[self.position].is_ascii_digit(){
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position]).ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid_data("expected a number in PNM file"))
+    }
+    ///Read a single 0 or 1 of a plain PBM file, which need not be separated by whitespace.
+    fn read_bit(&mut self) -> io::Result<bool>{
+        self.skip_whitespace();
+        let bit = match self.bytes.get(self.position){
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(invalid_data("expected 0 or 1 in PBM file"))
+        };
+        self.position += 1;
+        Ok(bit)
+    }
+}
+
+///Decode a PBM (P1, P4), PGM (P2, P5) or PPM (P3, P6) image. PBM pixels that are set are black.
+pub fn decode_pnm(bytes: &[u8]) -> io::Result<Image>{
+    if bytes.len() < 2 || bytes[0] != b'P'{
+        return Err(invalid_data("not a PNM file"))
+    }
+    let kind = bytes[1];
+    let mut reader = PnmReader{bytes, position: 2};
+    let width = reader.read_number()?;
+    let height = reader.read_number()?;
+    let count = width.checked_mul(height).ok_or_else(|| invalid_data("PNM image too large"))?;
+    let max = match kind{
+        b'1' | b'4' => 1,
+        _ => reader.read_number()?
+    };
+    if max == 0 || max > 65535{
+        return Err(invalid_data("invalid maximum value in PNM file"))
+    }
+    let scale = |value: usize| (value.min(max) * 255 / max) as u8;
+    let black_or_white = |set: bool| if set {[0u8, 0u8, 0u8, 255u8]} else {[255u8; 4]};
+    let channels = if kind == b'3' || kind == b'6' {3} else {1};
+    let sample_bytes = if max > 255 {2} else {1};
+    //the least data the pixels take, checked before making room for them: plain files have at
+    // least a character per sample
+    let data_size = match kind{
+        b'1' => Some(count),
+        b'2' | b'3' => count.checked_mul(channels),
+        b'4' => width.div_ceil(8).checked_mul(height),
+        b'5' | b'6' => count.checked_mul(channels * sample_bytes),
+        _ => return Err(invalid_data("unsupported PNM variant"))
+    }.ok_or_else(|| invalid_data("PNM image too large"))?;
+    if bytes.len().saturating_sub(reader.position) < data_size{
+        return Err(invalid_data("PNM file is cut off"))
+    }
+    let mut pixels = Vec::with_capacity(count);
+    match kind{
+        b'1' => {
+            for _ in 0..count{
+                pixels.push(black_or_white(reader.read_bit()?));
+            }
+        },
+        b'2' | b'3' => {
+            for _ in 0..count{
+                let mut pixel = [255u8; 4];
+                for sample in pixel.iter_mut().take(channels){
+                    *sample = scale(reader.read_number()?);
+                }
+                if channels == 1{
+                    pixel = [pixel[0], pixel[0], pixel[0], 255u8];
+                }
+                pixels.push(pixel);
+            }
+        },
+        b'4' => {
+            //a single whitespace character separates the header from the binary data
+            let data = bytes.get(reader.position + 1..).unwrap_or(&[]);
+            let row_bytes = width.div_ceil(8);
+            if data.len() < data_size{
+                return Err(invalid_data("PBM file is cut off"))
+            }
+            for y in 0..height{
+                for x in 0..width{
+                    let byte = data[y * row_bytes + x / 8];
+                    pixels.push(black_or_white(byte & (0x80 >> (x % 8)) != 0));
+                }
+            }
+        },
+        b'5' | b'6' => {
+            let data = bytes.get(reader.position + 1..).unwrap_or(&[]);
+            if data.len() < data_size{
+                return Err(invalid_data("PNM file is cut off"))
+            }
+            let sample = |i: usize| if sample_bytes == 2{
+                (data[i * 2] as usize) << 8 | data[i * 2 + 1] as usize
+            } else {
+                data[i] as usize
+            };
+            for i in 0..count{
+                pixels.push(if channels == 3{
+                    [scale(sample(i * 3)), scale(sample(i * 3 + 1)), scale(sample(i * 3 + 2)), 255u8]
+                } else {
+                    let v = scale(sample(i));
+                    [v, v, v, 255u8]
+                });
+            }
+        },
+        _ => return Err(invalid_data("unsupported PNM variant"))
+    }
+    Ok(Image::new(width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const BLACK: [u8; 4] = [0u8, 0u8, 0u8, 255u8];
+    const WHITE: [u8; 4] = [255u8; 4];
+
+    #[test]
+    fn plain_files_are_decoded(){
+        let bitmap = decode_pnm(b"P1\n# a comment\n3 2\n010\n1 0 1\n").unwrap();
+        assert_eq!(bitmap, Image::new(3, 2, vec![WHITE, BLACK, WHITE, BLACK, WHITE, BLACK]));
+        let grey = decode_pnm(b"P2 2 1 10 0 5").unwrap();
+        assert_eq!(grey.pixels, [BLACK, [127u8, 127u8, 127u8, 255u8]]);
+        let colour = decode_pnm(b"P3 1 1 255 10 20 30").unwrap();
+        assert_eq!(colour.pixels, [[10u8, 20u8, 30u8, 255u8]]);
+    }
+
+    #[test]
+    fn raw_files_are_decoded(){
+        //rows of packed bits are padded to whole bytes
+        let bitmap = decode_pnm(b"P4 10 2\n\x80\x40\xff\xc0").unwrap();
+        assert_eq!(&bitmap.pixels[..10], &[BLACK, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, BLACK]);
+        assert_eq!(&bitmap.pixels[10..], &[BLACK; 10]);
+        let colour = decode_pnm(b"P6 2 1 255\n\x01\x02\x03\x04\x05\x06").unwrap();
+        assert_eq!(colour.pixels, [[1u8, 2u8, 3u8, 255u8], [4u8, 5u8, 6u8, 255u8]]);
+        //samples over 255 take two bytes, most significant first
+        let deep = decode_pnm(b"P5 1 1 65535\n\x80\x00").unwrap();
+        assert_eq!(deep.pixels, [[127u8, 127u8, 127u8, 255u8]]);
+    }
+
+    #[test]
+    fn cut_off_files_are_errors(){
+        for bytes in [&b"P1 2 2 0 1 0"[..], b"P2 2 2 255 1 2 3", b"P4 9 2\n\xff\xff\xff", b"P6 2 1 255\n\x01\x02\x03\x04\x05"]{
+            assert!(decode_pnm(bytes).is_err(), "{:?}", String::from_utf8_lossy(bytes));
+        }
+    }
+
+    #[test]
+    fn oversized_headers_are_errors(){
+        //neither may try to make room for the pixels before noticing the data is missing
+        assert!(decode_pnm(b"P5 100000 100000 255\n").is_err());
+        assert!(decode_pnm(b"P2 100000 100000 255 0").is_err());
+        assert!(decode_pnm(b"P6 4294967296 4294967296 65535\n").is_err());
+        assert!(decode_pnm(b"P6 3074457345618258602 2 65535\n").is_err());
+    }
+}
@@ -1,2 +1,8 @@
+pub mod clipboard;
+pub mod font_file;
+pub mod font_registry;
+pub mod icon;
+pub mod image;
 pub mod widget;
-pub mod pixel_font;
\ No newline at end of file
+pub mod pixel_font;
+pub mod text;
\ No newline at end of file
@@ -1,9 +1,16 @@
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::sync::Mutex;
-use std::time::Duration;
-use minifb::{Key, MouseMode, Window, WindowOptions};
+use std::time::{Duration, Instant};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use lazy_static::lazy_static;
-use desktop_minifb::widget::Widget;
+use desktop_minifb::widget::Color;
+use desktop_minifb::widget::application::{AppContext, Application};
+use desktop_minifb::widget::event;
+use desktop_minifb::widget::event::{Modifiers, WidgetEvent};
 
 
 const WIDTH : usize = 720;
@@ -33,56 +40,281 @@ fn main() {
         *elem = converted[idx];
     }*/
     window.limit_update_rate(Some(Duration::from_micros(16666)));
-    let mut x_off = 0;
+    let typed_chars = Rc::new(RefCell::new(Vec::new()));
+    window.set_input_callback(Box::new(CharBuffer{chars: typed_chars.clone()}));
+    let mut mouse_down = false;
+    let mut right_mouse_down = false;
+    let mut last_mouse_pos = (0isize, 0isize);
     let mut main_widget = desktop_minifb::widget::MainWidget::new(WIDTH, HEIGHT);
+    main_widget.set_background(desktop_minifb::widget::background::Background::classic_grey());
+    //refused clicks ring the terminal bell
+    main_widget.set_on_beep(Box::new(|| {
+        print!("\x07");
+        let _ = std::io::stdout().flush();
+    }));
+    //$MODESTO_WALLPAPER replaces the pattern with a tiled image
+    if let Some(path) = std::env::var_os("MODESTO_WALLPAPER").map(PathBuf::from){
+        match desktop_minifb::image::Image::load(&path){
+            Ok(image) => main_widget.set_background(desktop_minifb::widget::background::Background::Image{
+                image,
+                placement: desktop_minifb::widget::background::ImagePlacement::Tiled,
+                fill: [255u8; 4],
+            }),
+            Err(e) => eprintln!("can't load wallpaper {}: {}", path.display(), e)
+        }
+    }
+    //$MODESTO_THEME is a theme file, reloaded whenever it is saved
+    let mut theme_watcher = std::env::var_os("MODESTO_THEME")
+        .map(|path| desktop_minifb::widget::theme::ThemeWatcher::new(&PathBuf::from(path)));
+    //desktop icons come from $MODESTO_DESKTOP, or ~/Desktop if it isn't set
+    let desktop_directory = std::env::var_os("MODESTO_DESKTOP").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Desktop")));
+    if let Some(directory) = desktop_directory{
+        if let Err(e) = main_widget.set_desktop_directory(&directory){
+            eprintln!("can't show desktop directory {}: {}", directory.display(), e);
+        }
+    }
 
-    let mut window1 = desktop_minifb::widget::window::WindowWidget::new(" Title ", 500, 300, 50, 50);
-    window1.register_top_bar(Box::new(desktop_minifb::widget::top_bar::TopBarWidget::new(
-        Box::new(vec![
-            Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
-                Box::new("{}"), Box::new(BTreeMap::new()),
-            )),
-            Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
-            Box::new("Button"), Box::new(BTreeMap::new()))),
-            Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
-              Box::new("Second Button"), Box::new(BTreeMap::new()),
-              )),
-
-        ])
-    )));
-    main_widget.reg_window(Box::new(window1));
+    //themes are switched from the main loop, applications can't reach the main widget
+    let chosen_theme = Rc::new(Cell::new(None));
+    main_widget.launch(Box::new(NotesApp));
+    main_widget.launch(Box::new(BinApp));
+    main_widget.launch(Box::new(PlaygroundApp{chosen_theme: chosen_theme.clone()}));
     while window.is_open() && !(window.is_key_down(Key::LeftAlt) && window.is_key_down(Key::F4)){
         //MAIN LOOP - FUTURE: IN USERSPACE PROGRAM
-        if x_off == 0{
-            main_widget.windows[0].set_moving(true);
+        let modifiers = Modifiers{
+            shift: window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift),
+            control: window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl),
+            option: window.is_key_down(Key::LeftAlt) || window.is_key_down(Key::RightAlt),
+            command: window.is_key_down(Key::LeftSuper) || window.is_key_down(Key::RightSuper),
+        };
+        if let Some((x, y)) = window.get_mouse_pos(MouseMode::Clamp){
+            let (x, y) = (x as isize, y as isize);
+            let pressed = window.get_mouse_down(MouseButton::Left);
+            if (x, y) != last_mouse_pos{
+                main_widget.handle_event(WidgetEvent::MouseMove{x, y, pressed: mouse_down});
+                last_mouse_pos = (x, y);
+            }
+            if pressed && !mouse_down{
+                main_widget.handle_event(WidgetEvent::MouseDown{x, y, button: event::MouseButton::Left, modifiers});
+            } else if !pressed && mouse_down{
+                main_widget.handle_event(WidgetEvent::MouseUp{x, y, button: event::MouseButton::Left, modifiers});
+            }
+            mouse_down = pressed;
+            //the right button only opens context menus, which track the mouse by its moves
+            let right_pressed = window.get_mouse_down(MouseButton::Right);
+            if right_pressed && !right_mouse_down{
+                main_widget.handle_event(WidgetEvent::MouseDown{x, y, button: event::MouseButton::Right, modifiers});
+            } else if !right_pressed && right_mouse_down{
+                main_widget.handle_event(WidgetEvent::MouseUp{x, y, button: event::MouseButton::Right, modifiers});
+            }
+            right_mouse_down = right_pressed;
+            if let Some((dx, dy)) = window.get_scroll_wheel(){
+                //minifb reports wheel notches, scroll a line (16px) per notch
+                main_widget.handle_event(WidgetEvent::Scroll{
+                    x, y, delta_x: (dx * 16.0) as isize, delta_y: (dy * 16.0) as isize
+                });
+            }
+        }
+        for key in window.get_keys_pressed(KeyRepeat::Yes){
+            if let Some(key) = convert_key(key){
+                main_widget.handle_event(WidgetEvent::KeyDown{key, modifiers});
+            }
+        }
+        for c in typed_chars.borrow_mut().drain(..){
+            main_widget.handle_event(WidgetEvent::TextInput(c));
         }
-        if x_off == 60{
-            main_widget.windows[0].set_moving(false);
+        main_widget.handle_event(WidgetEvent::Tick(Instant::now()));
+        if let Some(result) = theme_watcher.as_mut().and_then(|watcher| watcher.poll()){
+            match result{
+                Ok(theme) => main_widget.set_theme(theme),
+                Err(e) => eprintln!("can't load theme: {}", e)
+            }
         }
-        x_off = (x_off + 1) % 120;
-        println!("{}", match window.get_mouse_pos(MouseMode::Clamp){
-            Some(p) => format!("{}:{}", p.0, p.1),
-            None => String::from("NONE")
-        });
-        if x_off < 60 {
-            main_widget.windows[0].x_position = 50 + x_off;
-            main_widget.windows[0].y_position = 50 + x_off;
-        } else {
-            main_widget.windows[0].x_position = 110;
-            main_widget.windows[0].y_position = 110;
+        if let Some(i) = chosen_theme.take(){
+            main_widget.set_theme(desktop_minifb::widget::theme::Theme::get_builtin_themes().swap_remove(i));
         }
         let newfb = main_widget.render(WIDTH, HEIGHT);
         //draw to buffer - REPLACE
-        let converted:Vec<u32> = newfb.iter().map(compute_col_u32_alpha).collect();//FRAMEBUFFER.lock().unwrap().iter().map(compute_col_u32.no_alpha).collect();
+        let converted:Vec<u32> = newfb.iter().map(|p| Color::from(*p).to_argb_u32()).collect();
         //draw buffer to screen - REPLACE
         window.update_with_buffer(&converted, WIDTH, HEIGHT).unwrap();
     }
 }
-///Combines the u8 components (order RGB) and 255 into a 32 bit unsigned integer (order: ARGB)
-fn compute_col_u32_no_alpha(components: &[u8; 3])-> u32{
-    ((255 as u32) << 24) | ((components[0] as u32) << 16) | ((components[1] as u32) << 8) | (components[2] as u32)
+///Collects the characters typed into the minifb window, to be sent as TextInput events.
+struct CharBuffer{
+    chars: Rc<RefCell<Vec<char>>>,
+}
+impl minifb::InputCallback for CharBuffer{
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(c) = char::from_u32(uni_char){
+            self.chars.borrow_mut().push(c);
+        }
+    }
+}
+///Shows off the widgets of the toolkit in one window, and starts the other programs.
+struct PlaygroundApp{
+    ///Index of the built-in theme chosen from the Appearance menu, applied by the main loop.
+    chosen_theme: Rc<Cell<Option<usize>>>,
+}
+impl Application for PlaygroundApp{
+    fn get_name(&self) -> &str{
+        "Playground"
+    }
+    fn launch(&mut self, context: &AppContext){
+        let mut theme_actions: BTreeMap<Box<str>, Box<dyn Fn()>> = BTreeMap::new();
+        for (i, theme) in desktop_minifb::widget::theme::Theme::get_builtin_themes().iter().enumerate(){
+            let chosen_theme_clone = self.chosen_theme.clone();
+            theme_actions.insert(theme.name.as_str().into(), Box::new(move || chosen_theme_clone.set(Some(i))));
+        }
+        let mut app_actions: BTreeMap<Box<str>, Box<dyn Fn()>> = BTreeMap::new();
+        let context_clone = context.clone();
+        app_actions.insert("Font Editor".into(), Box::new(move || context_clone.launch(Box::new(FontEditorApp))));
+        context.set_menu(desktop_minifb::widget::top_bar::TopBarWidget::new(
+            Box::new(vec![
+                Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
+                    "{}", Box::new(app_actions),
+                )),
+                Box::new(desktop_minifb::widget::top_bar::TopBarButton::edit_menu()),
+                Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
+                "Button", Box::new(BTreeMap::new()))),
+                Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
+                  "Second Button", Box::new(BTreeMap::new()),
+                  )),
+                Box::new(desktop_minifb::widget::top_bar::TopBarButton::new(
+                    "Appearance", Box::new(theme_actions),
+                )),
+
+            ])
+        ));
+        let mut window1 = desktop_minifb::widget::window::WindowWidget::new(" Title ", 500, 300, 50, 50);
+        let body_bounds = window1.get_body_bounds();
+        let mut body = desktop_minifb::widget::container::ContainerWidget::new(body_bounds.width, body_bounds.height);
+        let text_field = desktop_minifb::widget::text_field::TextField::new(200, "Edit me");
+        body.add_child(Box::new(text_field), 20, 20);
+        let checkbox = desktop_minifb::widget::button::Checkbox::new("Subtitle", true);
+        body.add_child(Box::new(checkbox), 20, 60);
+        let radio_group = desktop_minifb::widget::button::RadioGroup::new(&["Bullet", "Stone", "Tile"], 0);
+        body.add_child(Box::new(radio_group), 20, 90);
+        let mut slider = desktop_minifb::widget::slider::Slider::new(
+            desktop_minifb::widget::slider::Orientation::Horizontal, 200, 0, 100, 40);
+        slider.set_ticks(Some(10), false);
+        body.add_child(Box::new(slider), 260, 20);
+        let mut progress_bar = desktop_minifb::widget::progress_bar::ProgressBar::new(200);
+        progress_bar.set_value(0.4);
+        body.add_child(Box::new(progress_bar), 260, 60);
+        body.add_child(Box::new(desktop_minifb::widget::progress_bar::IndeterminateProgressBar::new(200)), 260, 84);
+        let popup_menu = desktop_minifb::widget::popup_menu::PopupMenu::new(&["Bullet", "Stone", "Tile", "Button"], 1, 140);
+        body.add_child(Box::new(popup_menu), 260, 110);
+        let mut test_button = desktop_minifb::widget::button::Button::new("Test", 90);
+        let context_clone = context.clone();
+        test_button.set_on_click(Box::new(move || {
+            let alert = desktop_minifb::widget::alert::Alert::new(
+                desktop_minifb::widget::alert::AlertIcon::Caution,
+                "Set the Subtitle to Tile",
+                &["Set", "Button"]);
+            let (screen_width, screen_height) = context_clone.get_screen_size();
+            context_clone.open_window(alert.into_window(screen_width, screen_height));
+        }));
+        body.add_child(Box::new(test_button), body_bounds.width - 210, body_bounds.height - 40);
+        let mut set_button = desktop_minifb::widget::button::Button::new("Set", 90);
+        set_button.set_default(true);
+        body.add_child(Box::new(set_button), body_bounds.width - 110, body_bounds.height - 40);
+        window1.set_body(Box::new(body));
+        context.open_window(window1);
+    }
+}
+///A text area for notes.
+struct NotesApp;
+impl Application for NotesApp{
+    fn get_name(&self) -> &str{
+        "Notes"
+    }
+    fn launch(&mut self, context: &AppContext){
+        context.set_menu(desktop_minifb::widget::top_bar::TopBarWidget::new(Box::new(vec![
+            Box::new(desktop_minifb::widget::top_bar::TopBarButton::edit_menu()),
+        ])));
+        let mut notes = desktop_minifb::widget::window::WindowWidget::new(" Notes ", 260, 200, 420, 240);
+        let notes_bounds = notes.get_body_bounds();
+        notes.set_body(Box::new(desktop_minifb::widget::scroll_view::ScrollView::new(
+            Box::new(desktop_minifb::widget::text_area::TextArea::new(
+                notes_bounds.width - desktop_minifb::widget::scroll_view::SCROLL_BAR_WIDTH,
+                notes_bounds.height,
+                "Notes")),
+            notes_bounds.width, notes_bounds.height)));
+        context.open_window(notes);
+    }
 }
-///Combines the components (order: RGBA) into a u32 (order: ARGB)
-fn compute_col_u32_alpha(components: &[u8; 4]) -> u32 {
-    ((components[3] as u32) << 24) | ((components[0] as u32) << 16) | ((components[1] as u32) << 8) | (components[2] as u32)
+///A table of files, sortable by its columns.
+struct BinApp;
+impl Application for BinApp{
+    fn get_name(&self) -> &str{
+        "Bin"
+    }
+    fn launch(&mut self, context: &AppContext){
+        context.set_menu(desktop_minifb::widget::top_bar::TopBarWidget::new(Box::new(vec![
+            Box::new(desktop_minifb::widget::top_bar::TopBarButton::edit_menu()),
+        ])));
+        let mut files = desktop_minifb::widget::window::WindowWidget::new(" Bin ", 240, 180, 460, 40);
+        let files_bounds = files.get_body_bounds();
+        let rows: Vec<Vec<String>> = ["Notes", "Bin", "Docs", "Bootle", "Tinted", "Sound", "Lint", "Bonus"].iter()
+            .enumerate()
+            .map(|(i, name)| vec![name.to_string(), format!("{}", (i * 37) % 100)])
+            .collect();
+        let mut table = desktop_minifb::widget::list_view::ListView::with_columns(
+            Box::new(rows),
+            vec![
+                desktop_minifb::widget::list_view::ListColumn::new("Title", 140),
+                desktop_minifb::widget::list_view::ListColumn::new("Size", 80),
+            ],
+            files_bounds.width, files_bounds.height);
+        table.set_selection_mode(desktop_minifb::widget::list_view::SelectionMode::Multiple);
+        files.set_body(Box::new(table));
+        context.open_window(files);
+    }
+}
+///The pixel font editor, starting with a copy of the theme font.
+struct FontEditorApp;
+impl Application for FontEditorApp{
+    fn get_name(&self) -> &str{
+        "Font Editor"
+    }
+    fn launch(&mut self, context: &AppContext){
+        context.set_menu(desktop_minifb::widget::top_bar::TopBarWidget::new(Box::new(vec![
+            Box::new(desktop_minifb::widget::top_bar::TopBarButton::edit_menu()),
+        ])));
+        let (screen_width, screen_height) = context.get_screen_size();
+        let editor = desktop_minifb::widget::font_editor::FontEditor::new(context.get_theme().font.clone());
+        context.open_window(editor.into_window(120, 60, screen_width, screen_height));
+    }
+}
+///Converts a minifb key into the key type used by widgets, if widgets care about it.
+fn convert_key(key: Key) -> Option<event::Key>{
+    Some(match key{
+        Key::Left => event::Key::Left,
+        Key::Right => event::Key::Right,
+        Key::Up => event::Key::Up,
+        Key::Down => event::Key::Down,
+        Key::Home => event::Key::Home,
+        Key::End => event::Key::End,
+        Key::PageUp => event::Key::PageUp,
+        Key::PageDown => event::Key::PageDown,
+        Key::Backspace => event::Key::Backspace,
+        Key::Delete => event::Key::Delete,
+        Key::Enter | Key::NumPadEnter => event::Key::Return,
+        Key::Escape => event::Key::Escape,
+        Key::Tab => event::Key::Tab,
+        Key::Space => event::Key::Space,
+        _ => {
+            //letters and digits, only used for shortcuts
+            let idx = key as u32;
+            if idx <= Key::Key9 as u32{
+                event::Key::Char(char::from_digit(idx, 10)?)
+            } else if idx <= Key::Z as u32{
+                event::Key::Char((b'a' + (idx - Key::A as u32) as u8) as char)
+            } else {
+                return None
+            }
+        }
+    })
 }
@@ -1,38 +1,698 @@
 use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Sub};
+use std::path::Path;
+use crate::font_file;
+use crate::image::invalid_data;
+use crate::text;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
-///struct representing an 8 bit font pixel (Alpha only)
+///struct representing an 8 bit font pixel (Alpha only). The alpha is the coverage of the pixel by
+/// the glyph: 0 shows the background, 255 the text colour, anything between blends the two.
 pub struct FontPixel{
     pub(crate) alpha: u8,
 }
 
+impl FontPixel{
+    ///Create a font pixel with the given coverage, e.g. from an anti-aliasing rasterizer.
+    pub fn new(alpha: u8) -> Self{
+        FontPixel{alpha}
+    }
+    pub fn get_alpha(&self) -> u8{
+        self.alpha
+    }
+}
+
 #[derive(Clone, Eq, PartialEq)]
+///The bitmap of a character with its metrics. The advance, the distance from the pen position of
+/// this character to that of the next, is left_bearing + width + right_offset.
 pub struct PixelFontChar{
     pub(crate) width: usize,
     pub(crate) height: usize,
+    ///Space after the bitmap.
     pub right_offset: usize,
+    ///Offset of the left edge of the bitmap from the pen position; negative to reach into the
+    /// previous character.
+    pub left_bearing: isize,
+    ///Rows of the bitmap above the baseline. The rest of them are below it.
+    pub ascent: isize,
     pub(crate) pixels: Box<Vec<FontPixel>>,
 }
 
+impl PixelFontChar{
+    ///Create a character from its bitmap, row by row, with the bitmap standing on the baseline and
+    /// no space around it. Panics if the number of pixels doesn't match the size.
+    pub fn new(width: usize, height: usize, pixels: Vec<FontPixel>) -> Self{
+        assert_eq!(pixels.len(), width * height, "pixel count doesn't match the character size");
+        PixelFontChar{
+            width,
+            height,
+            right_offset: 0,
+            left_bearing: 0,
+            ascent: height as isize,
+            pixels: Box::new(pixels),
+        }
+    }
+    pub fn get_width(&self) -> usize{
+        self.width
+    }
+    pub fn get_height(&self) -> usize{
+        self.height
+    }
+    ///Get the pixels of the bitmap, row by row.
+    pub fn get_pixels(&self) -> &[FontPixel]{
+        &self.pixels
+    }
+    ///Get the pixel at the given column and row of the bitmap, None outside of it.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<FontPixel>{
+        if x >= self.width || y >= self.height{
+            return None
+        }
+        self.pixels.get(y * self.width + x).copied()
+    }
+    ///Set the pixel at the given column and row of the bitmap. Ignored outside of it.
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: FontPixel){
+        if x < self.width && y < self.height{
+            self.pixels[y * self.width + x] = pixel;
+        }
+    }
+    ///Change the width of the bitmap, cutting off columns on the right or adding empty ones.
+    pub fn set_width(&mut self, width: usize){
+        let mut pixels = Vec::with_capacity(width * self.height);
+        for y in 0..self.height{
+            for x in 0..width{
+                pixels.push(self.get_pixel(x, y).unwrap_or(W));
+            }
+        }
+        self.width = width;
+        self.pixels = Box::new(pixels);
+    }
+    ///Get the distance from the pen position of this character to that of the next.
+    pub fn get_advance(&self) -> usize{
+        (self.left_bearing + (self.width + self.right_offset) as isize).max(0) as usize
+    }
+    ///Get the rows of the bitmap below the baseline.
+    pub fn get_descent(&self) -> isize{
+        self.height as isize - self.ascent
+    }
+}
+
+///A character placed on a line by PixelFont::layout.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PlacedChar{
+    ///Character drawn, which differs from the text for ligatures and mirrored brackets.
+    pub glyph: char,
+    ///Index of the first character of the text it stands for, and how many characters it covers.
+    /// Combining marks cover none, the cluster they belong to covers them.
+    pub start: usize,
+    pub len: usize,
+    ///Pen position, with the kerning to the previous character applied.
+    pub x: isize,
+    pub advance: usize,
+    ///Whether the character is part of right-to-left text, so its start is on the right.
+    pub rtl: bool,
+}
+
 #[derive(Clone, Eq, PartialEq)]
 ///Structure representing a pixel font with a set of characters in a specific size
 pub struct PixelFont {
     size_in_pts: u32,
+    ///Height of the line above and below the baseline.
+    ascent: usize,
+    descent: usize,
     pub(crate) charset: Box<BTreeMap<char, PixelFontChar>>,
+    ///Adjustment of the distance between two characters following each other.
+    kerning: BTreeMap<(char, char), isize>,
+    ///Sequences of characters drawn as a single character, e.g. "fi" as U+FB01.
+    ligatures: BTreeMap<String, char>,
+    ///Character drawn for characters the font lacks.
+    replacement_char: char,
+}
+
+///Set of text styles, synthesized from the plain characters of any font like QuickDraw did.
+/// Combine them with `|`, e.g. `TextStyle::BOLD | TextStyle::UNDERLINE`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct TextStyle(u8);
+
+impl TextStyle{
+    pub const PLAIN: TextStyle = TextStyle(0);
+    ///Characters smeared one pixel to the right, one pixel wider.
+    pub const BOLD: TextStyle = TextStyle(1);
+    ///Characters slanted to the right, without changing their advance.
+    pub const ITALIC: TextStyle = TextStyle(2);
+    ///A line one pixel below the characters, across the whole text.
+    pub const UNDERLINE: TextStyle = TextStyle(4);
+    ///Only a one pixel ring around every character is drawn, the inside shows the background.
+    pub const OUTLINE: TextStyle = TextStyle(8);
+    ///Like OUTLINE, with the ring one pixel thicker at the bottom and right.
+    pub const SHADOW: TextStyle = TextStyle(16);
+
+    ///Returns true if all styles of other are set.
+    pub fn contains(self, other: TextStyle) -> bool{
+        self.0 & other.0 == other.0
+    }
+    pub fn is_plain(self) -> bool{
+        self.0 == 0
+    }
+    pub fn insert(&mut self, other: TextStyle){
+        self.0 |= other.0;
+    }
+    pub fn remove(&mut self, other: TextStyle){
+        self.0 &= !other.0;
+    }
+    ///Get the width and height the ring of OUTLINE or SHADOW adds to every character cell.
+    pub(crate) fn get_ring_size(self) -> usize{
+        if self.contains(TextStyle::SHADOW) {3} else if self.contains(TextStyle::OUTLINE) {2} else {0}
+    }
 }
 
-const B:FontPixel = FontPixel{alpha: 1};
-const W:FontPixel = FontPixel{alpha: 255};
+impl BitOr for TextStyle{
+    type Output = TextStyle;
+    fn bitor(self, rhs: TextStyle) -> TextStyle{
+        TextStyle(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for TextStyle{
+    fn bitor_assign(&mut self, rhs: TextStyle){
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for TextStyle{
+    type Output = TextStyle;
+    fn bitand(self, rhs: TextStyle) -> TextStyle{
+        TextStyle(self.0 & rhs.0)
+    }
+}
+
+impl Sub for TextStyle{
+    type Output = TextStyle;
+    fn sub(self, rhs: TextStyle) -> TextStyle{
+        TextStyle(self.0 & !rhs.0)
+    }
+}
+
+///Rows per pixel of slant of synthesized italics, counted from the bottom of the character cell.
+const ITALIC_SLANT: usize = 4;
+///Character drawn for missing ones by default.
+const REPLACEMENT_CHARACTER: char = '\u{fffd}';
+
+const B:FontPixel = FontPixel{alpha: 255};
+const W:FontPixel = FontPixel{alpha: 0};
 //hard-coded for testing, TODO: load pixel fonts (maybe: ttf to pixel font?)
 impl PixelFont {
+    ///Create a font without any characters, with the given size and line metrics.
+    pub fn new(size_in_pts: u32, ascent: usize, descent: usize) -> Self{
+        PixelFont{
+            size_in_pts,
+            ascent,
+            descent,
+            charset: Box::new(BTreeMap::new()),
+            kerning: BTreeMap::new(),
+            ligatures: BTreeMap::new(),
+            replacement_char: REPLACEMENT_CHARACTER,
+        }
+    }
+    ///Get the height of a line: the ascent and descent of the font together.
+    pub fn get_line_height(&self) -> usize{
+        self.ascent + self.descent
+    }
+    ///Get the height of the line above the baseline, which is also the row of the baseline.
+    pub fn get_ascent(&self) -> usize{
+        self.ascent
+    }
+    ///Get the height of the line below the baseline.
+    pub fn get_descent(&self) -> usize{
+        self.descent
+    }
+    ///Get the bitmap and metrics of a character, if the font has it.
+    pub fn get_char(&self, c: char) -> Option<&PixelFontChar>{
+        self.charset.get(&c)
+    }
+    ///Get a character to change it, if the font has it.
+    pub fn get_char_mut(&mut self, c: char) -> Option<&mut PixelFontChar>{
+        self.charset.get_mut(&c)
+    }
+    ///Get all characters of the font, ordered by code point.
+    pub fn get_charset(&self) -> &BTreeMap<char, PixelFontChar>{
+        &self.charset
+    }
+    ///Add a character to the font, replacing the one it had.
+    pub fn set_char(&mut self, c: char, glyph: PixelFontChar){
+        self.charset.insert(c, glyph);
+    }
+    ///Remove a character from the font, returning it if the font had it.
+    pub fn remove_char(&mut self, c: char) -> Option<PixelFontChar>{
+        self.charset.remove(&c)
+    }
+    ///Get the horizontal distance from the start of the given character to the start of the next
+    /// one. Characters missing from the font are drawn as the replacement character.
+    pub fn get_char_advance(&self, c: char) -> usize{
+        match self.get_plain_glyph(c){
+            Some(c) => c.get_advance(),
+            None => 9
+        }
+    }
+    pub fn get_replacement_char(&self) -> char{
+        self.replacement_char
+    }
+    ///Set the character drawn for characters the font lacks, U+FFFD by default. If the font lacks
+    /// it too, an empty box is drawn.
+    pub fn set_replacement_char(&mut self, c: char){
+        self.replacement_char = c;
+    }
+    ///Get the character, or the replacement character if the font lacks it.
+    fn get_plain_glyph(&self, c: char) -> Option<&PixelFontChar>{
+        self.charset.get(&c).or_else(|| self.charset.get(&self.replacement_char))
+    }
+    ///Get the box drawn for missing characters when the font lacks the replacement character:
+    /// 8 pixels wide and as tall as the part of the line above the baseline.
+    fn get_missing_box(&self) -> PixelFontChar{
+        let (width, height) = (8, self.ascent.max(1));
+        let mut pixels = vec![W; width * height];
+        for y in 0..height{
+            for x in 0..width{
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1{
+                    pixels[y * width + x] = B;
+                }
+            }
+        }
+        PixelFontChar{
+            width,
+            height,
+            right_offset: 1,
+            left_bearing: 0,
+            ascent: height as isize,
+            pixels: Box::new(pixels),
+        }
+    }
+    ///Get the adjustment of the distance between the given characters following each other,
+    /// negative to move them closer.
+    pub fn get_kerning(&self, left: char, right: char) -> isize{
+        self.kerning.get(&(left, right)).copied().unwrap_or(0)
+    }
+    ///Set the adjustment of the distance between the given characters, 0 removes the pair.
+    pub fn set_kerning(&mut self, left: char, right: char, adjustment: isize){
+        if adjustment == 0{
+            self.kerning.remove(&(left, right));
+        } else {
+            self.kerning.insert((left, right), adjustment);
+        }
+    }
+    ///Get all kerning pairs with their adjustments.
+    pub fn get_kerning_pairs(&self) -> &BTreeMap<(char, char), isize>{
+        &self.kerning
+    }
+    ///Draw the given sequence of characters as a single character of the font. Only used if the
+    /// font has that character.
+    pub fn add_ligature(&mut self, sequence: &str, glyph: char){
+        if sequence.chars().count() > 1{
+            self.ligatures.insert(String::from(sequence), glyph);
+        }
+    }
+    pub fn get_ligatures(&self) -> &BTreeMap<String, char>{
+        &self.ligatures
+    }
+    ///Place the characters of a line, left to right as they are shown:
+    ///- the text is split into grapheme clusters, see text::get_grapheme_boundaries; a cluster is
+    ///  drawn as its first character, the combining marks in it on top of that and other
+    ///  characters (joiners, variation selectors) not at all.
+    ///- ligatures replace the clusters they stand for (the longest one wins).
+    ///- right-to-left runs are reversed, see text::get_bidi_levels, and the brackets in them
+    ///  mirrored if the font has the mirrored character.
+    ///- every character is moved by its kerning to the one shown left of it.
+    ///
+    ///Combining marks with no advance are drawn at the end of their character, expecting a
+    /// negative left bearing to reach back over it; others are centered above it. Marks the font
+    /// lacks are left out.
+    pub fn layout(&self, text: &[char], style: TextStyle) -> Vec<PlacedChar>{
+        let boundaries = text::get_grapheme_boundaries(text);
+        //units of clusters drawn as one character: start, character, characters it is drawn for,
+        // end including the marks after them
+        let mut units: Vec<(usize, char, usize, usize)> = vec![];
+        let mut b = 0;
+        while b + 1 < boundaries.len(){
+            let i = boundaries[b];
+            let (glyph, len) = self.ligatures.iter()
+                .filter(|(sequence, glyph)| {
+                    let len = sequence.chars().count();
+                    self.charset.contains_key(glyph)
+                        && boundaries.binary_search(&(i + len)).is_ok()
+                        && sequence.chars().zip(text[i..].iter()).filter(|(a, b)| a == *b).count() == len
+                })
+                .map(|(sequence, glyph)| (*glyph, sequence.chars().count()))
+                .max_by_key(|(_, len)| *len)
+                .unwrap_or((text[i], 1));
+            //ligatures end on a cluster boundary, so they take in all of their marks
+            let end = if len == 1 {boundaries[b + 1]} else {i + len};
+            units.push((i, glyph, len, end));
+            b = boundaries.partition_point(|boundary| *boundary < end);
+        }
+        let levels = text::get_bidi_levels(text, text::get_paragraph_direction(text));
+        let unit_levels: Vec<u8> = units.iter().map(|(start, _, _, _)| levels[*start]).collect();
+        let mut placed: Vec<PlacedChar> = Vec::with_capacity(text.len());
+        let mut x = 0isize;
+        let mut previous: Option<char> = None;
+        for u in text::get_visual_order(&unit_levels){
+            let (start, glyph, drawn, end) = units[u];
+            let rtl = unit_levels[u] % 2 == 1;
+            let mirrored = text::get_mirrored(glyph);
+            let glyph = if rtl && self.charset.contains_key(&mirrored) {mirrored} else {glyph};
+            if let Some(previous) = previous{
+                x += self.get_kerning(previous, glyph);
+            }
+            previous = Some(glyph);
+            let advance = self.get_styled_char_advance(glyph, style);
+            placed.push(PlacedChar{glyph, start, len: end - start, x, advance, rtl});
+            for (k, c) in text.iter().enumerate().take(end).skip(start + drawn){
+                if !text::is_combining_mark(*c) || !self.charset.contains_key(c){
+                    continue
+                }
+                let mark_x = match self.get_char_advance(*c){
+                    0 => x + advance as isize,
+                    _ => x + (advance as isize - self.get_styled_char_advance(*c, style) as isize) / 2
+                };
+                placed.push(PlacedChar{glyph: *c, start: k, len: 0, x: mark_x, advance: 0, rtl});
+            }
+            x += advance as isize;
+        }
+        placed
+    }
+    ///Get the x offset of every character boundary of a line, one entry more than there are
+    /// characters. A cluster starts at its kerned position, on its right edge if it is
+    /// right-to-left; the characters of a ligature share its advance and those within a cluster
+    /// its start. The last entry is the end of the last cluster.
+    pub fn get_text_offsets(&self, text: &[char], style: TextStyle) -> Vec<usize>{
+        let placed = self.layout(text, style);
+        let mut offsets = vec![0usize; text.len() + 1];
+        let mut last: Option<&PlacedChar> = None;
+        for p in placed.iter().filter(|p| p.len > 0){
+            let boundaries = text::get_grapheme_boundaries(&text[p.start..p.start + p.len]);
+            for k in 0..p.len{
+                //the cluster this character is in starts at
+                let cluster = boundaries[boundaries.partition_point(|b| *b <= k) - 1];
+                let along = (p.advance * cluster / p.len) as isize;
+                let offset = if p.rtl {p.x + p.advance as isize - along} else {p.x + along};
+                offsets[p.start + k] = offset.max(0) as usize;
+            }
+            if last.is_none_or(|l| l.start < p.start){
+                last = Some(p);
+            }
+        }
+        offsets[text.len()] = match last{
+            Some(p) if p.rtl => p.x.max(0) as usize,
+            Some(p) => (p.x + p.advance as isize).max(0) as usize,
+            None => 0
+        };
+        offsets
+    }
+    ///Get the size of the font in points.
+    pub fn get_size(&self) -> u32{
+        self.size_in_pts
+    }
+    ///Returns true if the font has a glyph for the given character.
+    pub fn has_char(&self, c: char) -> bool{
+        self.charset.contains_key(&c)
+    }
+    ///Get a copy of the font scaled (nearest neighbour) to the given size in points. Scaling by
+    /// a whole factor keeps every pixel square.
+    pub fn scaled(&self, size_in_pts: u32) -> PixelFont{
+        if size_in_pts == self.size_in_pts || size_in_pts == 0{
+            return self.clone()
+        }
+        let scale = |v: usize| (v * size_in_pts as usize + self.size_in_pts as usize / 2) / self.size_in_pts as usize;
+        let scale_signed = |v: isize| v.signum() * scale(v.unsigned_abs()) as isize;
+        let charset = self.charset.iter().map(|(c, glyph)| {
+            //empty glyphs (e.g. spaces) have nothing to sample and stay empty
+            let (width, height) = match glyph.width == 0 || glyph.height == 0{
+                true => (scale(glyph.width), scale(glyph.height)),
+                false => (scale(glyph.width).max(1), scale(glyph.height).max(1))
+            };
+            let mut pixels = Vec::with_capacity(width * height);
+            for y in 0..height{
+                let row = y * glyph.height / height * glyph.width;
+                for x in 0..width{
+                    pixels.push(glyph.pixels[row + x * glyph.width / width]);
+                }
+            }
+            (*c, PixelFontChar{
+                width,
+                height,
+                right_offset: scale(glyph.right_offset),
+                left_bearing: scale_signed(glyph.left_bearing),
+                ascent: scale_signed(glyph.ascent),
+                pixels: Box::new(pixels),
+            })
+        }).collect();
+        PixelFont{
+            size_in_pts,
+            ascent: scale(self.ascent),
+            descent: scale(self.descent),
+            charset: Box::new(charset),
+            kerning: self.kerning.iter().map(|(pair, k)| (*pair, scale_signed(*k))).collect(),
+            ligatures: self.ligatures.clone(),
+            replacement_char: self.replacement_char,
+        }
+    }
+    ///Take the characters this font lacks from another one. They line up on the baseline, the
+    /// line keeps its height.
+    pub fn add_missing_chars(&mut self, other: &PixelFont){
+        for (c, glyph) in other.charset.iter(){
+            if !self.charset.contains_key(c){
+                self.charset.insert(*c, glyph.clone());
+            }
+        }
+    }
+    ///Get the advance of a character drawn in the given style. BOLD adds a pixel, OUTLINE two and
+    /// SHADOW three; the slant of ITALIC overhangs into the next character instead.
+    pub fn get_styled_char_advance(&self, c: char, style: TextStyle) -> usize{
+        self.get_char_advance(c) + style.get_ring_size() + if style.contains(TextStyle::BOLD) {1} else {0}
+    }
+    ///Get the line height of the font in the given style. OUTLINE and SHADOW make the cells taller,
+    /// UNDERLINE adds the row of the line.
+    pub fn get_styled_line_height(&self, style: TextStyle) -> usize{
+        self.get_line_height() + style.get_ring_size() + if style.contains(TextStyle::UNDERLINE) {1} else {0}
+    }
+    ///Synthesize the given style (except UNDERLINE, which spans the whole text) from the plain
+    /// character. Returns None if the font lacks the character.
+    pub fn get_styled_char(&self, c: char, style: TextStyle) -> Option<PixelFontChar>{
+        Some(self.style_glyph(self.charset.get(&c)?, style))
+    }
+    ///Get the character as it is drawn in the given style: the replacement character if the font
+    /// lacks it, a box if it lacks that too.
+    pub fn get_styled_glyph(&self, c: char, style: TextStyle) -> PixelFontChar{
+        match self.get_plain_glyph(c){
+            Some(plain) => self.style_glyph(plain, style),
+            None => self.style_glyph(&self.get_missing_box(), style)
+        }
+    }
+    fn style_glyph(&self, plain: &PixelFontChar, style: TextStyle) -> PixelFontChar{
+        let (mut width, height) = (plain.width, plain.height);
+        let mut pixels: Vec<u8> = plain.pixels.iter().map(|p| p.alpha).collect();
+        if style.contains(TextStyle::ITALIC){
+            //shift every row right by its height above the bottom, divided by the slant
+            let new_width = width + height.saturating_sub(1) / ITALIC_SLANT;
+            let mut slanted = vec![0u8; new_width * height];
+            for y in 0..height{
+                let shift = (height - 1 - y) / ITALIC_SLANT;
+                slanted[y * new_width + shift..y * new_width + shift + width]
+                    .copy_from_slice(&pixels[y * width..(y + 1) * width]);
+            }
+            width = new_width;
+            pixels = slanted;
+        }
+        if style.contains(TextStyle::BOLD){
+            //smear: every pixel also covers the one to its right
+            let new_width = width + 1;
+            let mut smeared = vec![0u8; new_width * height];
+            for y in 0..height{
+                for x in 0..new_width{
+                    let left = if x > 0 {pixels[y * width + x - 1]} else {0};
+                    let own = if x < width {pixels[y * width + x]} else {0};
+                    smeared[y * new_width + x] = left.max(own);
+                }
+            }
+            width = new_width;
+            pixels = smeared;
+        }
+        let ring = style.get_ring_size();
+        if ring > 0{
+            //dilate into the surrounding pixels (two to the bottom and right for the shadow), then
+            // take out the character itself, which is moved one pixel right and down
+            let (new_width, new_height) = (width + ring, height + ring);
+            let reach = ring as isize - 1;
+            let at = |x: isize, y: isize| -> u8{
+                if x < 0 || y < 0 || x >= width as isize || y >= height as isize {0} else {pixels[y as usize * width + x as usize]}
+            };
+            let mut outlined = vec![0u8; new_width * new_height];
+            for y in 0..new_height as isize{
+                for x in 0..new_width as isize{
+                    let mut dilated = 0u8;
+                    for dy in -reach..=1{
+                        for dx in -reach..=1{
+                            dilated = dilated.max(at(x - 1 + dx, y - 1 + dy));
+                        }
+                    }
+                    outlined[y as usize * new_width + x as usize] = dilated.saturating_sub(at(x - 1, y - 1));
+                }
+            }
+            width = new_width;
+            pixels = outlined;
+        }
+        //the ring moves the character one pixel right and down, and reaches one pixel above it
+        let ascent = plain.ascent + if ring > 0 {1} else {0};
+        let advance = plain.get_advance() + ring + if style.contains(TextStyle::BOLD) {1} else {0};
+        PixelFontChar{
+            width,
+            height: pixels.len() / width.max(1),
+            right_offset: (advance as isize - plain.left_bearing - width as isize).max(0) as usize,
+            left_bearing: plain.left_bearing,
+            ascent,
+            pixels: Box::new(pixels.into_iter().map(|alpha| FontPixel{alpha}).collect()),
+        }
+    }
+    ///Load a font from a file, either in the binary format of font_file or a BDF (Glyph Bitmap
+    /// Distribution Format) file. The format is recognized by the contents.
+    pub fn load(path: &Path) -> io::Result<Self>{
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(&font_file::MAGIC){
+            return PixelFont::from_bytes(&bytes)
+        }
+        let text = String::from_utf8(bytes).map_err(|_| invalid_data("font is neither binary nor BDF text"))?;
+        PixelFont::from_bdf(&text)
+    }
+    ///Read a font in the binary format of font_file, e.g. one embedded with include_bytes!.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self>{
+        font_file::decode(bytes)
+    }
+    ///Write the font in the binary format of font_file.
+    pub fn to_bytes(&self) -> Vec<u8>{
+        font_file::encode(self)
+    }
+    ///Write the font to a file in the binary format of font_file.
+    pub fn save(&self, path: &Path) -> io::Result<()>{
+        fs::write(path, self.to_bytes())
+    }
+    ///Parse a font in the BDF text format. Every character is drawn in a cell as tall as the font
+    /// bounding box, with the baselines lined up, and as wide as its advance. Characters without a
+    /// Unicode encoding are left out.
+    pub fn from_bdf(text: &str) -> io::Result<Self>{
+        let error = |line: usize, message: &str| invalid_data(&format!("line {}: {}", line + 1, message));
+        let numbers = |line: usize, args: &[&str], count: usize| -> io::Result<Vec<isize>>{
+            if args.len() < count{
+                return Err(error(line, &format!("expected {} numbers", count)))
+            }
+            args[..count].iter()
+                .map(|a| a.parse::<isize>().map_err(|_| error(line, &format!("'{}' is not a number", a))))
+                .collect()
+        };
+        let mut size_in_pts = 12;
+        //height of the font bounding box and the part of it above the baseline
+        let mut cell: Option<(usize, isize)> = None;
+        let mut charset = BTreeMap::new();
+        let mut lines = text.lines().enumerate();
+        while let Some((idx, line)) = lines.next(){
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first(){
+                Some(&"SIZE") => size_in_pts = numbers(idx, &words[1..], 1)?[0].max(1) as u32,
+                Some(&"FONTBOUNDINGBOX") => {
+                    let n = numbers(idx, &words[1..], 4)?;
+                    cell = Some((n[1].max(0) as usize, n[1] + n[3]));
+                },
+                Some(&"STARTCHAR") => {
+                    let (cell_height, ascent) = cell.ok_or_else(|| error(idx, "STARTCHAR before FONTBOUNDINGBOX"))?;
+                    let mut encoding = None;
+                    let mut advance = None;
+                    let mut bbx = None;
+                    //read the properties of the character up to its bitmap
+                    loop{
+                        let (idx, line) = lines.next().ok_or_else(|| error(idx, "character is not finished"))?;
+                        let words: Vec<&str> = line.split_whitespace().collect();
+                        match words.first(){
+                            Some(&"ENCODING") => encoding = Some(numbers(idx, &words[1..], 1)?[0]),
+                            Some(&"DWIDTH") => advance = Some(numbers(idx, &words[1..], 1)?[0].max(0) as usize),
+                            Some(&"BBX") => bbx = Some(numbers(idx, &words[1..], 4)?),
+                            Some(&"BITMAP") => break,
+                            Some(&"ENDCHAR") => return Err(error(idx, "character has no BITMAP")),
+                            _ => {}
+                        }
+                    }
+                    let bbx = bbx.ok_or_else(|| error(idx, "character has no BBX"))?;
+                    let (w, h, xoff, yoff) = (bbx[0].max(0) as usize, bbx[1].max(0) as usize, bbx[2], bbx[3]);
+                    let advance = advance.unwrap_or((xoff + w as isize).max(0) as usize);
+                    let width = advance.max((xoff + w as isize).max(0) as usize).max(1);
+                    let mut pixels = vec![W; width * cell_height];
+                    //rows of the bitmap, padded to whole bytes with the leftmost pixel in the highest bit
+                    let mut row = 0;
+                    loop{
+                        let (idx, line) = lines.next().ok_or_else(|| error(idx, "character has no ENDCHAR"))?;
+                        let line = line.trim();
+                        if line == "ENDCHAR"{
+                            break
+                        }
+                        if line.len() % 2 != 0 || !line.chars().all(|c| c.is_ascii_hexdigit()){
+                            return Err(error(idx, &format!("'{}' is not a bitmap row", line)))
+                        }
+                        let bytes: Vec<u8> = (0..line.len()).step_by(2)
+                            .map(|i| u8::from_str_radix(&line[i..i + 2], 16).unwrap())
+                            .collect();
+                        let y = ascent - (yoff + h as isize) + row;
+                        row += 1;
+                        if y < 0 || y >= cell_height as isize{
+                            continue
+                        }
+                        for x in 0..w.min(bytes.len() * 8){
+                            let px = xoff + x as isize;
+                            if px >= 0 && (px as usize) < width && bytes[x / 8] & (0x80 >> (x % 8)) != 0{
+                                pixels[y as usize * width + px as usize] = B;
+                            }
+                        }
+                    }
+                    if let Some(c) = encoding.and_then(|e| u32::try_from(e).ok()).and_then(char::from_u32){
+                        charset.insert(c, PixelFontChar{
+                            width,
+                            height: cell_height,
+                            right_offset: 0,
+                            left_bearing: 0,
+                            ascent,
+                            pixels: Box::new(pixels),
+                        });
+                    }
+                },
+                _ => {}
+            }
+        }
+        if charset.is_empty(){
+            return Err(invalid_data("font has no characters"))
+        }
+        let (cell_height, ascent) = cell.unwrap_or((0, 0));
+        Ok(PixelFont{
+            size_in_pts,
+            ascent: ascent.max(0) as usize,
+            descent: (cell_height as isize - ascent).max(0) as usize,
+            charset: Box::new(charset),
+            kerning: BTreeMap::new(),
+            ligatures: BTreeMap::new(),
+            replacement_char: REPLACEMENT_CHARACTER,
+        })
+    }
     pub fn default() -> Self{
         PixelFont{
             size_in_pts: 12,
+            ascent: 16,
+            descent: 0,
+            kerning: BTreeMap::new(),
+            ligatures: BTreeMap::new(),
+            replacement_char: REPLACEMENT_CHARACTER,
             charset: Box::new(BTreeMap::from(
                 [('B', PixelFontChar{
                         width: 8,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             B, B, B, B, B, B, W, W,
                             B, B, B, B, B, B, B, W,
@@ -56,6 +716,8 @@ impl PixelFont {
                         width: 8,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, W, W, W,
                             W, W, W, W, W, W, W, W,
@@ -79,6 +741,8 @@ impl PixelFont {
                         width: 6,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, B, B, W, W,
                             W, W, B, B, W, W,
@@ -105,6 +769,8 @@ impl PixelFont {
                         width: 6,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, W,
                             W, W, W, W, W, W,
@@ -129,6 +795,8 @@ impl PixelFont {
                         width: 7,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, W, W,
                             W, W, W, W, W, W, W,
@@ -152,6 +820,8 @@ impl PixelFont {
                         width: 8,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, W, W, W,
                             W, W, W, W, W, W, W, W,
@@ -175,6 +845,8 @@ impl PixelFont {
                         width: 8,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, B, B, B, B, B, B, B,
                             B, B, B, B, B, B, B, W,
@@ -198,6 +870,8 @@ impl PixelFont {
                         width:2,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W,
                             W, W,
@@ -222,6 +896,8 @@ impl PixelFont {
                         width: 2,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W,
                             B, B,
@@ -245,6 +921,8 @@ impl PixelFont {
                         width: 6,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, W,
                             W, W, W, W, W, W,
@@ -268,6 +946,8 @@ impl PixelFont {
                         width: 8,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, B, B, B, B, W, W,
                             W, B, B, B, B, B, B, W,
@@ -291,6 +971,8 @@ impl PixelFont {
                         width:6,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, W,
                             W, W, W, W, W, W,
@@ -314,6 +996,8 @@ impl PixelFont {
                         width: 7,
                         height: 16,
                         right_offset: 0,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, W, W, W, B, B,
                             W, W, W, W, W, B, B,
@@ -337,6 +1021,8 @@ impl PixelFont {
                         width: 4,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             W, W, B, B,
                             W, B, B, W,
@@ -360,6 +1046,8 @@ impl PixelFont {
                         width: 4,
                         height: 16,
                         right_offset: 1,
+                        left_bearing: 0,
+                        ascent: 16,
                         pixels: Box::new(vec![
                             B, B, W, W,
                             W, B, B, W,
@@ -384,4 +1072,77 @@ impl PixelFont {
         }
 
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///A font with square glyphs as wide as given, kerning between 'a' and 'b' and an "fi" ligature.
+    fn test_font() -> PixelFont{
+        let mut font = PixelFont::new(12, 8, 2);
+        for (c, width) in [('a', 4), ('b', 4), ('f', 3), ('i', 2), ('\u{fb01}', 6), ('\u{5d0}', 5), ('\u{5d1}', 5), ('(', 2), (')', 3)]{
+            font.set_char(c, PixelFontChar::new(width, 8, vec![FontPixel::new(255u8); width * 8]));
+        }
+        font.set_kerning('a', 'b', -1);
+        font.add_ligature("fi", '\u{fb01}');
+        font
+    }
+
+    fn chars(text: &str) -> Vec<char>{
+        text.chars().collect()
+    }
+
+    #[test]
+    fn kerning_moves_characters(){
+        let font = test_font();
+        let placed = font.layout(&chars("aba"), TextStyle::PLAIN);
+        assert_eq!(placed.iter().map(|p| p.x).collect::<Vec<_>>(), [0, 3, 7]);
+        assert_eq!(font.get_text_offsets(&chars("ab"), TextStyle::PLAIN), [0, 3, 7]);
+        assert_eq!(font.get_text_offsets(&chars("ba"), TextStyle::PLAIN), [0, 4, 8]);
+    }
+
+    #[test]
+    fn ligatures_share_their_advance(){
+        let font = test_font();
+        let placed = font.layout(&chars("fia"), TextStyle::PLAIN);
+        assert_eq!((placed[0].glyph, placed[0].len, placed[0].advance), ('\u{fb01}', 2, 6));
+        assert_eq!(font.get_text_offsets(&chars("fia"), TextStyle::PLAIN), [0, 3, 6, 10]);
+        //the parts are drawn on their own where the ligature glyph is missing
+        let mut font = font;
+        font.remove_char('\u{fb01}');
+        assert_eq!(font.get_text_offsets(&chars("fi"), TextStyle::PLAIN), [0, 3, 5]);
+    }
+
+    #[test]
+    fn right_to_left_offsets_start_on_the_right(){
+        let font = test_font();
+        let text = chars("\u{5d0}\u{5d1}");
+        let placed = font.layout(&text, TextStyle::PLAIN);
+        assert_eq!(placed.iter().map(|p| (p.glyph, p.x, p.rtl)).collect::<Vec<_>>(),
+            [('\u{5d1}', 0, true), ('\u{5d0}', 5, true)]);
+        assert_eq!(font.get_text_offsets(&text, TextStyle::PLAIN), [10, 5, 0]);
+        //Latin after Hebrew in a right-to-left paragraph is shown left of it
+        let text = chars("\u{5d0} ab");
+        assert_eq!(font.get_text_offsets(&text, TextStyle::PLAIN)[2..], [0, 3, 7]);
+    }
+
+    #[test]
+    fn brackets_are_mirrored_in_right_to_left_text(){
+        let font = test_font();
+        let placed = font.layout(&chars("\u{5d0}(\u{5d1}"), TextStyle::PLAIN);
+        assert_eq!(placed.iter().map(|p| p.glyph).collect::<Vec<_>>(), ['\u{5d1}', ')', '\u{5d0}']);
+        let placed = font.layout(&chars("a(b"), TextStyle::PLAIN);
+        assert_eq!(placed[1].glyph, '(');
+    }
+
+    #[test]
+    fn empty_glyphs_stay_empty_when_scaled(){
+        let mut font = PixelFont::new(10, 8, 2);
+        font.set_char(' ', PixelFontChar::new(0, 8, vec![]));
+        font.set_char('.', PixelFontChar::new(2, 2, vec![FontPixel::new(255u8); 4]));
+        let scaled = font.scaled(20);
+        let space = scaled.get_char(' ').unwrap();
+        assert_eq!((space.get_width(), space.get_height(), space.get_pixels().len()), (0, 16, 0));
+        assert_eq!(scaled.get_char('.').unwrap().get_pixels().len(), 16);
+    }
+}
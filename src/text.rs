@@ -0,0 +1,370 @@
+///Writing direction of a paragraph or run of text.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Direction{
+    LeftToRight,
+    RightToLeft,
+}
+
+///Ranges of non-spacing combining marks, drawn on top of the character before them.
+const COMBINING_MARKS: [(u32, u32); 30] = [
+    (0x0300, 0x036f), (0x0483, 0x0489), (0x0591, 0x05bd), (0x05bf, 0x05bf), (0x05c1, 0x05c2),
+    (0x05c4, 0x05c5), (0x05c7, 0x05c7), (0x0610, 0x061a), (0x064b, 0x065f), (0x0670, 0x0670),
+    (0x06d6, 0x06dc), (0x06df, 0x06e4), (0x06e7, 0x06e8), (0x06ea, 0x06ed), (0x0900, 0x0903),
+    (0x093a, 0x093c), (0x093e, 0x094f), (0x0951, 0x0957), (0x0962, 0x0963), (0x0e31, 0x0e31),
+    (0x0e34, 0x0e3a), (0x0e47, 0x0e4e), (0x1ab0, 0x1aff), (0x1dc0, 0x1dff), (0x20d0, 0x20ff),
+    (0xfe20, 0xfe2f), (0x08d3, 0x08ff), (0x0eb1, 0x0eb1), (0x0eb4, 0x0ebc), (0x0ec8, 0x0ecd),
+];
+///Characters that belong to the cluster before them without being drawn: zero width joiner,
+/// variation selectors, emoji skin tone modifiers and tags.
+const INVISIBLE_EXTENDERS: [(u32, u32); 5] = [
+    (0x200d, 0x200d), (0xfe00, 0xfe0f), (0x1f3fb, 0x1f3ff), (0xe0020, 0xe007f), (0xe0100, 0xe01ef),
+];
+///Ranges of right-to-left letters (Hebrew and others), bidi class R.
+const RIGHT_TO_LEFT: [(u32, u32); 4] = [
+    (0x0590, 0x05ff), (0x07c0, 0x085f), (0xfb1d, 0xfb4f), (0x10800, 0x10fff),
+];
+///Ranges of Arabic letters, bidi class AL.
+const ARABIC: [(u32, u32); 4] = [
+    (0x0600, 0x07bf), (0x0860, 0x08ff), (0xfb50, 0xfdff), (0xfe70, 0xfefe),
+];
+///Pairs of characters swapped when shown right-to-left.
+const MIRRORED: [(char, char); 6] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>'), ('«', '»'), ('‹', '›')];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool{
+    ranges.iter().any(|(start, end)| (*start..=*end).contains(&(c as u32)))
+}
+
+///Returns true if the character is a combining mark, drawn on top of the one before it.
+pub fn is_combining_mark(c: char) -> bool{
+    in_ranges(c, &COMBINING_MARKS)
+}
+
+///Returns true if the character extends the cluster before it, drawn or not.
+fn is_extender(c: char) -> bool{
+    is_combining_mark(c) || in_ranges(c, &INVISIBLE_EXTENDERS)
+}
+
+fn is_regional_indicator(c: char) -> bool{
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+///Get the indices where grapheme clusters (what a reader sees as one character) start, plus the
+/// length of the text. A cluster is a character with the combining marks and other extenders
+/// after it, a sequence joined by zero width joiners, a pair of regional indicators (a flag) or
+/// CR LF.
+pub fn get_grapheme_boundaries(text: &[char]) -> Vec<usize>{
+    let mut boundaries = vec![];
+    let mut i = 0;
+    while i < text.len(){
+        boundaries.push(i);
+        let first = text[i];
+        i += 1;
+        if first == '\r' && text.get(i) == Some(&'\n'){
+            i += 1;
+            continue
+        }
+        if is_regional_indicator(first) && text.get(i).is_some_and(|c| is_regional_indicator(*c)){
+            i += 1;
+        }
+        while i < text.len() && is_extender(text[i]){
+            //a zero width joiner takes the character after it into the cluster
+            if text[i] == '\u{200d}' && i + 1 < text.len(){
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+    boundaries.push(text.len());
+    boundaries
+}
+
+///Get the grapheme cluster boundary after the given index, or the length of the text.
+pub fn next_grapheme_boundary(text: &[char], index: usize) -> usize{
+    get_grapheme_boundaries(text).into_iter().find(|b| *b > index).unwrap_or(text.len())
+}
+
+///Get the grapheme cluster boundary before the given index, or 0.
+pub fn previous_grapheme_boundary(text: &[char], index: usize) -> usize{
+    get_grapheme_boundaries(text).into_iter().rev().find(|b| *b < index).unwrap_or(0)
+}
+
+///Simplified bidi classes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum BidiClass{
+    Left,
+    Right,
+    ArabicLetter,
+    EuropeanNumber,
+    ArabicNumber,
+    NonSpacingMark,
+    ///Separators within numbers, like the ',' in "1,000".
+    NumberSeparator,
+    ///Signs belonging to numbers, like '%' or '$'.
+    NumberTerminator,
+    Whitespace,
+    Neutral,
+}
+
+fn get_bidi_class(c: char) -> BidiClass{
+    if is_combining_mark(c){
+        BidiClass::NonSpacingMark
+    } else if c.is_ascii_digit() || ('\u{06f0}'..='\u{06f9}').contains(&c){
+        BidiClass::EuropeanNumber
+    } else if ('\u{0660}'..='\u{0669}').contains(&c) || c == '\u{066b}' || c == '\u{066c}'{
+        BidiClass::ArabicNumber
+    } else if in_ranges(c, &ARABIC){
+        BidiClass::ArabicLetter
+    } else if in_ranges(c, &RIGHT_TO_LEFT){
+        BidiClass::Right
+    } else if matches!(c, ',' | '.' | ':' | '/' | '+' | '-'){
+        BidiClass::NumberSeparator
+    } else if matches!(c, '#' | '$' | '%' | '°' | '¢' | '£' | '¥' | '€'){
+        BidiClass::NumberTerminator
+    } else if c.is_whitespace(){
+        BidiClass::Whitespace
+    } else if c.is_alphabetic(){
+        BidiClass::Left
+    } else {
+        BidiClass::Neutral
+    }
+}
+
+///Get the direction of a character with a strong direction (letters), None for digits,
+/// punctuation, spaces and marks.
+pub fn get_strong_direction(c: char) -> Option<Direction>{
+    match get_bidi_class(c){
+        BidiClass::Left => Some(Direction::LeftToRight),
+        BidiClass::Right | BidiClass::ArabicLetter => Some(Direction::RightToLeft),
+        _ => None
+    }
+}
+
+///Get the direction of a paragraph: that of its first letter with a strong direction,
+/// left-to-right if there is none.
+pub fn get_paragraph_direction(text: &[char]) -> Direction{
+    text.iter().find_map(|c| get_strong_direction(*c)).unwrap_or(Direction::LeftToRight)
+}
+
+///Get the embedding level of every character of a paragraph: even levels are shown left-to-right,
+/// odd ones right-to-left. This is the bidirectional algorithm without explicit embeddings and
+/// isolates, and with simplified character classes.
+pub fn get_bidi_levels(text: &[char], direction: Direction) -> Vec<u8>{
+    let base = match direction{
+        Direction::LeftToRight => 0u8,
+        Direction::RightToLeft => 1u8,
+    };
+    let base_class = if base == 0 {BidiClass::Left} else {BidiClass::Right};
+    let mut classes: Vec<BidiClass> = text.iter().map(|c| get_bidi_class(*c)).collect();
+    //marks take the class of the character before them, numbers after Arabic letters are Arabic
+    let mut last_strong = base_class;
+    for i in 0..classes.len(){
+        if classes[i] == BidiClass::NonSpacingMark{
+            classes[i] = if i > 0 {classes[i - 1]} else {base_class};
+        }
+        match classes[i]{
+            BidiClass::Left | BidiClass::Right | BidiClass::ArabicLetter => last_strong = classes[i],
+            BidiClass::EuropeanNumber if last_strong == BidiClass::ArabicLetter => classes[i] = BidiClass::ArabicNumber,
+            _ => {}
+        }
+    }
+    for class in classes.iter_mut(){
+        if *class == BidiClass::ArabicLetter{
+            *class = BidiClass::Right;
+        }
+    }
+    //a single separator between two numbers of the same kind joins them, signs next to European
+    // numbers belong to them
+    for i in 1..classes.len().saturating_sub(1){
+        if classes[i] == BidiClass::NumberSeparator && classes[i - 1] == classes[i + 1]
+            && matches!(classes[i - 1], BidiClass::EuropeanNumber | BidiClass::ArabicNumber){
+            classes[i] = classes[i - 1];
+        }
+    }
+    for i in 0..classes.len(){
+        if classes[i] != BidiClass::EuropeanNumber{
+            continue
+        }
+        let mut j = i;
+        while j > 0 && classes[j - 1] == BidiClass::NumberTerminator{
+            j -= 1;
+            classes[j] = BidiClass::EuropeanNumber;
+        }
+        let mut j = i + 1;
+        while j < classes.len() && classes[j] == BidiClass::NumberTerminator{
+            classes[j] = BidiClass::EuropeanNumber;
+            j += 1;
+        }
+    }
+    //European numbers in left-to-right text are left-to-right
+    let mut last_strong = base_class;
+    for class in classes.iter_mut(){
+        match *class{
+            BidiClass::Left | BidiClass::Right => last_strong = *class,
+            BidiClass::EuropeanNumber if last_strong == BidiClass::Left => *class = BidiClass::Left,
+            _ => {}
+        }
+    }
+    //neutrals between two runs of the same direction take it, others that of the paragraph;
+    // numbers count as right-to-left here
+    let direction_of = |class: BidiClass| -> Option<BidiClass>{
+        match class{
+            BidiClass::Left => Some(BidiClass::Left),
+            BidiClass::Right | BidiClass::EuropeanNumber | BidiClass::ArabicNumber => Some(BidiClass::Right),
+            _ => None
+        }
+    };
+    let mut i = 0;
+    while i < classes.len(){
+        if direction_of(classes[i]).is_some(){
+            i += 1;
+            continue
+        }
+        let start = i;
+        while i < classes.len() && direction_of(classes[i]).is_none(){
+            i += 1;
+        }
+        let before = if start > 0 {direction_of(classes[start - 1]).unwrap()} else {base_class};
+        let after = if i < classes.len() {direction_of(classes[i]).unwrap()} else {base_class};
+        let resolved = if before == after {before} else {base_class};
+        for class in classes[start..i].iter_mut(){
+            *class = resolved;
+        }
+    }
+    let mut levels: Vec<u8> = classes.iter().map(|class| {
+        match (base % 2 == 0, class){
+            (true, BidiClass::Right) => base + 1,
+            (true, BidiClass::EuropeanNumber | BidiClass::ArabicNumber) => base + 2,
+            (false, BidiClass::Left | BidiClass::EuropeanNumber | BidiClass::ArabicNumber) => base + 1,
+            _ => base
+        }
+    }).collect();
+    //whitespace at the end of the line goes back to the paragraph level
+    for (i, c) in text.iter().enumerate().rev(){
+        if !c.is_whitespace(){
+            break
+        }
+        levels[i] = base;
+    }
+    levels
+}
+
+///Get the order the characters (or clusters) with the given levels are shown in, left to right,
+/// as indices into levels: from the highest level down to the lowest odd one, every run at or
+/// above the level is reversed.
+pub fn get_visual_order(levels: &[u8]) -> Vec<usize>{
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().copied().filter(|l| l % 2 == 1).min().unwrap_or(highest + 1);
+    let mut level = highest;
+    while level >= lowest_odd && level > 0{
+        let mut i = 0;
+        while i < order.len(){
+            if levels[order[i]] < level{
+                i += 1;
+                continue
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level{
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+        level -= 1;
+    }
+    order
+}
+
+///Get the character shown instead of the given one in right-to-left text, e.g. ')' for '('.
+pub fn get_mirrored(c: char) -> char{
+    for (open, close) in MIRRORED{
+        if c == open{
+            return close
+        }
+        if c == close{
+            return open
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char>{
+        text.chars().collect()
+    }
+
+    #[test]
+    fn marks_stay_with_their_character(){
+        let text = chars("e\u{301}x");
+        assert_eq!(get_grapheme_boundaries(&text), [0, 2, 3]);
+        assert_eq!(next_grapheme_boundary(&text, 0), 2);
+        assert_eq!(previous_grapheme_boundary(&text, 2), 0);
+        //a mark at the start has nothing to stay with
+        assert_eq!(get_grapheme_boundaries(&chars("\u{301}e")), [0, 1, 2]);
+    }
+
+    #[test]
+    fn joined_emoji_are_one_cluster(){
+        //man, woman and girl joined by zero width joiners, between two letters
+        let text = chars("a\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}b");
+        assert_eq!(get_grapheme_boundaries(&text), [0, 1, 6, 7]);
+        //skin tones and variation selectors belong to the emoji before them
+        assert_eq!(get_grapheme_boundaries(&chars("\u{1f44d}\u{1f3fd}\u{2764}\u{fe0f}")), [0, 2, 4]);
+    }
+
+    #[test]
+    fn flags_are_pairs_of_regional_indicators(){
+        //DE FR, and a lone indicator left over
+        assert_eq!(get_grapheme_boundaries(&chars("\u{1f1e9}\u{1f1ea}\u{1f1eb}\u{1f1f7}")), [0, 2, 4]);
+        assert_eq!(get_grapheme_boundaries(&chars("\u{1f1e9}\u{1f1ea}\u{1f1eb}")), [0, 2, 3]);
+    }
+
+    #[test]
+    fn cr_lf_is_one_cluster(){
+        assert_eq!(get_grapheme_boundaries(&chars("a\r\nb")), [0, 1, 3, 4]);
+        assert_eq!(get_grapheme_boundaries(&chars("\n\r")), [0, 1, 2]);
+        assert_eq!(get_grapheme_boundaries(&[]), [0]);
+    }
+
+    #[test]
+    fn mixed_text_gets_levels_and_order(){
+        //Latin, Hebrew and digits in a left-to-right paragraph: the digits stay left-to-right
+        // within the right-to-left run, the space before them joins it
+        let text = chars("abc \u{5d0}\u{5d1}\u{5d2} 123");
+        assert_eq!(get_paragraph_direction(&text), Direction::LeftToRight);
+        let levels = get_bidi_levels(&text, Direction::LeftToRight);
+        assert_eq!(levels, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2]);
+        assert_eq!(get_visual_order(&levels), [0, 1, 2, 3, 8, 9, 10, 7, 6, 5, 4]);
+        //a paragraph starting with Hebrew is right-to-left, digits don't count
+        assert_eq!(get_paragraph_direction(&chars("12 \u{5d0}b")), Direction::RightToLeft);
+        assert_eq!(get_paragraph_direction(&chars("12 ")), Direction::LeftToRight);
+    }
+
+    #[test]
+    fn numbers_keep_their_signs_and_separators(){
+        let text = chars("\u{5d0} 1,000% \u{5d1}");
+        let levels = get_bidi_levels(&text, Direction::RightToLeft);
+        assert_eq!(levels, [1, 1, 2, 2, 2, 2, 2, 2, 1, 1]);
+        assert_eq!(get_visual_order(&levels), [9, 8, 2, 3, 4, 5, 6, 7, 1, 0]);
+    }
+
+    #[test]
+    fn trailing_whitespace_goes_back_to_the_paragraph_level(){
+        //the space between the letter and the number is part of the left-to-right run, the ones
+        // at the end are not
+        let text = chars("a 1  ");
+        let levels = get_bidi_levels(&text, Direction::RightToLeft);
+        assert_eq!(levels, [2, 2, 2, 1, 1]);
+        assert_eq!(get_visual_order(&levels), [4, 3, 0, 1, 2]);
+        assert_eq!(get_visual_order(&get_bidi_levels(&text, Direction::LeftToRight)), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn brackets_are_mirrored(){
+        assert_eq!(get_mirrored('('), ')');
+        assert_eq!(get_mirrored('»'), '«');
+        assert_eq!(get_mirrored('a'), 'a');
+    }
+}
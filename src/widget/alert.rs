@@ -0,0 +1,254 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::pixel_font::PixelFont;
+use crate::widget::button::Button;
+use crate::widget::container::ContainerWidget;
+use crate::widget::text_widget::TextWidget;
+use crate::widget::window::WindowWidget;
+use crate::widget::{draw_rounded_rect, Color, Widget, WidgetBounds};
+
+///Width of alert windows.
+const ALERT_WIDTH: usize = 360;
+///Size of the alert icons.
+const ICON_SIZE: usize = 32;
+///Space between the edges of the alert and its contents.
+const ALERT_MARGIN: usize = 16;
+///Offset of the message, right of the icon.
+const MESSAGE_X: usize = ALERT_MARGIN * 2 + ICON_SIZE;
+///Space between two lines of the message.
+const LINE_SPACING: usize = 2;
+///Width of the alert buttons (including the room for the default ring) and space between them.
+const BUTTON_WIDTH: usize = 80;
+const BUTTON_SPACING: usize = 8;
+///Height of the alert buttons, including the room for the default ring.
+const BUTTON_HEIGHT: usize = 28;
+///Maximum number of buttons in an alert.
+const MAX_BUTTONS: usize = 3;
+
+///Icon shown left of the message of an alert.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AlertIcon{
+    ///Information: a speech balloon.
+    Note,
+    ///A warning the user should think about: an exclamation mark in a triangle.
+    Caution,
+    ///Something went wrong: an exclamation mark in a stop sign.
+    Stop,
+}
+
+///Draw an exclamation mark 4 pixels wide and 19 pixels high, centered on the given column.
+fn draw_exclamation_mark(buf: &mut [[u8; 4]], center_x: usize, top: usize, color: [u8; 4]){
+    for y in top..top + 19{
+        if y >= top + 13 && y < top + 15{
+            continue
+        }
+        for x in center_x - 2..center_x + 2{
+            buf[y * ICON_SIZE + x] = color;
+        }
+    }
+}
+
+impl AlertIcon{
+    ///Render the icon, black on white, at 32x32 pixels.
+    pub fn render(&self) -> Vec<[u8; 4]>{
+        let black = [0u8, 0u8, 0u8, 255u8];
+        let mut buf = vec![[255u8; 4]; ICON_SIZE * ICON_SIZE];
+        match self{
+            AlertIcon::Note => {
+                //balloon with a tail at the bottom left and three lines of "text" inside
+                draw_rounded_rect(&mut buf, ICON_SIZE, (0, 2, ICON_SIZE, 22), 8, 2, black, None);
+                for row in 0..8{
+                    for x in 6 + row / 2..10 + row / 4{
+                        buf[(23 + row) * ICON_SIZE + x] = black;
+                    }
+                }
+                for line in 0..3{
+                    for x in 7..25 - line * 4{
+                        buf[(8 + line * 4) * ICON_SIZE + x] = black;
+                        buf[(9 + line * 4) * ICON_SIZE + x] = black;
+                    }
+                }
+            },
+            AlertIcon::Caution => {
+                //triangle with a 2 pixel outline, pointing up
+                for y in 1..ICON_SIZE - 1{
+                    let half = y / 2;
+                    let (left, right) = (15 - half, 16 + half);
+                    for x in left..right + 1{
+                        let edge = y >= ICON_SIZE - 3 || x < left + 2 || x > right - 2;
+                        if edge{
+                            buf[y * ICON_SIZE + x] = black;
+                        }
+                    }
+                }
+                draw_exclamation_mark(&mut buf, 16, 9, black);
+            },
+            AlertIcon::Stop => {
+                //filled octagon with the corners cut off
+                for y in 0..ICON_SIZE{
+                    let cut = 9usize.saturating_sub(y.min(ICON_SIZE - 1 - y));
+                    for x in cut..ICON_SIZE - cut{
+                        buf[y * ICON_SIZE + x] = black;
+                    }
+                }
+                draw_exclamation_mark(&mut buf, 16, 6, [255u8; 4]);
+            },
+        }
+        buf
+    }
+}
+
+///Widget showing an alert icon.
+struct AlertIconWidget{
+    icon: AlertIcon,
+    cache: Box<Vec<[u8; 4]>>,
+}
+
+impl Widget for AlertIconWidget{
+    fn render(&mut self, _width: usize, _height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.cache.is_empty(){
+            return None
+        }
+        let buf = self.icon.render();
+        self.cache = Box::new(buf.clone());
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: ICON_SIZE,
+            height: ICON_SIZE,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+}
+
+///Break text into lines no wider than the given width, at spaces. Words wider than a line are
+/// put on a line of their own.
+fn wrap_text(font: &PixelFont, text: &str, width: usize) -> Vec<String>{
+    let measure = |s: &str| s.chars().map(|c| font.get_char_advance(c)).sum::<usize>();
+    let mut lines = vec![];
+    for paragraph in text.split('\n'){
+        let mut line = String::new();
+        for word in paragraph.split(' '){
+            if !line.is_empty() && measure(&line) + measure(" ") + measure(word) > width{
+                lines.push(line);
+                line = String::new();
+            }
+            if !line.is_empty(){
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+///An alert: a small modal window telling the user something, with an icon, a message and up to
+/// three buttons. The first button is the default button (the rightmost one, clicked with
+/// Return), the second one the cancel button (clicked with Escape). An alert with a single
+/// button is dismissed with either key.
+pub struct Alert{
+    icon: AlertIcon,
+    message: String,
+    buttons: Vec<String>,
+    on_result: Option<Box<dyn FnMut(usize)>>,
+}
+
+impl Alert{
+    ///Create a new alert with the given icon, message and button labels. Only the first three
+    /// buttons are used; without any buttons the alert gets a single "OK" button.
+    pub fn new(icon: AlertIcon, message: &str, buttons: &[&str]) -> Self{
+        let mut buttons: Vec<String> = buttons.iter().take(MAX_BUTTONS).map(|b| String::from(*b)).collect();
+        if buttons.is_empty(){
+            buttons.push(String::from("OK"));
+        }
+        Alert{
+            icon,
+            message: String::from(message),
+            buttons,
+            on_result: None,
+        }
+    }
+    ///Set the callback run with the index of the button that dismissed the alert.
+    pub fn set_on_result(&mut self, callback: Box<dyn FnMut(usize)>){
+        self.on_result = Some(callback);
+    }
+    ///Build the modal window showing the alert, centered on a screen of the given size. Usually
+    /// shown with MainWidget::show_alert.
+    pub fn into_window(self, screen_width: usize, screen_height: usize) -> WindowWidget{
+        let font = PixelFont::default();
+        let cancel = if self.buttons.len() > 1 {1} else {0};
+        let buttons: Vec<Button> = self.buttons.iter().enumerate().map(|(i, label)| {
+            let mut button = Button::new(label, BUTTON_WIDTH);
+            button.set_default(i == 0);
+            button.set_cancel(i == cancel);
+            button
+        }).collect();
+        //buttons grow to fit their labels, the alert grows to fit the buttons
+        let buttons_width = buttons.iter().map(|b| b.get_min_bounds().width).sum::<usize>()
+            + (buttons.len() - 1) * BUTTON_SPACING;
+        let body_width = (ALERT_WIDTH - 2).max(buttons_width + ALERT_MARGIN * 2);
+        let lines = wrap_text(&font, &self.message, body_width - MESSAGE_X - ALERT_MARGIN);
+        let line_height = font.get_line_height() + LINE_SPACING;
+        let text_bottom = (ALERT_MARGIN + lines.len() * line_height).max(ALERT_MARGIN + ICON_SIZE);
+        let button_y = text_bottom + ALERT_MARGIN;
+        let body_height = button_y + BUTTON_HEIGHT + ALERT_MARGIN;
+
+        let mut window = WindowWidget::new_modal("", body_width, body_height, screen_width, screen_height);
+        let mut body = ContainerWidget::new(body_width, body_height);
+        body.add_child(Box::new(AlertIconWidget{
+            icon: self.icon,
+            cache: Box::new(vec![]),
+        }), ALERT_MARGIN, ALERT_MARGIN);
+        for (i, line) in lines.iter().enumerate(){
+            body.add_child(Box::new(TextWidget::new(
+                Box::new(PixelFont::default()), false, line, Color::black(), Color::white())),
+                MESSAGE_X, ALERT_MARGIN + i * line_height);
+        }
+        let on_result = Rc::new(RefCell::new(self.on_result));
+        let mut button_x = body_width - ALERT_MARGIN;
+        for (i, mut button) in buttons.into_iter().enumerate(){
+            let (closer, on_result) = (window.get_closer(), on_result.clone());
+            button.set_on_click(Box::new(move || {
+                closer.close();
+                if let Some(callback) = on_result.borrow_mut().as_mut(){
+                    callback(i);
+                }
+            }));
+            button_x -= button.get_min_bounds().width;
+            body.add_child(Box::new(button), button_x, button_y);
+            button_x = button_x.saturating_sub(BUTTON_SPACING);
+        }
+        window.set_body(Box::new(body));
+        window
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn short_alerts_keep_their_width(){
+        let window = Alert::new(AlertIcon::Note, "Saved.", &["OK"]).into_window(720, 480);
+        assert_eq!(window.get_min_bounds().width, ALERT_WIDTH);
+    }
+
+    #[test]
+    fn alerts_grow_to_fit_wide_buttons(){
+        let labels = ["Save and Close Document", "Cancel Without Saving", "Discard All Changes"];
+        let buttons_width: usize = labels.iter().map(|l| Button::new(l, BUTTON_WIDTH).get_min_bounds().width).sum();
+        let mut window = Alert::new(AlertIcon::Caution, "Save changes?", &labels).into_window(720, 480);
+        let bounds = window.get_min_bounds();
+        assert!(window.render(bounds.width, bounds.height).is_some());
+        let body = window.get_body_bounds();
+        assert!(body.width >= buttons_width + 2 * BUTTON_SPACING + 2 * ALERT_MARGIN, "{} for {}", body.width, buttons_width);
+    }
+}
@@ -0,0 +1,107 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use crate::widget::theme::Theme;
+use crate::widget::top_bar::TopBarWidget;
+use crate::widget::window::WindowWidget;
+
+///Identifies a running application, unique for the lifetime of the MainWidget.
+pub type AppId = usize;
+
+///A program running on the desktop. It owns the windows it opens through its context and the
+/// menus shown in the global top bar while it is active, i.e. while one of its windows is in
+/// front or it was chosen from the application menu.
+///
+///The hooks are called by MainWidget between events, never while a widget handles one: menu items
+/// and widget callbacks can't reach the application, so they set shared flags (or use a clone of
+/// the context) and the application acts on them in tick.
+pub trait Application{
+    ///Get the name shown in the application menu.
+    fn get_name(&self) -> &str;
+    ///Called once when the application is started. Opens its first windows and sets its menus.
+    fn launch(&mut self, context: &AppContext);
+    ///Called when the application becomes the active one, after launch too.
+    fn activate(&mut self, _context: &AppContext){}
+    ///Called when another application becomes the active one.
+    fn deactivate(&mut self, _context: &AppContext){}
+    ///Called on every tick of the main loop.
+    fn tick(&mut self, _context: &AppContext){}
+    ///Called once when the application quits, right before its windows are closed.
+    fn quit(&mut self, _context: &AppContext){}
+}
+
+///Handle an application uses to open windows, set its menus, start other applications and quit.
+/// Clones share the same requests, so they can be moved into callbacks. MainWidget performs the
+/// requests after the current event.
+#[derive(Clone)]
+pub struct AppContext{
+    opened: Rc<RefCell<Vec<WindowWidget>>>,
+    menu: Rc<RefCell<Option<TopBarWidget>>>,
+    quit_requested: Rc<Cell<bool>>,
+    ///Applications to start, shared by all contexts.
+    launched: Rc<RefCell<Vec<Box<dyn Application>>>>,
+    ///Theme of the MainWidget, shared by all contexts.
+    theme: Rc<RefCell<Rc<Theme>>>,
+    screen_width: usize,
+    screen_height: usize,
+}
+
+impl AppContext{
+    pub(crate) fn new(
+        launched: Rc<RefCell<Vec<Box<dyn Application>>>>,
+        theme: Rc<RefCell<Rc<Theme>>>,
+        screen_width: usize,
+        screen_height: usize,
+    ) -> Self{
+        AppContext{
+            opened: Rc::new(RefCell::new(vec![])),
+            menu: Rc::new(RefCell::new(None)),
+            quit_requested: Rc::new(Cell::new(false)),
+            launched,
+            theme,
+            screen_width,
+            screen_height,
+        }
+    }
+    ///Open a window belonging to the application, in front of all others. Windows it opens in
+    /// turn (e.g. dialogs) belong to the application too.
+    pub fn open_window(&self, window: WindowWidget){
+        self.opened.borrow_mut().push(window);
+    }
+    ///Set the menus shown in the global top bar while the application is active.
+    pub fn set_menu(&self, menu: TopBarWidget){
+        *self.menu.borrow_mut() = Some(menu);
+    }
+    ///Start another application, which becomes the active one.
+    pub fn launch(&self, app: Box<dyn Application>){
+        self.launched.borrow_mut().push(app);
+    }
+    ///Quit the application, closing all of its windows.
+    pub fn quit(&self){
+        self.quit_requested.set(true);
+    }
+    ///Get the theme windows are drawn in, e.g. for its font.
+    pub fn get_theme(&self) -> Rc<Theme>{
+        self.theme.borrow().clone()
+    }
+    ///Get the size of the screen, e.g. for centering dialogs.
+    pub fn get_screen_size(&self) -> (usize, usize){
+        (self.screen_width, self.screen_height)
+    }
+    pub(crate) fn take_opened_windows(&self) -> Vec<WindowWidget>{
+        self.opened.borrow_mut().drain(..).collect()
+    }
+    pub(crate) fn take_menu(&self) -> Option<TopBarWidget>{
+        self.menu.borrow_mut().take()
+    }
+    pub(crate) fn is_quit_requested(&self) -> bool{
+        self.quit_requested.get()
+    }
+}
+
+///An application started by MainWidget, with its context and current menus.
+pub(crate) struct RunningApp{
+    pub id: AppId,
+    pub app: Box<dyn Application>,
+    pub context: AppContext,
+    pub top_bar: Box<TopBarWidget>,
+}
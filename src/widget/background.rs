@@ -0,0 +1,94 @@
+use crate::image::Image;
+use crate::widget;
+
+///The classic 50% grey: alternating black and white pixels.
+pub const GREY_PATTERN: [u8; 8] = [0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55];
+
+///How a background image is put on the screen.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ImagePlacement{
+    ///Repeated from the top left corner to cover the whole screen.
+    Tiled,
+    ///Shown once at its own size in the middle of the screen, on the fill colour.
+    Centered,
+    ///Scaled (nearest neighbour) to the size of the screen.
+    Stretched,
+}
+
+///What the desktop shows behind the icons and windows.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Background{
+    Solid([u8; 4]),
+    ///A repeating 8x8 pattern, one byte per row with the leftmost pixel in the highest bit. Set
+    /// bits are drawn in the foreground colour.
+    Pattern{
+        rows: [u8; 8],
+        foreground: [u8; 4],
+        background: [u8; 4],
+    },
+    Image{
+        image: Image,
+        placement: ImagePlacement,
+        ///Colour shown where a centered image doesn't cover the screen, and behind transparent
+        /// pixels.
+        fill: [u8; 4],
+    },
+}
+
+impl Default for Background{
+    fn default() -> Self{
+        Background::Solid([128u8, 128u8, 128u8, 255u8])
+    }
+}
+
+impl Background{
+    ///The grey dither pattern of the classic desktop.
+    pub fn classic_grey() -> Self{
+        Background::Pattern{
+            rows: GREY_PATTERN,
+            foreground: [0u8, 0u8, 0u8, 255u8],
+            background: [255u8; 4],
+        }
+    }
+    ///Render the background for a screen of the given size.
+    pub fn render(&self, width: usize, height: usize) -> Vec<[u8; 4]>{
+        match self{
+            Background::Solid(color) => vec![*color; width * height],
+            Background::Pattern{rows, foreground, background} => {
+                (0..width * height).map(|i| {
+                    let (x, y) = (i % width, i / width);
+                    if rows[y % 8] & (0x80 >> (x % 8)) != 0 {*foreground} else {*background}
+                }).collect()
+            },
+            Background::Image{image, placement, fill} => {
+                let buf = vec![*fill; width * height];
+                if image.pixels.is_empty(){
+                    return buf
+                }
+                match placement{
+                    ImagePlacement::Tiled => {
+                        let mut buf = buf;
+                        for y in (0..height).step_by(image.height){
+                            for x in (0..width).step_by(image.width){
+                                buf = widget::draw_masked_at(
+                                    (x as isize, y as isize),
+                                    buf, (width, height),
+                                    &image.pixels, (image.width, image.height));
+                            }
+                        }
+                        buf
+                    },
+                    ImagePlacement::Centered => widget::draw_masked_at(
+                        ((width as isize - image.width as isize) / 2, (height as isize - image.height as isize) / 2),
+                        buf, (width, height),
+                        &image.pixels, (image.width, image.height)),
+                    ImagePlacement::Stretched => widget::draw_masked_at(
+                        (0, 0),
+                        buf, (width, height),
+                        &image.scaled(width, height).pixels, (width, height)),
+                }
+            },
+        }
+    }
+}
+
@@ -0,0 +1,403 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+use crate::clipboard::Clipboard;
+use crate::widget;
+use crate::widget::desktop::{render_icon_cell, ICON_CELL_HEIGHT, ICON_CELL_WIDTH};
+use crate::widget::event::{EditAction, Key, WidgetEvent, DOUBLE_CLICK_TIME};
+use crate::widget::file_dialog::{get_directory_name, list_directory, DirectorySource, FileEntry};
+use crate::widget::list_view::{ListColumn, ListView};
+use crate::widget::menu::MenuItem;
+use crate::widget::scroll_view::{ScrollBar, SCROLL_BAR_WIDTH};
+use crate::widget::top_bar::{TopBarButton, TopBarWidget};
+use crate::widget::window::{WindowOpener, WindowWidget};
+use crate::widget::{Widget, WidgetBounds};
+
+///Size of folder windows.
+const BROWSER_WIDTH: usize = 340;
+const BROWSER_HEIGHT: usize = 240;
+///Offset of a folder window opened from another one.
+const CASCADE_OFFSET: usize = 20;
+///Width of the size column in list mode.
+const SIZE_COLUMN_WIDTH: usize = 80;
+
+///How a folder window shows its contents.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ViewMode{
+    ///Large icons in a grid.
+    Icons,
+    ///A table with small icons, names and sizes.
+    List,
+}
+
+///A scrolling grid of large icons for the entries of a folder.
+struct IconGrid{
+    width: usize,
+    height: usize,
+    entries: Rc<RefCell<Vec<FileEntry>>>,
+    selected: BTreeSet<usize>,
+    bar: Box<ScrollBar>,
+    bar_captured: bool,
+    focused: bool,
+    ///Time and index of the last click on an icon, to detect double clicks.
+    last_click: Option<(Instant, usize)>,
+    on_activate: Option<Box<dyn FnMut(usize)>>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl IconGrid{
+    fn new(entries: Rc<RefCell<Vec<FileEntry>>>, width: usize, height: usize) -> Self{
+        let mut grid = IconGrid{
+            width,
+            height,
+            entries,
+            selected: BTreeSet::new(),
+            bar: Box::new(ScrollBar::new(height, ICON_CELL_HEIGHT / 2)),
+            bar_captured: false,
+            focused: false,
+            last_click: None,
+            on_activate: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        };
+        grid.update_range();
+        grid
+    }
+    ///Get the number of icons in a row.
+    fn get_columns(&self) -> usize{
+        ((self.width - SCROLL_BAR_WIDTH) / ICON_CELL_WIDTH).max(1)
+    }
+    fn update_range(&mut self){
+        let rows = self.entries.borrow().len().div_ceil(self.get_columns());
+        self.bar.set_range(rows * ICON_CELL_HEIGHT, self.height);
+    }
+    ///Get the index of the icon at the given point of the grid, if any.
+    fn icon_at(&self, x: isize, y: isize) -> Option<usize>{
+        let y = y + self.bar.get_offset() as isize;
+        if x < 0 || y < 0 || x as usize >= self.get_columns() * ICON_CELL_WIDTH{
+            return None
+        }
+        let index = (y as usize / ICON_CELL_HEIGHT) * self.get_columns() + x as usize / ICON_CELL_WIDTH;
+        if index < self.entries.borrow().len() {Some(index)} else {None}
+    }
+    fn activate(&mut self, index: usize){
+        if let Some(callback) = &mut self.on_activate{
+            callback(index);
+        }
+    }
+    ///Forget the selection, e.g. after the entries changed.
+    fn reload(&mut self){
+        self.selected.clear();
+        self.update_range();
+        self.bar.set_offset(0);
+        self.needs_redraw = true;
+    }
+}
+
+impl Widget for IconGrid{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.update_range();
+        if !self.needs_redraw() && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let grid_width = self.width - SCROLL_BAR_WIDTH;
+        let mut grid = vec![[255u8; 4]; grid_width * self.height];
+        let (columns, offset) = (self.get_columns(), self.bar.get_offset());
+        let first_row = offset / ICON_CELL_HEIGHT;
+        let last_row = (offset + self.height) / ICON_CELL_HEIGHT + 1;
+        let entries = self.entries.borrow();
+        for index in first_row * columns..(last_row * columns).min(entries.len()){
+            let cell = render_icon_cell(&entries[index], self.selected.contains(&index), [255u8; 4]);
+            let (x, y) = ((index % columns) * ICON_CELL_WIDTH, (index / columns) * ICON_CELL_HEIGHT);
+            grid = widget::draw_on_top_at_clipped(
+                (x as isize, y as isize - offset as isize),
+                grid, (grid_width, self.height),
+                &cell, (ICON_CELL_WIDTH, ICON_CELL_HEIGHT));
+        }
+        drop(entries);
+        let mut buf = widget::draw_on_top_at_clipped(
+            (0, 0),
+            vec![[255u8; 4]; width * height], (width, height),
+            &grid, (grid_width, self.height));
+        let bar = match self.bar.render(SCROLL_BAR_WIDTH, self.height){
+            Some(v) => v,
+            None => self.bar.get_cache()
+        };
+        buf = widget::draw_on_top_at_clipped(
+            (grid_width as isize, 0),
+            buf, (width, height),
+            &bar, (SCROLL_BAR_WIDTH, self.height));
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.bar.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        let grid_width = (self.width - SCROLL_BAR_WIDTH) as isize;
+        match *event{
+            WidgetEvent::MouseDown{x, ..} if x >= grid_width => {
+                self.bar_captured = true;
+                self.bar.handle_event(&event.translated(-grid_width, 0))
+            },
+            WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} if self.bar_captured => {
+                if let WidgetEvent::MouseUp{..} = event{
+                    self.bar_captured = false;
+                }
+                self.bar.handle_event(&event.translated(-grid_width, 0))
+            },
+            WidgetEvent::MouseDown{x, y, modifiers, ..} => {
+                match self.icon_at(x, y){
+                    Some(index) => {
+                        if modifiers.shift || modifiers.is_shortcut(){
+                            if !self.selected.remove(&index){
+                                self.selected.insert(index);
+                            }
+                        } else {
+                            self.selected.clear();
+                            self.selected.insert(index);
+                        }
+                        let now = Instant::now();
+                        let double_click = matches!(self.last_click, Some((time, i)) if i == index && now.duration_since(time) < DOUBLE_CLICK_TIME);
+                        if double_click{
+                            self.last_click = None;
+                            self.activate(index);
+                        } else {
+                            self.last_click = Some((now, index));
+                        }
+                    },
+                    None => {
+                        self.selected.clear();
+                        self.last_click = None;
+                    }
+                }
+                self.needs_redraw = true;
+                true
+            },
+            WidgetEvent::Scroll{..} => {
+                self.update_range();
+                self.bar.handle_event(event)
+            },
+            WidgetEvent::KeyDown{key: Key::Return, ..} => {
+                match self.selected.iter().next().cloned(){
+                    Some(index) => {
+                        self.activate(index);
+                        true
+                    },
+                    None => false
+                }
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, _clipboard: &mut Clipboard) -> bool {
+        match action{
+            EditAction::SelectAll => {
+                self.selected = (0..self.entries.borrow().len()).collect();
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+///Body of a folder window, showing the entries of a directory as icons or as a list. Double
+/// clicking a folder opens it in a new window.
+pub struct FolderView{
+    directory: PathBuf,
+    entries: Rc<RefCell<Vec<FileEntry>>>,
+    mode: ViewMode,
+    ///Mode chosen in the View menu, switched to on the next render.
+    requested_mode: Rc<Cell<ViewMode>>,
+    grid: IconGrid,
+    list: ListView,
+    focused: bool,
+    ///Entry double clicked during the current event.
+    activated: Rc<Cell<Option<usize>>>,
+    opener: WindowOpener,
+    ///Position new folder windows are cascaded from.
+    window_x: usize,
+    window_y: usize,
+}
+
+impl FolderView{
+    ///Get the widget showing the current mode.
+    fn get_current(&mut self) -> &mut dyn Widget{
+        match self.mode{
+            ViewMode::Icons => &mut self.grid,
+            ViewMode::List => &mut self.list
+        }
+    }
+    ///Get the directory shown.
+    pub fn get_directory(&self) -> &Path{
+        &self.directory
+    }
+    ///Get the current view mode.
+    pub fn get_mode(&self) -> ViewMode{
+        self.requested_mode.get()
+    }
+    ///Show the contents as icons or as a list.
+    pub fn set_mode(&mut self, mode: ViewMode){
+        self.requested_mode.set(mode);
+    }
+    ///Read the directory again.
+    pub fn reload(&mut self) -> io::Result<()>{
+        *self.entries.borrow_mut() = list_directory(&self.directory, &[])?;
+        self.grid.reload();
+        self.list.set_data_source(Box::new(DirectorySource{
+            entries: self.entries.clone(),
+        }));
+        Ok(())
+    }
+    ///Switch to the mode chosen in the View menu, moving the keyboard focus along.
+    fn apply_requested_mode(&mut self){
+        if self.mode == self.requested_mode.get(){
+            return
+        }
+        if self.focused{
+            self.get_current().handle_event(&WidgetEvent::FocusLost);
+        }
+        self.mode = self.requested_mode.get();
+        if self.focused{
+            self.get_current().handle_event(&WidgetEvent::FocusGained);
+        }
+    }
+    ///Open the double clicked folder in a new window.
+    fn open_activated(&mut self){
+        let index = match self.activated.take(){
+            Some(i) => i,
+            None => return
+        };
+        let entry = match self.entries.borrow().get(index){
+            Some(e) if e.is_dir => e.clone(),
+            _ => return
+        };
+        if let Ok(window) = open_folder_window(&entry.path, self.window_x + CASCADE_OFFSET, self.window_y + CASCADE_OFFSET){
+            self.opener.open(window);
+        }
+    }
+}
+
+impl Widget for FolderView{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.apply_requested_mode();
+        self.get_current().render(width, height)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        self.grid.get_min_bounds()
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        self.get_current().get_cache()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.mode != self.requested_mode.get() || match self.mode{
+            ViewMode::Icons => self.grid.needs_redraw(),
+            ViewMode::List => self.list.needs_redraw()
+        }
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        self.apply_requested_mode();
+        if let WidgetEvent::FocusGained | WidgetEvent::FocusLost = event{
+            self.focused = *event == WidgetEvent::FocusGained;
+        }
+        let consumed = self.get_current().handle_event(event);
+        self.open_activated();
+        consumed
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        self.get_current().handle_edit_action(action, clipboard)
+    }
+}
+
+///Create a window at the given position showing the contents of a directory as icons, with a
+/// View menu switching to a list. Fails if the directory can't be read.
+pub fn open_folder_window(directory: &Path, x: usize, y: usize) -> io::Result<WindowWidget>{
+    let entries = Rc::new(RefCell::new(list_directory(directory, &[])?));
+    let title = format!(" {} ", get_directory_name(directory));
+    let mut window = WindowWidget::new(&title, BROWSER_WIDTH, BROWSER_HEIGHT, x, y);
+    let bounds = window.get_body_bounds();
+    let requested_mode = Rc::new(Cell::new(ViewMode::Icons));
+    let (icons_mode, list_mode) = (requested_mode.clone(), requested_mode.clone());
+    window.register_top_bar(Box::new(TopBarWidget::new(Box::new(vec![
+        Box::new(TopBarButton::edit_menu()),
+        Box::new(TopBarButton::with_menu("View", vec![
+            MenuItem::new("by Icon", Box::new(move || icons_mode.set(ViewMode::Icons))),
+            MenuItem::new("by Name", Box::new(move || list_mode.set(ViewMode::List))),
+        ])),
+    ]))));
+
+    let activated = Rc::new(Cell::new(None));
+    let mut grid = IconGrid::new(entries.clone(), bounds.width, bounds.height);
+    let reported = activated.clone();
+    grid.on_activate = Some(Box::new(move |index| reported.set(Some(index))));
+    let name_width = bounds.width - SCROLL_BAR_WIDTH - SIZE_COLUMN_WIDTH;
+    let mut list = ListView::with_columns(
+        Box::new(DirectorySource{entries: entries.clone()}),
+        vec![ListColumn::new("Name", name_width), ListColumn::new("Size", SIZE_COLUMN_WIDTH)],
+        bounds.width, bounds.height);
+    let reported = activated.clone();
+    list.set_on_activate(Box::new(move |index| reported.set(Some(index))));
+    let opener = window.get_opener();
+    window.set_body(Box::new(FolderView{
+        directory: directory.to_path_buf(),
+        entries,
+        mode: ViewMode::Icons,
+        requested_mode,
+        grid,
+        list,
+        focused: false,
+        activated,
+        opener,
+        window_x: x,
+        window_y: y,
+    }));
+    Ok(window)
+}
@@ -0,0 +1,580 @@
+use crate::pixel_font::PixelFont;
+use crate::widget;
+use crate::widget::event::{Key, WidgetEvent};
+use crate::widget::text_widget::TextWidget;
+use crate::widget::{draw_focus_rect, draw_rounded_rect, Color, Widget, WidgetBounds};
+
+const BLACK: [u8; 4] = [0u8, 0u8, 0u8, 255u8];
+const WHITE: [u8; 4] = [255u8; 4];
+const GREY: [u8; 4] = [136u8, 136u8, 136u8, 255u8];
+const HOVER: [u8; 4] = [221u8, 221u8, 221u8, 255u8];
+///Height of a push button, without the default button ring.
+const BUTTON_HEIGHT: usize = 20;
+///Space around a push button for the thick default button ring.
+const DEFAULT_MARGIN: usize = 4;
+///Size of the box of a checkbox or the circle of a radio button.
+const MARK_SIZE: usize = 12;
+///Height of a checkbox or of a single radio button.
+const CHECK_HEIGHT: usize = 18;
+
+///Mouse tracking shared by all clickable controls: the control is pressed while the mouse button
+/// went down on it and is still over it, and clicked when the button is released over it.
+#[derive(Default)]
+struct ClickTracker{
+    hovered: bool,
+    pressed: bool,
+    tracking: bool,
+}
+
+impl ClickTracker{
+    ///Update the state with a mouse event for a control of the given size. Returns true if the
+    /// event completed a click, and sets changed if the look of the control changed.
+    fn handle_mouse(&mut self, event: &WidgetEvent, width: usize, height: usize, changed: &mut bool) -> bool{
+        let (x, y) = match event.position(){
+            Some(p) => p,
+            None => return false
+        };
+        let inside = x >= 0 && y >= 0 && x < width as isize && y < height as isize;
+        let (old_hovered, old_pressed) = (self.hovered, self.pressed);
+        let mut clicked = false;
+        match event{
+            WidgetEvent::MouseDown{..} => {
+                self.tracking = inside;
+                self.pressed = inside;
+            },
+            WidgetEvent::MouseMove{..} => {
+                self.pressed = self.tracking && inside;
+            },
+            WidgetEvent::MouseUp{..} => {
+                clicked = self.tracking && inside;
+                self.tracking = false;
+                self.pressed = false;
+            },
+            _ => {}
+        }
+        self.hovered = inside;
+        *changed |= old_hovered != self.hovered || old_pressed != self.pressed;
+        clicked
+    }
+}
+
+///Create the label of a control, greyed out if the control is disabled.
+fn make_label(text: &str, enabled: bool, background: Color) -> TextWidget{
+    let foreground = if enabled {Color::black()} else {Color::rgb(GREY[0], GREY[1], GREY[2])};
+    TextWidget::new(Box::new(PixelFont::default()), false, text, foreground, background)
+}
+
+///Render a label and return it with its bounds.
+fn render_label(label: &mut TextWidget) -> (Vec<[u8; 4]>, WidgetBounds){
+    let bounds = label.get_min_bounds();
+    let buf = match label.render(bounds.width, bounds.height){
+        Some(v) => v,
+        None => label.get_cache()
+    };
+    (buf, bounds)
+}
+
+///A classic push button: a rounded rect with a centered label. The default button of a container
+/// has a thick ring around it and is clicked when Return is pressed, the cancel button is clicked
+/// when Escape is pressed.
+pub struct Button{
+    label: Box<TextWidget>,
+    width: usize,
+    enabled: bool,
+    is_default: bool,
+    is_cancel: bool,
+    focused: bool,
+    tracker: ClickTracker,
+    on_click: Option<Box<dyn FnMut()>>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl Button{
+    ///Create a new button with the given label and width (the button is made wider if the label
+    /// does not fit).
+    pub fn new(label: &str, width: usize) -> Self{
+        let text = make_label(label, true, Color::white());
+        let width = width.max(text.get_min_bounds().width + 16 + DEFAULT_MARGIN * 2);
+        Button{
+            label: Box::new(text),
+            width,
+            enabled: true,
+            is_default: false,
+            is_cancel: false,
+            focused: false,
+            tracker: ClickTracker::default(),
+            on_click: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the callback run when the button is clicked.
+    pub fn set_on_click(&mut self, callback: Box<dyn FnMut()>){
+        self.on_click = Some(callback);
+    }
+    ///Make the button the default button (activated with Return) or a normal one.
+    pub fn set_default(&mut self, is_default: bool){
+        self.is_default = is_default;
+        self.needs_redraw = true;
+    }
+    ///Make the button the cancel button (activated with Escape) or a normal one.
+    pub fn set_cancel(&mut self, is_cancel: bool){
+        self.is_cancel = is_cancel;
+    }
+    ///Enable or disable the button. Disabled buttons are greyed out and ignore input.
+    pub fn set_enabled(&mut self, enabled: bool){
+        if enabled != self.enabled{
+            self.enabled = enabled;
+            let text = String::from(self.label.get_text());
+            self.label = Box::new(make_label(&text, enabled, Color::white()));
+            self.tracker = ClickTracker::default();
+            self.needs_redraw = true;
+        }
+    }
+    ///Change the label of the button.
+    pub fn set_label(&mut self, label: &str){
+        self.label.set_text(label);
+    }
+    ///Run the click callback, as if the button was clicked.
+    pub fn click(&mut self){
+        if !self.enabled{
+            return
+        }
+        if let Some(callback) = &mut self.on_click{
+            callback();
+        }
+    }
+}
+
+impl Widget for Button{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw() && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf = vec![WHITE; width * height];
+        let outline = if self.enabled {BLACK} else {GREY};
+        let rect = (DEFAULT_MARGIN, DEFAULT_MARGIN, width - DEFAULT_MARGIN * 2, BUTTON_HEIGHT);
+        let fill = if self.tracker.pressed {BLACK} else if self.tracker.hovered && self.enabled {HOVER} else {WHITE};
+        draw_rounded_rect(&mut buf, width, rect, 5, 1, outline, Some(fill));
+        if self.is_default{
+            draw_rounded_rect(&mut buf, width, (0, 0, width, BUTTON_HEIGHT + DEFAULT_MARGIN * 2), 8, 3, outline, None);
+        }
+        let (mut label, bounds) = render_label(&mut self.label);
+        if fill != WHITE{
+            //the label is rendered on white, swap the background for the fill (inverting when pressed)
+            for p in label.iter_mut(){
+                *p = if *p == WHITE {fill} else if self.tracker.pressed {WHITE} else {*p};
+            }
+        }
+        let label_x = (width / 2).saturating_sub(bounds.width / 2);
+        let label_y = (DEFAULT_MARGIN + BUTTON_HEIGHT / 2).saturating_sub(bounds.height / 2);
+        buf = widget::draw_on_top_at_clipped(
+            (label_x as isize, label_y as isize),
+            buf, (width, height),
+            &label, (bounds.width, bounds.height));
+        if self.focused && !self.tracker.pressed{
+            draw_focus_rect(&mut buf, width, (DEFAULT_MARGIN + 3, DEFAULT_MARGIN + 2, width - DEFAULT_MARGIN * 2 - 6, BUTTON_HEIGHT - 4));
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: BUTTON_HEIGHT + DEFAULT_MARGIN * 2,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.label.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        if !self.enabled{
+            return false
+        }
+        match *event{
+            WidgetEvent::MouseDown{..} | WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} => {
+                //only the rounded rect is clickable, not the default ring around it
+                let inner = event.translated(-(DEFAULT_MARGIN as isize), -(DEFAULT_MARGIN as isize));
+                let mut changed = false;
+                let clicked = self.tracker.handle_mouse(&inner, self.width - DEFAULT_MARGIN * 2, BUTTON_HEIGHT, &mut changed);
+                self.needs_redraw |= changed;
+                if clicked{
+                    self.click();
+                }
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Space, ..} => {
+                self.click();
+                true
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.enabled
+    }
+
+    fn handle_default_action(&mut self) -> bool {
+        if self.is_default && self.enabled{
+            self.click();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn handle_cancel_action(&mut self) -> bool {
+        if self.is_cancel && self.enabled{
+            self.click();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+///A checkbox with a label to its right; clicking it toggles the check mark.
+pub struct Checkbox{
+    label: Box<TextWidget>,
+    checked: bool,
+    enabled: bool,
+    focused: bool,
+    tracker: ClickTracker,
+    on_change: Option<Box<dyn FnMut(bool)>>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl Checkbox{
+    ///Create a new checkbox with the given label and state.
+    pub fn new(label: &str, checked: bool) -> Self{
+        Checkbox{
+            label: Box::new(make_label(label, true, Color::white())),
+            checked,
+            enabled: true,
+            focused: false,
+            tracker: ClickTracker::default(),
+            on_change: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the callback run with the new state when the user toggles the checkbox.
+    pub fn set_on_change(&mut self, callback: Box<dyn FnMut(bool)>){
+        self.on_change = Some(callback);
+    }
+    ///Returns true if the checkbox is checked.
+    pub fn is_checked(&self) -> bool{
+        self.checked
+    }
+    ///Check or uncheck the checkbox. Does not run the change callback.
+    pub fn set_checked(&mut self, checked: bool){
+        self.checked = checked;
+        self.needs_redraw = true;
+    }
+    ///Enable or disable the checkbox. Disabled checkboxes are greyed out and ignore input.
+    pub fn set_enabled(&mut self, enabled: bool){
+        if enabled != self.enabled{
+            self.enabled = enabled;
+            let text = String::from(self.label.get_text());
+            self.label = Box::new(make_label(&text, enabled, Color::white()));
+            self.tracker = ClickTracker::default();
+            self.needs_redraw = true;
+        }
+    }
+    ///Toggle the checkbox and run the change callback.
+    fn toggle(&mut self){
+        self.checked = !self.checked;
+        self.needs_redraw = true;
+        if let Some(callback) = &mut self.on_change{
+            callback(self.checked);
+        }
+    }
+}
+
+impl Widget for Checkbox{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw() && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf = vec![WHITE; width * height];
+        let color = if self.enabled {BLACK} else {GREY};
+        let box_y = (CHECK_HEIGHT - MARK_SIZE) / 2;
+        //the box gets a thicker outline while pressed
+        let thickness = if self.tracker.pressed {2} else {1};
+        let fill = if self.tracker.hovered && self.enabled {HOVER} else {WHITE};
+        draw_rounded_rect(&mut buf, width, (0, box_y, MARK_SIZE, MARK_SIZE), 0, thickness, color, Some(fill));
+        if self.checked{
+            for i in 0..MARK_SIZE{
+                buf[(box_y + i) * width + i] = color;
+                buf[(box_y + i) * width + MARK_SIZE - 1 - i] = color;
+            }
+        }
+        let (label, bounds) = render_label(&mut self.label);
+        buf = widget::draw_on_top_at_clipped(
+            ((MARK_SIZE + 5) as isize, ((CHECK_HEIGHT as isize) - bounds.height as isize) / 2),
+            buf, (width, height),
+            &label, (bounds.width, bounds.height));
+        if self.focused{
+            draw_focus_rect(&mut buf, width, (MARK_SIZE + 3, 0, width - MARK_SIZE - 3, height));
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: MARK_SIZE + 5 + self.label.get_min_bounds().width + 2,
+            height: CHECK_HEIGHT,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.label.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        if !self.enabled{
+            return false
+        }
+        match *event{
+            WidgetEvent::MouseDown{..} | WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} => {
+                let bounds = self.get_min_bounds();
+                let mut changed = false;
+                let clicked = self.tracker.handle_mouse(event, bounds.width, bounds.height, &mut changed);
+                self.needs_redraw |= changed;
+                if clicked{
+                    self.toggle();
+                }
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Space, ..} => {
+                self.toggle();
+                true
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.enabled
+    }
+}
+
+///A vertical group of radio buttons of which exactly one is selected.
+pub struct RadioGroup{
+    labels: Vec<TextWidget>,
+    selected: usize,
+    enabled: bool,
+    focused: bool,
+    ///Tracks clicks on the option the mouse went down on.
+    tracker: ClickTracker,
+    tracked_option: Option<usize>,
+    on_change: Option<Box<dyn FnMut(usize)>>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl RadioGroup{
+    ///Create a new group with one radio button per label, the given one being selected.
+    pub fn new(labels: &[&str], selected: usize) -> Self{
+        RadioGroup{
+            labels: labels.iter().map(|l| make_label(l, true, Color::white())).collect(),
+            selected: selected.min(labels.len().saturating_sub(1)),
+            enabled: true,
+            focused: false,
+            tracker: ClickTracker::default(),
+            tracked_option: None,
+            on_change: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the callback run with the index of the newly selected option when the user changes it.
+    pub fn set_on_change(&mut self, callback: Box<dyn FnMut(usize)>){
+        self.on_change = Some(callback);
+    }
+    ///Get the index of the selected option.
+    pub fn get_selected(&self) -> usize{
+        self.selected
+    }
+    ///Select the given option. Does not run the change callback.
+    pub fn set_selected(&mut self, selected: usize){
+        if selected < self.labels.len(){
+            self.selected = selected;
+            self.needs_redraw = true;
+        }
+    }
+    ///Enable or disable the group. Disabled groups are greyed out and ignore input.
+    pub fn set_enabled(&mut self, enabled: bool){
+        if enabled != self.enabled{
+            self.enabled = enabled;
+            self.labels = self.labels.iter()
+                .map(|l| make_label(l.get_text(), enabled, Color::white()))
+                .collect();
+            self.tracker = ClickTracker::default();
+            self.needs_redraw = true;
+        }
+    }
+    ///Select an option and run the change callback if the selection changed.
+    fn select(&mut self, index: usize){
+        if index != self.selected && index < self.labels.len(){
+            self.selected = index;
+            self.needs_redraw = true;
+            if let Some(callback) = &mut self.on_change{
+                callback(index);
+            }
+        }
+    }
+}
+
+impl Widget for RadioGroup{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf = vec![WHITE; width * height];
+        let color = if self.enabled {BLACK} else {GREY};
+        for idx in 0..self.labels.len(){
+            let y = idx * CHECK_HEIGHT;
+            let circle_y = y + (CHECK_HEIGHT - MARK_SIZE) / 2;
+            let tracked = self.tracked_option == Some(idx);
+            let thickness = if tracked && self.tracker.pressed {2} else {1};
+            let fill = if tracked && self.tracker.hovered && self.enabled {HOVER} else {WHITE};
+            draw_rounded_rect(&mut buf, width, (0, circle_y, MARK_SIZE, MARK_SIZE), MARK_SIZE / 2, thickness, color, Some(fill));
+            if idx == self.selected{
+                draw_rounded_rect(&mut buf, width, (3, circle_y + 3, MARK_SIZE - 6, MARK_SIZE - 6), (MARK_SIZE - 6) / 2, MARK_SIZE, color, None);
+            }
+            let (label, bounds) = render_label(&mut self.labels[idx]);
+            buf = widget::draw_on_top_at_clipped(
+                ((MARK_SIZE + 5) as isize, y as isize + ((CHECK_HEIGHT as isize) - bounds.height as isize) / 2),
+                buf, (width, height),
+                &label, (bounds.width, bounds.height));
+            if self.focused && idx == self.selected{
+                draw_focus_rect(&mut buf, width, (MARK_SIZE + 3, y, width - MARK_SIZE - 3, CHECK_HEIGHT));
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: MARK_SIZE + 5 + self.labels.iter().map(|l| l.get_min_bounds().width).max().unwrap_or(0) + 2,
+            height: CHECK_HEIGHT * self.labels.len(),
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        if !self.enabled || self.labels.is_empty(){
+            return false
+        }
+        match *event{
+            WidgetEvent::MouseDown{..} | WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} => {
+                let (_, y) = event.position().unwrap();
+                if let WidgetEvent::MouseDown{..} = event{
+                    self.tracked_option = Some((y.max(0) as usize / CHECK_HEIGHT).min(self.labels.len() - 1));
+                } else if !self.tracker.tracking{
+                    //hovering: track whichever option is under the mouse
+                    self.tracked_option = if y >= 0 {Some(y as usize / CHECK_HEIGHT)} else {None};
+                }
+                let option = match self.tracked_option{
+                    Some(o) if o < self.labels.len() => o,
+                    _ => {
+                        self.needs_redraw |= self.tracker.hovered;
+                        self.tracker.hovered = false;
+                        return true
+                    }
+                };
+                let width = self.get_min_bounds().width;
+                let mut changed = false;
+                let clicked = self.tracker.handle_mouse(
+                    &event.translated(0, -((option * CHECK_HEIGHT) as isize)), width, CHECK_HEIGHT, &mut changed);
+                self.needs_redraw |= changed;
+                if clicked{
+                    self.select(option);
+                }
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Up, ..} => {
+                self.select(self.selected.saturating_sub(1));
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Down, ..} => {
+                self.select(self.selected + 1);
+                true
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.enabled
+    }
+}
@@ -0,0 +1,363 @@
+use std::str::FromStr;
+use crate::image::CLASSIC_PALETTE;
+
+///A colour, in RGBA. 8-bit depth per component, alpha 255 is opaque.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Color{
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+///Colours that can be referred to by name, as in HTML. Names are matched case-insensitively.
+const NAMED_COLORS: [(&str, Color); 19] = [
+    ("black", Color::rgb(0, 0, 0)),
+    ("silver", Color::rgb(192, 192, 192)),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("grey", Color::rgb(128, 128, 128)),
+    ("white", Color::rgb(255, 255, 255)),
+    ("maroon", Color::rgb(128, 0, 0)),
+    ("red", Color::rgb(255, 0, 0)),
+    ("purple", Color::rgb(128, 0, 128)),
+    ("fuchsia", Color::rgb(255, 0, 255)),
+    ("green", Color::rgb(0, 128, 0)),
+    ("lime", Color::rgb(0, 255, 0)),
+    ("olive", Color::rgb(128, 128, 0)),
+    ("yellow", Color::rgb(255, 255, 0)),
+    ("navy", Color::rgb(0, 0, 128)),
+    ("blue", Color::rgb(0, 0, 255)),
+    ("teal", Color::rgb(0, 128, 128)),
+    ("aqua", Color::rgb(0, 255, 255)),
+    ("orange", Color::rgb(255, 165, 0)),
+    ("transparent", Color::rgba(0, 0, 0, 0)),
+];
+
+impl Color{
+    ///Create an opaque colour from its components.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color{
+        Color{r, g, b, a: 255u8}
+    }
+    ///Create a colour from its components, including alpha.
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color{
+        Color{r, g, b, a}
+    }
+    ///Returns a black Color struct
+    pub fn black()->Color{
+        Color::rgb(0u8, 0u8, 0u8)
+    }
+    ///Returns a white Color struct
+    pub fn white()-> Color{
+        Color::rgb(255u8, 255u8, 255u8)
+    }
+    ///Get the same colour with the given alpha.
+    pub fn with_alpha(self, a: u8) -> Color{
+        Color{a, ..self}
+    }
+    ///Look up a colour by its HTML name, e.g. "navy" or "transparent".
+    pub fn from_name(name: &str) -> Option<Color>{
+        NAMED_COLORS.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, c)| *c)
+    }
+    ///Parse a colour written in hex as `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`. The `#` may be
+    /// left out.
+    pub fn from_hex(hex: &str) -> Option<Color>{
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()){
+            return None
+        }
+        let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+        let byte = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        match hex.len(){
+            3 => Some(Color::rgb(digit(0) * 17, digit(1) * 17, digit(2) * 17)),
+            4 => Some(Color::rgba(digit(0) * 17, digit(1) * 17, digit(2) * 17, digit(3) * 17)),
+            6 => Some(Color::rgb(byte(0), byte(1), byte(2))),
+            8 => Some(Color::rgba(byte(0), byte(1), byte(2), byte(3))),
+            _ => None
+        }
+    }
+    ///Write the colour as `#rrggbb`, or `#rrggbbaa` if it isn't opaque.
+    pub fn to_hex(&self) -> String{
+        if self.a == 255{
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+    ///Create an opaque colour from hue (in degrees), saturation and value (0 to 1).
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color{
+        let (s, v) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+        let chroma = v * s;
+        Color::from_hue_chroma(hue, chroma, v - chroma)
+    }
+    ///Get the hue (in degrees), saturation and value (0 to 1) of the colour.
+    pub fn to_hsv(&self) -> (f32, f32, f32){
+        let (hue, max, min) = self.get_hue_max_min();
+        let saturation = if max == 0.0 {0.0} else {(max - min) / max};
+        (hue, saturation, max)
+    }
+    ///Create an opaque colour from hue (in degrees), saturation and lightness (0 to 1).
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color{
+        let (s, l) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Color::from_hue_chroma(hue, chroma, l - chroma / 2.0)
+    }
+    ///Get the hue (in degrees), saturation and lightness (0 to 1) of the colour.
+    pub fn to_hsl(&self) -> (f32, f32, f32){
+        let (hue, max, min) = self.get_hue_max_min();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {0.0} else {(max - min) / (1.0 - (2.0 * lightness - 1.0).abs())};
+        (hue, saturation, lightness)
+    }
+    ///Create a colour from a hue, the chroma and the amount added to every component, the common
+    /// part of HSV and HSL.
+    fn from_hue_chroma(hue: f32, chroma: f32, m: f32) -> Color{
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32{
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let component = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color::rgb(component(r), component(g), component(b))
+    }
+    ///Get the hue (in degrees) and the largest and smallest component (0 to 1) of the colour.
+    fn get_hue_max_min(&self) -> (f32, f32, f32){
+        let (r, g, b) = (self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let hue = if delta == 0.0{
+            0.0
+        } else if max == r{
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g{
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (hue, max, min)
+    }
+    ///Interpolate between this colour (t = 0) and the other one (t = 1), alpha included.
+    pub fn lerp(self, other: Color, t: f32) -> Color{
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::rgba(mix(self.r, other.r), mix(self.g, other.g), mix(self.b, other.b), mix(self.a, other.a))
+    }
+    ///Pack the colour into a u32 (order: ARGB), as used by the minifb frame buffer.
+    pub fn to_argb_u32(&self) -> u32{
+        ((self.a as u32) << 24) | ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
+    }
+    ///Unpack a colour from a u32 (order: ARGB).
+    pub fn from_argb_u32(argb: u32) -> Color{
+        Color::rgba((argb >> 16) as u8, (argb >> 8) as u8, argb as u8, (argb >> 24) as u8)
+    }
+}
+
+///Parses hex colours and names, see Color::from_hex and Color::from_name.
+impl FromStr for Color{
+    type Err = String;
+    fn from_str(s: &str) -> Result<Color, String>{
+        let s = s.trim();
+        let parsed = if s.starts_with('#') {Color::from_hex(s)} else {Color::from_name(s)};
+        parsed.ok_or_else(|| format!("expected a colour like #rrggbb or a colour name, got '{}'", s))
+    }
+}
+
+///Converts RGBA pixels as used in buffers.
+impl From<[u8; 4]> for Color{
+    fn from(p: [u8; 4]) -> Color{
+        Color::rgba(p[0], p[1], p[2], p[3])
+    }
+}
+
+///Converts to RGBA pixels as used in buffers.
+impl From<Color> for [u8; 4]{
+    fn from(c: Color) -> [u8; 4]{
+        [c.r, c.g, c.b, c.a]
+    }
+}
+
+///An indexed set of colours, e.g. for drawing on screens with few colours.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Palette{
+    colors: Vec<Color>,
+}
+
+impl Palette{
+    ///Create a palette from its colours, in index order.
+    pub fn new(colors: Vec<Color>) -> Self{
+        Palette{colors}
+    }
+    ///The 16 colours of the classic Macintosh system palette.
+    pub fn classic_16() -> Self{
+        Palette::new(CLASSIC_PALETTE.iter().map(|p| Color::from(*p)).collect())
+    }
+    ///The 256 colours of the classic Macintosh system palette: the 6x6x6 colour cube from white
+    /// down (without black), ramps of 10 reds, greens, blues and greys, then black.
+    pub fn classic_256() -> Self{
+        let levels = [255u8, 204u8, 153u8, 102u8, 51u8, 0u8];
+        let mut colors = Vec::with_capacity(256);
+        for r in levels{
+            for g in levels{
+                for b in levels{
+                    colors.push(Color::rgb(r, g, b));
+                }
+            }
+        }
+        colors.pop();
+        let ramp = [238u8, 221u8, 187u8, 170u8, 136u8, 119u8, 85u8, 68u8, 34u8, 17u8];
+        colors.extend(ramp.iter().map(|v| Color::rgb(*v, 0, 0)));
+        colors.extend(ramp.iter().map(|v| Color::rgb(0, *v, 0)));
+        colors.extend(ramp.iter().map(|v| Color::rgb(0, 0, *v)));
+        colors.extend(ramp.iter().map(|v| Color::rgb(*v, *v, *v)));
+        colors.push(Color::black());
+        Palette::new(colors)
+    }
+    ///Get the colours of the palette, in index order.
+    pub fn get_colors(&self) -> &[Color]{
+        &self.colors
+    }
+    ///Get the colour at the given index, if it exists.
+    pub fn get(&self, index: usize) -> Option<Color>{
+        self.colors.get(index).copied()
+    }
+    pub fn len(&self) -> usize{
+        self.colors.len()
+    }
+    pub fn is_empty(&self) -> bool{
+        self.colors.is_empty()
+    }
+    ///Get the index of the colour closest to the given one (by distance in RGB, alpha ignored),
+    /// None if the palette is empty.
+    pub fn nearest(&self, color: Color) -> Option<usize>{
+        let distance = |c: &Color| (c.r as i32 - color.r as i32).pow(2)
+            + (c.g as i32 - color.g as i32).pow(2)
+            + (c.b as i32 - color.b as i32).pow(2);
+        (0..self.colors.len()).min_by_key(|i| distance(&self.colors[*i]))
+    }
+    ///Get the colour closest to the given one, keeping its alpha. Returns the colour unchanged if
+    /// the palette is empty.
+    pub fn nearest_color(&self, color: Color) -> Color{
+        match self.nearest(color){
+            Some(i) => self.colors[i].with_alpha(color.a),
+            None => color
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///A spread of colours through the RGB cube, including greys and the corners.
+    fn sample_colors() -> Vec<Color>{
+        let levels = [0u8, 1u8, 17u8, 100u8, 128u8, 200u8, 254u8, 255u8];
+        let mut colors = Vec::new();
+        for r in levels{
+            for g in levels{
+                for b in levels{
+                    colors.push(Color::rgb(r, g, b));
+                }
+            }
+        }
+        colors
+    }
+
+    #[test]
+    fn hex_colours_are_parsed(){
+        assert_eq!(Color::from_hex("#f80"), Some(Color::rgb(255, 136, 0)));
+        assert_eq!(Color::from_hex("f80"), Some(Color::rgb(255, 136, 0)));
+        assert_eq!(Color::from_hex("#f808"), Some(Color::rgba(255, 136, 0, 136)));
+        assert_eq!(Color::from_hex("#1A2b3C"), Some(Color::rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!(Color::from_hex("#1a2b3c4d"), Some(Color::rgba(0x1a, 0x2b, 0x3c, 0x4d)));
+        for bad in ["", "#", "#12", "#12345", "#1234567", "#123456789", "#ggg", "#12 456", "#+12", "#éé"]{
+            assert_eq!(Color::from_hex(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn hex_colours_are_written_back(){
+        assert_eq!(Color::rgb(0x1a, 0x2b, 0x3c).to_hex(), "#1a2b3c");
+        assert_eq!(Color::rgba(0x1a, 0x2b, 0x3c, 0x4d).to_hex(), "#1a2b3c4d");
+        for color in sample_colors(){
+            assert_eq!(Color::from_hex(&color.to_hex()), Some(color));
+        }
+    }
+
+    #[test]
+    fn strings_are_hex_or_names(){
+        assert_eq!(" #00f ".parse::<Color>(), Ok(Color::rgb(0, 0, 255)));
+        assert_eq!("Navy".parse::<Color>(), Ok(Color::rgb(0, 0, 128)));
+        assert_eq!("transparent".parse::<Color>(), Ok(Color::rgba(0, 0, 0, 0)));
+        //hex without a '#' would be ambiguous with names, so it must be written with one
+        assert!("00f".parse::<Color>().is_err());
+        assert!("#blue".parse::<Color>().is_err());
+        assert!("bleu".parse::<Color>().unwrap_err().contains("'bleu'"));
+    }
+
+    #[test]
+    fn hsv_and_hsl_round_trip(){
+        for color in sample_colors(){
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(Color::from_hsv(h, s, v), color, "{:?}", (h, s, v));
+            let (h, s, l) = color.to_hsl();
+            assert_eq!(Color::from_hsl(h, s, l), color, "{:?}", (h, s, l));
+        }
+        assert_eq!(Color::from_hsv(120.0, 1.0, 1.0), Color::rgb(0, 255, 0));
+        assert_eq!(Color::from_hsv(-120.0, 1.0, 1.0), Color::rgb(0, 0, 255));
+        assert_eq!(Color::from_hsl(0.0, 1.0, 0.5), Color::rgb(255, 0, 0));
+        assert_eq!(Color::rgb(0, 0, 255).to_hsl(), (240.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn lerp_mixes_every_component(){
+        let from = Color::rgba(0, 100, 255, 0);
+        let to = Color::rgba(255, 100, 0, 255);
+        assert_eq!(from.lerp(to, 0.0), from);
+        assert_eq!(from.lerp(to, 1.0), to);
+        assert_eq!(from.lerp(to, 0.5), Color::rgba(128, 100, 128, 128));
+        assert_eq!(from.lerp(to, -1.0), from);
+        assert_eq!(from.lerp(to, 2.0), to);
+    }
+
+    #[test]
+    fn argb_packing(){
+        let color = Color::rgba(0x12, 0x34, 0x56, 0x78);
+        assert_eq!(color.to_argb_u32(), 0x78123456);
+        assert_eq!(Color::from_argb_u32(0x78123456), color);
+        assert_eq!(<[u8; 4]>::from(color), [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(Color::from([0x12, 0x34, 0x56, 0x78]), color);
+    }
+
+    #[test]
+    fn nearest_palette_colours(){
+        let palette = Palette::new(vec![Color::black(), Color::white(), Color::rgb(255, 0, 0)]);
+        assert_eq!(palette.nearest(Color::rgb(10, 20, 30)), Some(0));
+        assert_eq!(palette.nearest(Color::rgb(200, 60, 40)), Some(2));
+        assert_eq!(palette.nearest(Color::rgba(240, 240, 240, 0)), Some(1));
+        assert_eq!(palette.nearest_color(Color::rgba(240, 240, 240, 7)), Color::rgba(255, 255, 255, 7));
+        let empty = Palette::new(Vec::new());
+        assert_eq!(empty.nearest(Color::white()), None);
+        assert_eq!(empty.nearest_color(Color::rgb(1, 2, 3)), Color::rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn classic_palettes(){
+        let palette = Palette::classic_256();
+        assert_eq!(palette.len(), 256);
+        assert_eq!(palette.get(0), Some(Color::white()));
+        assert_eq!(palette.get(255), Some(Color::black()));
+        assert_eq!(palette.get(256), None);
+        //every colour appears once
+        let colors = palette.get_colors();
+        assert!((1..colors.len()).all(|i| !colors[..i].contains(&colors[i])));
+        for (i, color) in colors.iter().enumerate(){
+            assert_eq!(palette.nearest(*color), Some(i));
+        }
+        assert_eq!(Palette::classic_16().len(), 16);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::clipboard::Clipboard;
+use crate::widget;
+use crate::widget::event::{EditAction, Key, WidgetEvent};
+use crate::widget::menu::{MenuItem, PopupRequest};
+use crate::widget::{Widget, WidgetBounds};
+
+///Widget holding child widgets at fixed positions. Mouse events go to the child under the cursor,
+/// keyboard events to the child holding keyboard focus (changed by clicking or with Tab).
+pub struct ContainerWidget{
+    width: usize,
+    height: usize,
+    background: [u8; 4],
+    children: Box<Vec<Box<dyn Widget>>>,
+    positions: Vec<(usize, usize)>,
+    ///Rects last drawn for each child, cleared before the child is redrawn.
+    drawn_bounds: Vec<(usize, usize)>,
+    focused: Option<usize>,
+    ///Child that received the last mouse down, it gets all mouse events until the button is released.
+    captured: Option<usize>,
+    ///Child the mouse was last over, told when the mouse leaves it.
+    hovered: Option<usize>,
+    has_focus: bool,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl ContainerWidget{
+    ///Create an empty container of the given size with a white background.
+    pub fn new(width: usize, height: usize) -> Self{
+        ContainerWidget{
+            width,
+            height,
+            background: [255u8; 4],
+            children: Box::new(vec![]),
+            positions: vec![],
+            drawn_bounds: vec![],
+            focused: None,
+            captured: None,
+            hovered: None,
+            has_focus: false,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the colour the container is filled with behind its children.
+    pub fn set_background(&mut self, background: [u8; 4]){
+        self.background = background;
+        self.needs_redraw = true;
+    }
+    ///Add a child at the given position (relative to the container) and return its index.
+    pub fn add_child(&mut self, child: Box<dyn Widget>, x: usize, y: usize) -> usize{
+        self.children.push(child);
+        self.positions.push((x, y));
+        self.drawn_bounds.push((0, 0));
+        self.needs_redraw = true;
+        self.children.len() - 1
+    }
+    ///Get the child at the given index.
+    pub fn get_child_mut(&mut self, index: usize) -> Option<&mut Box<dyn Widget>>{
+        self.children.get_mut(index)
+    }
+    ///Move the child at the given index.
+    pub fn set_child_position(&mut self, index: usize, x: usize, y: usize){
+        if index < self.positions.len(){
+            self.positions[index] = (x, y);
+            self.needs_redraw = true;
+        }
+    }
+    ///Give keyboard focus to the child at the given index (None removes focus from all children).
+    pub fn set_focus(&mut self, index: Option<usize>){
+        if index == self.focused{
+            return
+        }
+        if let Some(old) = self.focused{
+            self.children[old].handle_event(&WidgetEvent::FocusLost);
+        }
+        self.focused = index.filter(|i| *i < self.children.len());
+        if let Some(new) = self.focused{
+            if self.has_focus{
+                self.children[new].handle_event(&WidgetEvent::FocusGained);
+            }
+        }
+    }
+    ///Get the index of the child holding keyboard focus.
+    pub fn get_focus(&self) -> Option<usize>{
+        self.focused
+    }
+    ///Move focus to the next (or previous) child accepting focus, wrapping around.
+    fn cycle_focus(&mut self, backwards: bool) -> bool{
+        let count = self.children.len();
+        if count == 0{
+            return false
+        }
+        let start = match self.focused{
+            Some(i) => i,
+            None => if backwards {0} else {count - 1}
+        };
+        for step in 1..=count{
+            let idx = if backwards {(start + count * 2 - step) % count} else {(start + step) % count};
+            if self.children[idx].accepts_focus(){
+                self.set_focus(Some(idx));
+                return true
+            }
+        }
+        false
+    }
+    ///Returns the index of the top-most child containing the given point.
+    fn child_at(&self, x: isize, y: isize) -> Option<usize>{
+        (0..self.children.len()).rev().find(|i| {
+            let (cx, cy) = self.positions[*i];
+            let bounds = self.children[*i].get_min_bounds();
+            x >= cx as isize && y >= cy as isize &&
+                x < (cx + bounds.width) as isize && y < (cy + bounds.height) as isize
+        })
+    }
+    ///Send an event to the child at the given index, translated into its coordinates.
+    fn dispatch(&mut self, index: usize, event: &WidgetEvent) -> bool{
+        let (x, y) = self.positions[index];
+        self.children[index].handle_event(&event.translated(-(x as isize), -(y as isize)))
+    }
+    ///Draw a child onto the buffer, clearing the rect it was last drawn in first.
+    fn draw_child(&mut self, index: usize, mut buf: Vec<[u8; 4]>, clear: bool) -> Vec<[u8; 4]>{
+        let (x, y) = self.positions[index];
+        if clear{
+            let (old_w, old_h) = self.drawn_bounds[index];
+            for row in y..(y + old_h).min(self.cache_height){
+                for col in x..(x + old_w).min(self.cache_width){
+                    buf[row * self.cache_width + col] = self.background;
+                }
+            }
+        }
+        let child = &mut self.children[index];
+        let bounds = child.get_min_bounds();
+        let child_buf = match child.render(bounds.width, bounds.height){
+            Some(v) => v,
+            None => child.get_cache()
+        };
+        self.drawn_bounds[index] = (bounds.width, bounds.height);
+        widget::draw_on_top_at_clipped(
+            (x as isize, y as isize),
+            buf, (self.cache_width, self.cache_height),
+            &child_buf, (bounds.width, bounds.height))
+    }
+}
+
+impl Widget for ContainerWidget{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        let cache_valid = !self.cache.is_empty() && self.cache_width == width && self.cache_height == height;
+        if cache_valid && !self.needs_redraw(){
+            return None
+        }
+        let mut buf;
+        if cache_valid && !self.needs_redraw{
+            //only children changed, redraw those on top of the cache
+            buf = *self.cache.clone();
+            for idx in 0..self.children.len(){
+                if self.children[idx].needs_redraw(){
+                    buf = self.draw_child(idx, buf, true);
+                }
+            }
+        } else {
+            buf = vec![self.background; width * height];
+            self.cache_width = width;
+            self.cache_height = height;
+            for idx in 0..self.children.len(){
+                buf = self.draw_child(idx, buf, false);
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        Some(&self.children)
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.children.iter().any(|c| c.needs_redraw())
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{x, y, ..} => {
+                match self.child_at(x, y){
+                    Some(idx) => {
+                        self.captured = Some(idx);
+                        if self.children[idx].accepts_focus(){
+                            self.set_focus(Some(idx));
+                        }
+                        self.dispatch(idx, event)
+                    },
+                    None => false
+                }
+            },
+            WidgetEvent::MouseUp{..} => {
+                match self.captured.take(){
+                    Some(idx) => self.dispatch(idx, event),
+                    None => false
+                }
+            },
+            WidgetEvent::MouseMove{x, y, ..} => {
+                if self.captured.is_some(){
+                    return self.dispatch(self.captured.unwrap(), event)
+                }
+                let target = self.child_at(x, y);
+                if let Some(old) = self.hovered{
+                    if target != Some(old) && old < self.children.len(){
+                        //the mouse is outside of the old child now, let it clear its hover state
+                        self.dispatch(old, event);
+                    }
+                }
+                self.hovered = target;
+                match target{
+                    Some(idx) => self.dispatch(idx, event),
+                    None => false
+                }
+            },
+            WidgetEvent::Scroll{x, y, ..} => {
+                match self.child_at(x, y){
+                    Some(idx) => self.dispatch(idx, event),
+                    None => false
+                }
+            },
+            WidgetEvent::KeyDown{key, modifiers} => {
+                if let Some(idx) = self.focused{
+                    if self.children[idx].handle_event(event){
+                        return true
+                    }
+                }
+                if key == Key::Tab{
+                    return self.cycle_focus(modifiers.shift)
+                }
+                if key == Key::Return{
+                    return self.handle_default_action()
+                }
+                if key == Key::Escape{
+                    return self.handle_cancel_action()
+                }
+                false
+            },
+            WidgetEvent::TextInput(_) => {
+                match self.focused{
+                    Some(idx) => self.children[idx].handle_event(event),
+                    None => false
+                }
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.has_focus = *event == WidgetEvent::FocusGained;
+                match self.focused{
+                    Some(idx) => self.children[idx].handle_event(event),
+                    None => false
+                }
+            },
+            WidgetEvent::Tick(_) => {
+                let mut consumed = false;
+                for child in self.children.iter_mut(){
+                    consumed |= child.handle_event(event);
+                }
+                consumed
+            },
+        }
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        match self.focused{
+            Some(idx) => self.children[idx].handle_edit_action(action, clipboard),
+            None => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.children.iter().any(|c| c.accepts_focus())
+    }
+
+    fn handle_default_action(&mut self) -> bool {
+        self.children.iter_mut().any(|c| c.handle_default_action())
+    }
+
+    fn handle_cancel_action(&mut self) -> bool {
+        self.children.iter_mut().any(|c| c.handle_cancel_action())
+    }
+
+    fn get_context_menu(&mut self, x: isize, y: isize) -> Option<Vec<MenuItem>> {
+        let idx = self.child_at(x, y)?;
+        //edit actions from the menu go to the focused widget, so focus the clicked one
+        if self.children[idx].accepts_focus(){
+            self.set_focus(Some(idx));
+        }
+        let (cx, cy) = self.positions[idx];
+        self.children[idx].get_context_menu(x - cx as isize, y - cy as isize)
+    }
+
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        for idx in 0..self.children.len(){
+            if let Some(request) = self.children[idx].take_popup_request(){
+                let (x, y) = self.positions[idx];
+                return Some(request.translated(x as isize, y as isize))
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,285 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::icon::{IconState, LARGE_ICON_SIZE};
+use crate::pixel_font::PixelFont;
+use crate::widget;
+use crate::widget::event::{WidgetEvent, DOUBLE_CLICK_TIME};
+use crate::widget::file_dialog::{get_file_icon, list_directory, FileEntry};
+use crate::widget::text_widget::TextWidget;
+use crate::widget::top_bar::TOP_BAR_HEIGHT;
+use crate::widget::{Color, Widget};
+
+///Size of the cell taken by a large icon and its label.
+pub const ICON_CELL_WIDTH: usize = 80;
+pub const ICON_CELL_HEIGHT: usize = 56;
+///Space between the desktop icons and the edges of the screen.
+const DESKTOP_MARGIN: usize = 8;
+
+///Render an icon cell: the large icon of a file or folder with its name centered below, on the
+/// given background. Selected icons are drawn darkened with an inverted label.
+pub(crate) fn render_icon_cell(entry: &FileEntry, selected: bool, background: [u8; 4]) -> Vec<[u8; 4]>{
+    let buf = vec![background; ICON_CELL_WIDTH * ICON_CELL_HEIGHT];
+    let state = if selected {IconState::Selected} else {IconState::Normal};
+    let icon_x = (ICON_CELL_WIDTH - LARGE_ICON_SIZE) / 2;
+    let buf = get_file_icon(entry.is_dir).draw_onto(
+        buf, (ICON_CELL_WIDTH, ICON_CELL_HEIGHT),
+        (icon_x as isize, 2),
+        LARGE_ICON_SIZE, state);
+    let (foreground, label_background) = if selected {(Color::white(), Color::black())} else {(Color::black(), Color::white())};
+    let mut label = TextWidget::new(Box::new(PixelFont::default()), false, &entry.name, foreground, label_background);
+    let bounds = label.get_min_bounds();
+    let label_buf = match label.render(bounds.width, bounds.height){
+        Some(v) => v,
+        None => label.get_cache()
+    };
+    //long names are cut off at the cell width
+    let visible_width = bounds.width.min(ICON_CELL_WIDTH);
+    let label_height = bounds.height.min(ICON_CELL_HEIGHT - LARGE_ICON_SIZE - 4);
+    let label_buf = widget::crop_width(&label_buf, bounds.width, visible_width);
+    widget::draw_on_top_at_clipped(
+        (((ICON_CELL_WIDTH - visible_width) / 2) as isize, (LARGE_ICON_SIZE + 4) as isize),
+        buf, (ICON_CELL_WIDTH, ICON_CELL_HEIGHT),
+        &label_buf[..visible_width * label_height], (visible_width, label_height))
+}
+
+///A file or folder shown on the desktop.
+pub struct DesktopIcon{
+    pub entry: FileEntry,
+    ///Position of the top left corner of the icon cell on screen.
+    pub x: isize,
+    pub y: isize,
+    pub selected: bool,
+    ///Rendered cell and the selection state it was drawn with.
+    cache: Box<Vec<[u8; 4]>>,
+    drawn_selected: bool,
+}
+
+impl DesktopIcon{
+    fn new(entry: FileEntry) -> Self{
+        DesktopIcon{
+            entry,
+            x: 0,
+            y: 0,
+            selected: false,
+            cache: Box::new(vec![]),
+            drawn_selected: false,
+        }
+    }
+    ///Returns true if the given screen point is on the icon cell.
+    fn contains(&self, x: isize, y: isize) -> bool{
+        x >= self.x && y >= self.y && x < self.x + ICON_CELL_WIDTH as isize && y < self.y + ICON_CELL_HEIGHT as isize
+    }
+}
+
+///The files and folders of a directory shown as icons on the desktop background, behind all
+/// windows. Icons can be selected, dragged around and opened with a double click.
+pub struct Desktop{
+    directory: PathBuf,
+    width: usize,
+    height: usize,
+    icons: Vec<DesktopIcon>,
+    ///Last mouse position while selected icons are dragged.
+    drag: Option<(isize, isize)>,
+    ///Time of the last click on an icon and its path, to detect double clicks.
+    last_click: Option<(Instant, PathBuf)>,
+    ///Icon double clicked, waiting to be opened by MainWidget.
+    open_request: Option<FileEntry>,
+}
+
+impl Desktop{
+    ///Create a desktop for a screen of the given size, showing the contents of the given
+    /// directory. Fails if the directory can't be read.
+    pub fn new(directory: &Path, width: usize, height: usize) -> io::Result<Self>{
+        let mut desktop = Desktop{
+            directory: directory.to_path_buf(),
+            width,
+            height,
+            icons: vec![],
+            drag: None,
+            last_click: None,
+            open_request: None,
+        };
+        desktop.reload()?;
+        Ok(desktop)
+    }
+    ///Get the directory shown on the desktop.
+    pub fn get_directory(&self) -> &Path{
+        &self.directory
+    }
+    ///Get the icons, in the order they are drawn.
+    pub fn get_icons(&self) -> &[DesktopIcon]{
+        &self.icons
+    }
+    ///Get the entries of the selected icons.
+    pub fn get_selection(&self) -> Vec<&FileEntry>{
+        self.icons.iter().filter(|i| i.selected).map(|i| &i.entry).collect()
+    }
+    ///Read the directory again. Icons of files that are still there keep their position and
+    /// selection, new ones are put in the next free places.
+    pub fn reload(&mut self) -> io::Result<()>{
+        let entries = list_directory(&self.directory, &[])?;
+        let mut old_icons: Vec<DesktopIcon> = self.icons.drain(..).collect();
+        let mut new_icons = vec![];
+        for entry in entries{
+            match old_icons.iter().position(|i| i.entry.path == entry.path){
+                Some(index) => {
+                    let mut icon = old_icons.remove(index);
+                    icon.entry = entry;
+                    self.icons.push(icon);
+                },
+                None => new_icons.push(DesktopIcon::new(entry))
+            }
+        }
+        for mut icon in new_icons{
+            let (x, y) = self.get_free_position();
+            icon.x = x;
+            icon.y = y;
+            self.icons.push(icon);
+        }
+        Ok(())
+    }
+    ///Put all icons back in columns along the right edge of the screen, in name order.
+    pub fn arrange(&mut self){
+        self.icons.sort_by(|a, b| b.entry.is_dir.cmp(&a.entry.is_dir)
+            .then_with(|| a.entry.name.to_lowercase().cmp(&b.entry.name.to_lowercase())));
+        let positions: Vec<(isize, isize)> = (0..self.icons.len()).map(|i| self.get_grid_position(i)).collect();
+        for (icon, (x, y)) in self.icons.iter_mut().zip(positions){
+            icon.x = x;
+            icon.y = y;
+        }
+    }
+    ///Get the position of the given place of the icon grid, filling columns from the top right.
+    fn get_grid_position(&self, index: usize) -> (isize, isize){
+        let rows = (self.height.saturating_sub(TOP_BAR_HEIGHT + DESKTOP_MARGIN) / ICON_CELL_HEIGHT).max(1);
+        let (column, row) = (index / rows, index % rows);
+        let x = self.width as isize - ((column + 1) * ICON_CELL_WIDTH + DESKTOP_MARGIN) as isize;
+        let y = (TOP_BAR_HEIGHT + DESKTOP_MARGIN + row * ICON_CELL_HEIGHT) as isize;
+        (x, y)
+    }
+    ///Get the first place of the icon grid not taken by an icon.
+    fn get_free_position(&self) -> (isize, isize){
+        let mut index = 0;
+        loop{
+            let (x, y) = self.get_grid_position(index);
+            if !self.icons.iter().any(|i| i.x == x && i.y == y) || index > self.icons.len(){
+                return (x, y)
+            }
+            index += 1;
+        }
+    }
+    ///Get the index of the top-most icon at the given screen point.
+    fn icon_at(&self, x: isize, y: isize) -> Option<usize>{
+        self.icons.iter().rposition(|i| i.contains(x, y))
+    }
+    ///Take the entry of the icon double clicked since the last call.
+    pub fn take_open_request(&mut self) -> Option<FileEntry>{
+        self.open_request.take()
+    }
+    ///Handles a mouse event with screen coordinates that was not meant for any window. Returns
+    /// true if the event was consumed.
+    pub fn handle_event(&mut self, event: &WidgetEvent) -> bool{
+        match *event{
+            WidgetEvent::MouseDown{x, y, modifiers, ..} => {
+                let index = match self.icon_at(x, y){
+                    Some(i) => i,
+                    None => {
+                        for icon in self.icons.iter_mut(){
+                            icon.selected = false;
+                        }
+                        self.last_click = None;
+                        return true
+                    }
+                };
+                if modifiers.shift{
+                    self.icons[index].selected = !self.icons[index].selected;
+                } else if !self.icons[index].selected{
+                    for icon in self.icons.iter_mut(){
+                        icon.selected = false;
+                    }
+                    self.icons[index].selected = true;
+                }
+                //the clicked icon is drawn on top of the others
+                let icon = self.icons.remove(index);
+                self.icons.push(icon);
+                let index = self.icons.len() - 1;
+                let now = Instant::now();
+                let path = &self.icons[index].entry.path;
+                let double_click = matches!(&self.last_click, Some((time, p)) if p == path && now.duration_since(*time) < DOUBLE_CLICK_TIME);
+                if double_click{
+                    self.open_request = Some(self.icons[index].entry.clone());
+                    self.last_click = None;
+                } else {
+                    self.last_click = Some((now, path.clone()));
+                }
+                if self.icons[index].selected{
+                    self.drag = Some((x, y));
+                }
+                true
+            },
+            WidgetEvent::MouseMove{x, y, ..} => {
+                let (last_x, last_y) = match self.drag{
+                    Some(d) => d,
+                    None => return false
+                };
+                let (dx, dy) = (x - last_x, y - last_y);
+                //keep the icons on screen and below the top bar, or at the top left if the
+                //screen is smaller than an icon
+                let max_x = (self.width as isize - ICON_CELL_WIDTH as isize).max(0);
+                let max_y = (self.height as isize - ICON_CELL_HEIGHT as isize).max(TOP_BAR_HEIGHT as isize);
+                for icon in self.icons.iter_mut().filter(|i| i.selected){
+                    icon.x = (icon.x + dx).clamp(0, max_x);
+                    icon.y = (icon.y + dy).clamp(TOP_BAR_HEIGHT as isize, max_y);
+                }
+                self.drag = Some((x, y));
+                //a drag is not the first click of a double click
+                self.last_click = None;
+                true
+            },
+            WidgetEvent::MouseUp{..} => self.drag.take().is_some(),
+            _ => false
+        }
+    }
+    ///Draw the icons onto the screen buffer.
+    pub fn render_onto(&mut self, mut buf: Vec<[u8; 4]>, width: usize, height: usize) -> Vec<[u8; 4]>{
+        for icon in self.icons.iter_mut(){
+            if icon.cache.is_empty() || icon.drawn_selected != icon.selected{
+                //transparent around the icon and label, so the desktop background shows through
+                icon.cache = Box::new(render_icon_cell(&icon.entry, icon.selected, [0u8; 4]));
+                icon.drawn_selected = icon.selected;
+            }
+            buf = widget::draw_masked_at(
+                (icon.x, icon.y),
+                buf, (width, height),
+                &icon.cache, (ICON_CELL_WIDTH, ICON_CELL_HEIGHT));
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::fs;
+    use crate::widget::event::{Modifiers, MouseButton};
+
+    #[test]
+    fn screens_smaller_than_an_icon_keep_icons_at_the_top_left(){
+        let directory = std::env::temp_dir().join(format!("modesto_desktop_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["a.txt", "b.txt"]{
+            fs::write(directory.join(name), "").unwrap();
+        }
+        let desktop = Desktop::new(&directory, 40, 30);
+        fs::remove_dir_all(&directory).unwrap();
+        let mut desktop = desktop.unwrap();
+        desktop.arrange();
+        let (x, y) = (desktop.get_icons()[0].x, desktop.get_icons()[0].y);
+        let modifiers = Modifiers::default();
+        assert!(desktop.handle_event(&WidgetEvent::MouseDown{x: x + 1, y: y + 1, button: MouseButton::Left, modifiers}));
+        assert!(desktop.handle_event(&WidgetEvent::MouseMove{x: x + 6, y: y + 6, pressed: true}));
+        let icon = desktop.get_icons().iter().find(|i| i.selected).unwrap();
+        assert_eq!((icon.x, icon.y), (0, TOP_BAR_HEIGHT as isize));
+    }
+}
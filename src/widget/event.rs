@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+///Longest time between two clicks on the same item for them to count as a double click.
+pub const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+///Keys widgets react to. Printable text arrives separately as WidgetEvent::TextInput, Char is
+/// only meant for shortcuts (e.g. command + c).
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Key{
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Backspace,
+    Delete,
+    Return,
+    Escape,
+    Tab,
+    Space,
+    Char(char),
+}
+
+///Modifier keys held down while an event happened.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Modifiers{
+    pub shift: bool,
+    pub control: bool,
+    pub option: bool,
+    pub command: bool,
+}
+
+impl Modifiers{
+    ///Returns true if the modifiers make a key press a menu shortcut. That is the command key, or
+    /// control, since window managers tend to take the super key for themselves.
+    pub fn is_shortcut(&self) -> bool{
+        self.command || self.control
+    }
+}
+
+///Mouse buttons. Possible values: Left, Right, Middle.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MouseButton{
+    Left,
+    Right,
+    Middle,
+}
+
+///An input event delivered to a widget. Mouse coordinates are relative to the top left corner of
+/// the receiving widget and may be negative or outside its bounds while the mouse is captured.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WidgetEvent{
+    MouseDown{x: isize, y: isize, button: MouseButton, modifiers: Modifiers},
+    MouseUp{x: isize, y: isize, button: MouseButton, modifiers: Modifiers},
+    ///Mouse moved; pressed is true while a button is held (dragging).
+    MouseMove{x: isize, y: isize, pressed: bool},
+    ///Scroll wheel moved over (x, y) by the given amount of pixels. Positive deltas scroll towards
+    /// the top/left of the content.
+    Scroll{x: isize, y: isize, delta_x: isize, delta_y: isize},
+    KeyDown{key: Key, modifiers: Modifiers},
+    ///A printable character was typed.
+    TextInput(char),
+    FocusGained,
+    FocusLost,
+    ///Sent once per frame, for animations such as blinking carets.
+    Tick(Instant),
+}
+
+impl WidgetEvent{
+    ///Returns the mouse position of the event, if it is a mouse event.
+    pub fn position(&self) -> Option<(isize, isize)>{
+        match *self{
+            WidgetEvent::MouseDown{x, y, ..} |
+            WidgetEvent::MouseUp{x, y, ..} |
+            WidgetEvent::MouseMove{x, y, ..} |
+            WidgetEvent::Scroll{x, y, ..} => Some((x, y)),
+            _ => None
+        }
+    }
+    ///Returns a copy of the event with the mouse position moved by the given offsets, e.g. to
+    /// translate it into the coordinates of a child widget. Non-mouse events are returned as is.
+    pub fn translated(&self, dx: isize, dy: isize) -> WidgetEvent{
+        let mut out = *self;
+        match &mut out{
+            WidgetEvent::MouseDown{x, y, ..} |
+            WidgetEvent::MouseUp{x, y, ..} |
+            WidgetEvent::MouseMove{x, y, ..} |
+            WidgetEvent::Scroll{x, y, ..} => {
+                *x += dx;
+                *y += dy;
+            },
+            _ => {}
+        }
+        out
+    }
+}
+
+///Standard editing commands from the Edit menu, sent to the widget holding keyboard focus.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum EditAction{
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste,
+    Clear,
+    SelectAll,
+}
+
+impl EditAction{
+    ///Get the action for a shortcut key (pressed together with command), if it has one.
+    pub fn from_shortcut(c: char, shift: bool) -> Option<EditAction>{
+        match c{
+            'z' if shift => Some(EditAction::Redo),
+            'z' => Some(EditAction::Undo),
+            'x' => Some(EditAction::Cut),
+            'c' => Some(EditAction::Copy),
+            'v' => Some(EditAction::Paste),
+            'a' => Some(EditAction::SelectAll),
+            _ => None
+        }
+    }
+}
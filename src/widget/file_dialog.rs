@@ -0,0 +1,577 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::clipboard::Clipboard;
+use crate::icon::{Icon, IconState};
+use crate::widget::alert::{Alert, AlertIcon};
+use crate::widget::button::Button;
+use crate::widget::container::ContainerWidget;
+use crate::widget::event::{EditAction, WidgetEvent};
+use crate::widget::list_view::{ListColumn, ListDataSource, ListView, ICON_SIZE};
+use crate::widget::menu::{MenuItem, PopupRequest};
+use crate::widget::popup_menu::PopupMenu;
+use crate::widget::text_field::TextField;
+use crate::widget::window::{WindowCloser, WindowOpener, WindowWidget};
+use crate::widget::{Widget, WidgetBounds};
+
+///Width of file dialogs.
+const DIALOG_WIDTH: usize = 400;
+///Space between the edges of the dialog and its contents.
+const DIALOG_MARGIN: usize = 16;
+///Size of the file list and its columns.
+const LIST_WIDTH: usize = 260;
+const LIST_HEIGHT: usize = 180;
+const NAME_COLUMN_WIDTH: usize = 170;
+const SIZE_COLUMN_WIDTH: usize = 74;
+///Offset of the file list, below the directory pop-up menu.
+const LIST_Y: usize = 44;
+///Width of the buttons right of the list and space between them.
+const BUTTON_WIDTH: usize = 90;
+const BUTTON_SPACING: usize = 36;
+
+///Callback run with the chosen path, or None if the dialog was cancelled.
+pub type ResultCallback = Box<dyn FnMut(Option<PathBuf>)>;
+
+///Get the icon of a folder or document.
+pub(crate) fn get_file_icon(is_dir: bool) -> Icon{
+    if is_dir {Icon::folder()} else {Icon::document()}
+}
+
+///A file or directory shown in a file dialog.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileEntry{
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    ///Size in bytes, 0 for directories.
+    pub size: u64,
+}
+
+///Returns true if the file has one of the given extensions (without the dot, compared ignoring
+/// case). An empty filter matches all files.
+pub fn matches_filter(path: &Path, filter: &[String]) -> bool{
+    if filter.is_empty(){
+        return true
+    }
+    match path.extension().and_then(|e| e.to_str()){
+        Some(extension) => filter.iter().any(|f| f.eq_ignore_ascii_case(extension)),
+        None => false
+    }
+}
+
+///Read the entries of a directory as shown by file dialogs: directories first, then the files
+/// matching the filter, both sorted by name ignoring case. Hidden entries (starting with a dot)
+/// are left out.
+pub fn list_directory(dir: &Path, filter: &[String]) -> io::Result<Vec<FileEntry>>{
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir)?{
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.'){
+            continue
+        }
+        let path = entry.path();
+        //follow links, so links to directories can be opened
+        let metadata = fs::metadata(&path).or_else(|_| entry.metadata())?;
+        if !metadata.is_dir() && !matches_filter(&path, filter){
+            continue
+        }
+        entries.push(FileEntry{
+            name,
+            path,
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() {0} else {metadata.len()},
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+    Ok(entries)
+}
+
+///Format a file size for the size column.
+fn format_size(entry: &FileEntry) -> String{
+    if entry.is_dir{
+        String::from("--")
+    } else if entry.size < 1024{
+        format!("{} B", entry.size)
+    } else if entry.size < 1024 * 1024{
+        format!("{} K", entry.size.div_ceil(1024))
+    } else {
+        format!("{} M", entry.size.div_ceil(1024 * 1024))
+    }
+}
+
+///Get the name of a directory as shown in the directory pop-up menu.
+pub(crate) fn get_directory_name(dir: &Path) -> String{
+    match dir.file_name(){
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => dir.to_string_lossy().into_owned()
+    }
+}
+
+///Rows of a list of files, shared with its owner so it knows what a selected row stands for after
+/// the list was sorted.
+pub(crate) struct DirectorySource{
+    pub(crate) entries: Rc<RefCell<Vec<FileEntry>>>,
+}
+
+impl ListDataSource for DirectorySource{
+    fn get_row_count(&self) -> usize {
+        self.entries.borrow().len()
+    }
+    fn get_text(&self, row: usize, column: usize) -> String {
+        let entries = self.entries.borrow();
+        if column == 0 {entries[row].name.clone()} else {format_size(&entries[row])}
+    }
+    fn get_icon(&self, row: usize) -> Option<Vec<[u8; 4]>> {
+        get_file_icon(self.entries.borrow()[row].is_dir).render(ICON_SIZE, IconState::Normal)
+    }
+    fn sort(&mut self, column: usize, ascending: bool) {
+        self.entries.borrow_mut().sort_by(|a, b| {
+            let order = if column == 0{
+                a.name.to_lowercase().cmp(&b.name.to_lowercase())
+            } else {
+                a.size.cmp(&b.size)
+            };
+            if ascending {order} else {order.reverse()}
+        });
+    }
+}
+
+///Whether a file dialog picks an existing file or a place to save to.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FileDialogMode{
+    Open,
+    ///The dialog has a file name field and asks before replacing an existing file.
+    Save,
+}
+
+///Something the user did in a file dialog, handled once the widget that reported it is done.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum DialogAction{
+    ///An item of the directory pop-up menu was chosen.
+    Navigate(usize),
+    ///A row of the list was double clicked.
+    Activate(usize),
+    SelectionChanged,
+    NameChanged,
+    Confirm,
+    Cancel,
+}
+
+///Body of a file dialog window. Its controls report what the user did through a shared action,
+/// performed after the event was handled since the controls can't change each other.
+struct FileDialogBody{
+    container: ContainerWidget,
+    mode: FileDialogMode,
+    directory: PathBuf,
+    filter: Vec<String>,
+    entries: Rc<RefCell<Vec<FileEntry>>>,
+    action: Rc<Cell<Option<DialogAction>>>,
+    path_menu: Rc<RefCell<PopupMenu>>,
+    list: Rc<RefCell<ListView>>,
+    name_field: Option<Rc<RefCell<TextField>>>,
+    confirm_button: Rc<RefCell<Button>>,
+    on_result: Rc<RefCell<Option<ResultCallback>>>,
+    closer: WindowCloser,
+    opener: WindowOpener,
+    screen_width: usize,
+    screen_height: usize,
+}
+
+impl FileDialogBody{
+    ///Show the given entries of the given directory.
+    fn show_directory(&mut self, dir: &Path, entries: Vec<FileEntry>){
+        self.directory = dir.to_path_buf();
+        *self.entries.borrow_mut() = entries;
+        self.list.borrow_mut().set_data_source(Box::new(DirectorySource{
+            entries: self.entries.clone(),
+        }));
+        let names: Vec<String> = dir.ancestors().map(get_directory_name).collect();
+        let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+        self.path_menu.borrow_mut().set_items(&names, 0);
+        self.update_confirm_button();
+    }
+    ///Show the given directory, or an alert if it can't be read.
+    fn navigate(&mut self, dir: &Path){
+        match list_directory(dir, &self.filter){
+            Ok(entries) => self.show_directory(dir, entries),
+            Err(e) => {
+                let message = format!("The folder \"{}\" can't be opened: {}", get_directory_name(dir), e);
+                let alert = Alert::new(AlertIcon::Stop, &message, &["OK"]);
+                self.opener.open(alert.into_window(self.screen_width, self.screen_height));
+            }
+        }
+    }
+    ///Get the entry of the selected row, if any.
+    fn get_selected_entry(&self) -> Option<FileEntry>{
+        let row = *self.list.borrow().get_selection().first()?;
+        self.entries.borrow().get(row).cloned()
+    }
+    ///Get the file name typed into the name field of a save dialog.
+    fn get_typed_name(&self) -> String{
+        match &self.name_field{
+            Some(field) => String::from(field.borrow().get_text().trim()),
+            None => String::new()
+        }
+    }
+    ///Enable the Open/Save button only when there is something to open or save.
+    fn update_confirm_button(&mut self){
+        let enabled = match self.mode{
+            FileDialogMode::Open => self.get_selected_entry().is_some(),
+            FileDialogMode::Save => !self.get_typed_name().is_empty() || self.get_selected_entry().is_some_and(|e| e.is_dir)
+        };
+        self.confirm_button.borrow_mut().set_enabled(enabled);
+    }
+    ///Close the dialog, reporting the chosen file (None if it was cancelled).
+    fn finish(&mut self, path: Option<PathBuf>){
+        self.closer.close();
+        if let Some(callback) = self.on_result.borrow_mut().as_mut(){
+            callback(path);
+        }
+    }
+    ///Open a directory entry, or choose a file entry.
+    fn open_entry(&mut self, entry: FileEntry){
+        if entry.is_dir{
+            self.navigate(&entry.path);
+        } else if self.mode == FileDialogMode::Open{
+            self.finish(Some(entry.path));
+        } else if let Some(field) = &self.name_field{
+            field.borrow_mut().set_text(&entry.name);
+            self.confirm();
+        }
+    }
+    ///Save to the typed name, asking first if the file already exists.
+    fn save(&mut self){
+        let name = self.get_typed_name();
+        if name.is_empty(){
+            return
+        }
+        let path = self.directory.join(&name);
+        if path.is_dir(){
+            self.navigate(&path);
+            return
+        }
+        if !path.exists(){
+            self.finish(Some(path));
+            return
+        }
+        let mut alert = Alert::new(AlertIcon::Caution, &format!("Replace existing \"{}\"?", name), &["Replace", "Cancel"]);
+        let (closer, on_result) = (self.closer.clone(), self.on_result.clone());
+        alert.set_on_result(Box::new(move |button| {
+            if button == 0{
+                closer.close();
+                if let Some(callback) = on_result.borrow_mut().as_mut(){
+                    callback(Some(path.clone()));
+                }
+            }
+        }));
+        self.opener.open(alert.into_window(self.screen_width, self.screen_height));
+    }
+    ///Perform the Open/Save button.
+    fn confirm(&mut self){
+        let selected = self.get_selected_entry();
+        match (self.mode, selected){
+            (FileDialogMode::Open, Some(entry)) => self.open_entry(entry),
+            (FileDialogMode::Save, Some(entry)) if entry.is_dir => self.navigate(&entry.path),
+            (FileDialogMode::Save, _) => self.save(),
+            _ => {}
+        }
+    }
+    ///Perform what the controls reported during the last event.
+    fn perform_action(&mut self){
+        let action = match self.action.take(){
+            Some(a) => a,
+            None => return
+        };
+        match action{
+            DialogAction::Navigate(index) => {
+                if let Some(dir) = self.directory.ancestors().nth(index).map(|d| d.to_path_buf()){
+                    self.navigate(&dir);
+                }
+            },
+            DialogAction::Activate(row) => {
+                let entry = self.entries.borrow().get(row).cloned();
+                if let Some(entry) = entry{
+                    self.open_entry(entry);
+                }
+            },
+            DialogAction::SelectionChanged => {
+                //in a save dialog, picking a file suggests its name
+                if let (Some(field), Some(entry)) = (&self.name_field, self.get_selected_entry()){
+                    if !entry.is_dir{
+                        field.borrow_mut().set_text(&entry.name);
+                    }
+                }
+                self.update_confirm_button();
+            },
+            DialogAction::NameChanged => self.update_confirm_button(),
+            DialogAction::Confirm => self.confirm(),
+            DialogAction::Cancel => self.finish(None),
+        }
+    }
+}
+
+impl Widget for FileDialogBody{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.container.render(width, height)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        self.container.get_children()
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        self.container.get_min_bounds()
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        self.container.get_cache()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.container.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        //also runs on ticks, which perform the choices made in the directory pop-up menu
+        let consumed = self.container.handle_event(event);
+        self.perform_action();
+        consumed
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.container.accepts_focus()
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        self.container.handle_edit_action(action, clipboard)
+    }
+
+    fn get_caret_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        self.container.get_caret_rect()
+    }
+
+    fn handle_default_action(&mut self) -> bool {
+        let performed = self.container.handle_default_action();
+        self.perform_action();
+        performed
+    }
+
+    fn handle_cancel_action(&mut self) -> bool {
+        let performed = self.container.handle_cancel_action();
+        self.perform_action();
+        performed
+    }
+
+    fn get_context_menu(&mut self, x: isize, y: isize) -> Option<Vec<MenuItem>> {
+        self.container.get_context_menu(x, y)
+    }
+
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        self.container.take_popup_request()
+    }
+}
+
+///A standard dialog picking a file to open or a place to save to, reading the file system. Shown
+/// as a modal window with a pop-up menu of the parent directories, the list of the current
+/// directory and (when saving) a file name field.
+pub struct FileDialog{
+    mode: FileDialogMode,
+    directory: PathBuf,
+    file_name: String,
+    filter: Vec<String>,
+    on_result: Option<ResultCallback>,
+}
+
+impl FileDialog{
+    ///Create an Open dialog starting in the given directory.
+    pub fn open(directory: &Path) -> Self{
+        FileDialog{
+            mode: FileDialogMode::Open,
+            directory: directory.to_path_buf(),
+            file_name: String::new(),
+            filter: vec![],
+            on_result: None,
+        }
+    }
+    ///Create a Save dialog starting in the given directory, suggesting the given file name.
+    pub fn save(directory: &Path, file_name: &str) -> Self{
+        FileDialog{
+            mode: FileDialogMode::Save,
+            file_name: String::from(file_name),
+            ..FileDialog::open(directory)
+        }
+    }
+    ///Only show files with one of the given extensions (without the dot). Directories are always
+    /// shown.
+    pub fn set_filter(&mut self, extensions: &[&str]){
+        self.filter = extensions.iter().map(|e| String::from(e.trim_start_matches('.'))).collect();
+    }
+    ///Set the callback run with the chosen path when the dialog is closed, or None if it was
+    /// cancelled.
+    pub fn set_on_result(&mut self, callback: ResultCallback){
+        self.on_result = Some(callback);
+    }
+    ///Build the modal window showing the dialog, centered on a screen of the given size. Fails if
+    /// the starting directory can't be read.
+    pub fn into_window(self, screen_width: usize, screen_height: usize) -> io::Result<WindowWidget>{
+        let entries = list_directory(&self.directory, &self.filter)?;
+        let body_width = DIALOG_WIDTH - 2;
+        let name_y = LIST_Y + LIST_HEIGHT + DIALOG_MARGIN;
+        let name_field = match self.mode{
+            FileDialogMode::Open => None,
+            FileDialogMode::Save => Some(Rc::new(RefCell::new(TextField::new(LIST_WIDTH, &self.file_name))))
+        };
+        let body_height = match &name_field{
+            Some(field) => name_y + field.borrow().get_min_bounds().height + DIALOG_MARGIN,
+            None => name_y
+        };
+        let title = if self.mode == FileDialogMode::Open {" Open "} else {" Save "};
+        let mut window = WindowWidget::new_modal(title, body_width, body_height, screen_width, screen_height);
+
+        let action = Rc::new(Cell::new(None));
+        let path_menu = Rc::new(RefCell::new(PopupMenu::new(&[], 0, LIST_WIDTH)));
+        let reported = action.clone();
+        path_menu.borrow_mut().set_on_change(Box::new(move |index| reported.set(Some(DialogAction::Navigate(index)))));
+        let list = Rc::new(RefCell::new(ListView::with_columns(
+            Box::new(DirectorySource{entries: Rc::new(RefCell::new(vec![]))}),
+            vec![ListColumn::new("Name", NAME_COLUMN_WIDTH), ListColumn::new("Size", SIZE_COLUMN_WIDTH)],
+            LIST_WIDTH, LIST_HEIGHT)));
+        let reported = action.clone();
+        list.borrow_mut().set_on_activate(Box::new(move |row| reported.set(Some(DialogAction::Activate(row)))));
+        let reported = action.clone();
+        list.borrow_mut().set_on_selection_change(Box::new(move |_| reported.set(Some(DialogAction::SelectionChanged))));
+        let confirm_label = if self.mode == FileDialogMode::Open {"Open"} else {"Save"};
+        let confirm_button = Rc::new(RefCell::new(Button::new(confirm_label, BUTTON_WIDTH)));
+        confirm_button.borrow_mut().set_default(true);
+        let reported = action.clone();
+        confirm_button.borrow_mut().set_on_click(Box::new(move || reported.set(Some(DialogAction::Confirm))));
+        let mut cancel_button = Button::new("Cancel", BUTTON_WIDTH);
+        cancel_button.set_cancel(true);
+        let reported = action.clone();
+        cancel_button.set_on_click(Box::new(move || reported.set(Some(DialogAction::Cancel))));
+
+        let mut container = ContainerWidget::new(body_width, body_height);
+        let button_x = DIALOG_MARGIN * 2 + LIST_WIDTH;
+        container.add_child(Box::new(path_menu.clone()), DIALOG_MARGIN, 12);
+        let list_index = container.add_child(Box::new(list.clone()), DIALOG_MARGIN, LIST_Y);
+        container.add_child(Box::new(confirm_button.clone()), button_x, LIST_Y);
+        container.add_child(Box::new(cancel_button), button_x, LIST_Y + BUTTON_SPACING);
+        match &name_field{
+            Some(field) => {
+                let reported = action.clone();
+                field.borrow_mut().set_on_change(Box::new(move |_| reported.set(Some(DialogAction::NameChanged))));
+                let field_index = container.add_child(Box::new(field.clone()), DIALOG_MARGIN, name_y);
+                container.set_focus(Some(field_index));
+            },
+            None => container.set_focus(Some(list_index))
+        }
+
+        let mut body = FileDialogBody{
+            container,
+            mode: self.mode,
+            directory: self.directory.clone(),
+            filter: self.filter,
+            entries: Rc::new(RefCell::new(vec![])),
+            action,
+            path_menu,
+            list,
+            name_field,
+            confirm_button,
+            on_result: Rc::new(RefCell::new(self.on_result)),
+            closer: window.get_closer(),
+            opener: window.get_opener(),
+            screen_width,
+            screen_height,
+        };
+        body.show_directory(&self.directory, entries);
+        window.set_body(Box::new(body));
+        Ok(window)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    ///A fresh directory in the temporary directory, removed when dropped.
+    struct TempDir{
+        path: PathBuf,
+    }
+
+    impl TempDir{
+        fn new(name: &str) -> Self{
+            let path = std::env::temp_dir().join(format!("modesto_file_dialog_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir{path}
+        }
+    }
+
+    impl Drop for TempDir{
+        fn drop(&mut self){
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    #[test]
+    fn lists_directories_first_and_filters_files(){
+        let dir = TempDir::new("list");
+        for name in ["b.txt", "A.TXT", "c.png", ".hidden.txt", "noext"]{
+            fs::write(dir.path.join(name), b"data").unwrap();
+        }
+        fs::create_dir(dir.path.join("zeta")).unwrap();
+        fs::create_dir(dir.path.join("Alpha.png")).unwrap();
+        let names = |filter: &[String]| -> Vec<String>{
+            list_directory(&dir.path, filter).unwrap().into_iter().map(|e| e.name).collect()
+        };
+        assert_eq!(names(&[String::from("txt")]), ["Alpha.png", "zeta", "A.TXT", "b.txt"]);
+        assert_eq!(names(&[]), ["Alpha.png", "zeta", "A.TXT", "b.txt", "c.png", "noext"]);
+        let entries = list_directory(&dir.path, &[String::from("png")]).unwrap();
+        assert!(entries[0].is_dir && entries[0].size == 0);
+        assert_eq!(entries[2].size, 4);
+    }
+
+    #[test]
+    fn filter_ignores_case_and_needs_an_extension(){
+        let filter = [String::from("png"), String::from("bmp")];
+        assert!(matches_filter(Path::new("a/b.PNG"), &filter));
+        assert!(matches_filter(Path::new("c.bmp"), &filter));
+        assert!(!matches_filter(Path::new("c.bmp.txt"), &filter));
+        assert!(!matches_filter(Path::new("png"), &filter));
+        assert!(matches_filter(Path::new("png"), &[]));
+    }
+
+    ///Build a save dialog for the given name and press its Save button, returning the dialog window
+    /// and the path it reported, if any.
+    fn save_as(dir: &Path, name: &str) -> (WindowWidget, Rc<RefCell<Option<Option<PathBuf>>>>){
+        let result = Rc::new(RefCell::new(None));
+        let reported = result.clone();
+        let mut dialog = FileDialog::save(dir, name);
+        dialog.set_on_result(Box::new(move |path| *reported.borrow_mut() = Some(path)));
+        let mut window = dialog.into_window(720, 480).unwrap();
+        assert!(window.handle_default_action());
+        (window, result)
+    }
+
+    #[test]
+    fn saving_a_new_file_reports_its_path(){
+        let dir = TempDir::new("save_new");
+        let (mut window, result) = save_as(&dir.path, "new.txt");
+        assert_eq!(*result.borrow(), Some(Some(dir.path.join("new.txt"))));
+        assert!(window.is_close_requested());
+        assert!(window.take_opened_windows().is_empty());
+    }
+
+    #[test]
+    fn saving_over_an_existing_file_asks_first(){
+        let dir = TempDir::new("save_existing");
+        fs::write(dir.path.join("old.txt"), b"keep").unwrap();
+        let (mut window, result) = save_as(&dir.path, "old.txt");
+        assert_eq!(*result.borrow(), None);
+        assert!(!window.is_close_requested());
+        let opened = window.take_opened_windows();
+        assert_eq!(opened.len(), 1);
+        assert!(opened[0].is_modal());
+    }
+}
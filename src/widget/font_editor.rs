@@ -0,0 +1,683 @@
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::clipboard::Clipboard;
+use crate::pixel_font::{FontPixel, PixelFont, PixelFontChar};
+use crate::widget::alert::{Alert, AlertIcon};
+use crate::widget::button::Button;
+use crate::widget::container::ContainerWidget;
+use crate::widget::event::{EditAction, MouseButton, WidgetEvent};
+use crate::widget::file_dialog::FileDialog;
+use crate::widget::list_view::{ListColumn, ListDataSource, ListView};
+use crate::widget::menu::{MenuItem, PopupRequest};
+use crate::widget::slider::{Orientation, Slider};
+use crate::widget::text_field::TextField;
+use crate::widget::text_widget::TextWidget;
+use crate::widget::window::{WindowOpener, WindowWidget};
+use crate::widget::{Color, Widget, WidgetBounds};
+
+///Size of the font editor window.
+const EDITOR_WIDTH: usize = 480;
+const EDITOR_HEIGHT: usize = 335;
+///Space between the edges of the window and its contents.
+const EDITOR_MARGIN: usize = 12;
+///Size of the character list and its columns.
+const LIST_WIDTH: usize = 110;
+const LIST_HEIGHT: usize = 200;
+const CHAR_COLUMN_WIDTH: usize = 40;
+const CODE_COLUMN_WIDTH: usize = 66;
+///Size of the zoomed character, and the largest zoom it is shown in.
+const GRID_SIZE: usize = 200;
+const MAX_ZOOM: usize = 12;
+///Width of the controls right of the zoomed character.
+const CONTROLS_WIDTH: usize = 120;
+///Largest width and spacing the sliders offer.
+const MAX_GLYPH_WIDTH: i32 = 32;
+const MAX_SPACING: i32 = 16;
+///Height of the preview line at the bottom.
+const PREVIEW_HEIGHT: usize = 40;
+///Text shown in the preview until another one is typed.
+const DEFAULT_SAMPLE: &str = "The quick brown fox jumps over the lazy dog";
+///Colours of the zoomed character: empty pixels in the spacing, grid lines and the baseline.
+const SPACING_COLOR: [u8; 4] = [221u8, 221u8, 221u8, 255u8];
+const GRID_COLOR: [u8; 4] = [187u8, 187u8, 187u8, 255u8];
+const BASELINE_COLOR: [u8; 4] = [221u8, 8u8, 6u8, 255u8];
+
+///A character of a font, zoomed so every pixel is a square of the grid. Clicking a pixel toggles
+/// it; dragging paints the other pixels the same. The spacing after the bitmap is shaded and the
+/// baseline drawn in red.
+pub struct GlyphGrid{
+    font: Rc<RefCell<PixelFont>>,
+    glyph: Option<char>,
+    width: usize,
+    height: usize,
+    ///Coverage the pixels dragged over get, while the mouse is held down.
+    painting: Option<u8>,
+    on_change: Option<Box<dyn FnMut(char)>>,
+    cache: Box<Vec<[u8; 4]>>,
+    needs_redraw: bool,
+}
+
+impl GlyphGrid{
+    ///Create a grid of the given size editing the characters of a shared font.
+    pub fn new(font: Rc<RefCell<PixelFont>>, width: usize, height: usize) -> Self{
+        GlyphGrid{
+            font,
+            glyph: None,
+            width,
+            height,
+            painting: None,
+            on_change: None,
+            cache: Box::new(vec![]),
+            needs_redraw: true,
+        }
+    }
+    ///Set the callback run with the character whenever a pixel of it was changed.
+    pub fn set_on_change(&mut self, callback: Box<dyn FnMut(char)>){
+        self.on_change = Some(callback);
+    }
+    pub fn get_glyph(&self) -> Option<char>{
+        self.glyph
+    }
+    ///Show another character of the font, or none.
+    pub fn set_glyph(&mut self, glyph: Option<char>){
+        self.glyph = glyph;
+        self.painting = None;
+        self.needs_redraw = true;
+    }
+    ///Draw the character again after it was changed elsewhere, e.g. made wider.
+    pub fn refresh(&mut self){
+        self.needs_redraw = true;
+    }
+    ///Get the size of the squares the given character is drawn with.
+    fn get_zoom(&self, glyph: &PixelFontChar) -> usize{
+        let columns = (glyph.get_width() + glyph.right_offset).max(1);
+        let rows = glyph.get_height().max(1);
+        ((self.width - 1) / columns).min((self.height - 1) / rows).clamp(1, MAX_ZOOM)
+    }
+    ///Get the pixel of the bitmap under the given point.
+    fn pixel_at(&self, x: isize, y: isize) -> Option<(usize, usize)>{
+        let font = self.font.borrow();
+        let glyph = font.get_char(self.glyph?)?;
+        if x < 0 || y < 0{
+            return None
+        }
+        let zoom = self.get_zoom(glyph);
+        let (px, py) = (x as usize / zoom, y as usize / zoom);
+        if px < glyph.get_width() && py < glyph.get_height() {Some((px, py))} else {None}
+    }
+    ///Give the pixel under the point the coverage being painted.
+    fn paint(&mut self, x: isize, y: isize){
+        let (c, alpha) = match (self.glyph, self.painting){
+            (Some(c), Some(alpha)) => (c, alpha),
+            _ => return
+        };
+        let (px, py) = match self.pixel_at(x, y){
+            Some(p) => p,
+            None => return
+        };
+        let changed = match self.font.borrow_mut().get_char_mut(c){
+            Some(glyph) if glyph.get_pixel(px, py).map(|p| p.get_alpha()) != Some(alpha) => {
+                glyph.set_pixel(px, py, FontPixel::new(alpha));
+                true
+            },
+            _ => false
+        };
+        if changed{
+            self.needs_redraw = true;
+            if let Some(callback) = &mut self.on_change{
+                callback(c);
+            }
+        }
+    }
+}
+
+impl Widget for GlyphGrid{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && self.cache.len() == width * height{
+            return None
+        }
+        let mut buf = vec![[255u8; 4]; width * height];
+        let font = self.font.borrow();
+        if let Some(glyph) = self.glyph.and_then(|c| font.get_char(c)){
+            let zoom = self.get_zoom(glyph);
+            let columns = glyph.get_width() + glyph.right_offset;
+            let mut fill = |x: usize, y: usize, color: [u8; 4]|{
+                if x < width && y < height{
+                    buf[y * width + x] = color;
+                }
+            };
+            for row in 0..glyph.get_height(){
+                for column in 0..columns{
+                    let color = match glyph.get_pixel(column, row){
+                        Some(pixel) => {
+                            let v = 255 - pixel.get_alpha();
+                            [v, v, v, 255u8]
+                        },
+                        None => SPACING_COLOR
+                    };
+                    for y in row * zoom..(row + 1) * zoom{
+                        for x in column * zoom..(column + 1) * zoom{
+                            //grid lines on the top and left of every square, if there is room
+                            let on_line = zoom >= 4 && (x == column * zoom || y == row * zoom);
+                            fill(x, y, if on_line {GRID_COLOR} else {color});
+                        }
+                    }
+                }
+            }
+            for x in 0..=columns * zoom{
+                fill(x, glyph.get_height() * zoom, GRID_COLOR);
+            }
+            for y in 0..=glyph.get_height() * zoom{
+                fill(columns * zoom, y, GRID_COLOR);
+            }
+            if glyph.ascent >= 0{
+                for x in 0..=columns * zoom{
+                    fill(x, glyph.ascent as usize * zoom, BASELINE_COLOR);
+                }
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{x, y, button: MouseButton::Left, ..} => {
+                let (c, (px, py)) = match (self.glyph, self.pixel_at(x, y)){
+                    (Some(c), Some(p)) => (c, p),
+                    _ => return false
+                };
+                //the first pixel decides whether the drag sets or clears pixels
+                let alpha = self.font.borrow().get_char(c).and_then(|g| g.get_pixel(px, py)).map_or(0, |p| p.get_alpha());
+                self.painting = Some(if alpha >= 128 {0u8} else {255u8});
+                self.paint(x, y);
+                true
+            },
+            WidgetEvent::MouseMove{x, y, pressed: true} if self.painting.is_some() => {
+                self.paint(x, y);
+                true
+            },
+            WidgetEvent::MouseUp{..} => self.painting.take().is_some(),
+            _ => false
+        }
+    }
+}
+
+///A line of text in a shared font, framed, redrawn whenever it is told the font changed.
+struct FontPreview{
+    font: Rc<RefCell<PixelFont>>,
+    text: String,
+    width: usize,
+    height: usize,
+    cache: Box<Vec<[u8; 4]>>,
+    needs_redraw: bool,
+}
+
+impl FontPreview{
+    fn set_text(&mut self, text: &str){
+        self.text = String::from(text);
+        self.needs_redraw = true;
+    }
+    fn refresh(&mut self){
+        self.needs_redraw = true;
+    }
+}
+
+impl Widget for FontPreview{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && self.cache.len() == width * height{
+            return None
+        }
+        let mut buf = vec![[255u8; 4]; width * height];
+        for x in 0..width{
+            buf[x] = [0u8, 0u8, 0u8, 255u8];
+            buf[(height - 1) * width + x] = [0u8, 0u8, 0u8, 255u8];
+        }
+        for y in 0..height{
+            buf[y * width] = [0u8, 0u8, 0u8, 255u8];
+            buf[y * width + width - 1] = [0u8, 0u8, 0u8, 255u8];
+        }
+        let font = self.font.borrow().clone();
+        let line_height = font.get_line_height();
+        let text = TextWidget::new(Box::new(font), false, &self.text, Color::black(), Color::white());
+        let y = (height as isize - line_height as isize) / 2;
+        buf = text.draw_onto(buf, width, height, 4, y);
+        self.cache = Box::new(buf.clone());
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+}
+
+///Rows of the character list: the characters of the font, shared with the editor.
+struct CharsetSource{
+    chars: Rc<RefCell<Vec<char>>>,
+}
+
+impl ListDataSource for CharsetSource{
+    fn get_row_count(&self) -> usize {
+        self.chars.borrow().len()
+    }
+    fn get_text(&self, row: usize, column: usize) -> String {
+        let c = self.chars.borrow()[row];
+        if column == 0 {String::from(c)} else {format!("U+{:04X}", c as u32)}
+    }
+    fn sort(&mut self, _column: usize, ascending: bool) {
+        //both columns sort by code point
+        self.chars.borrow_mut().sort_by(|a, b| if ascending {a.cmp(b)} else {b.cmp(a)});
+    }
+}
+
+///Something the user did in the font editor, handled once the widget that reported it is done.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum EditorAction{
+    ///A row of the character list was selected.
+    Select(usize),
+    ///Pixels of the shown character were changed.
+    Edited,
+    Width(i32),
+    Spacing(i32),
+    SampleChanged,
+    AddChar,
+    Open,
+    Save,
+    ///A file was chosen in the Open or Save dialog.
+    Load(PathBuf),
+    Write(PathBuf),
+}
+
+///Body of the font editor window. Like the file dialog, its controls report what the user did
+/// through a shared action, performed after the event was handled.
+struct FontEditorBody{
+    container: ContainerWidget,
+    font: Rc<RefCell<PixelFont>>,
+    chars: Rc<RefCell<Vec<char>>>,
+    action: Rc<Cell<Option<EditorAction>>>,
+    list: Rc<RefCell<ListView>>,
+    grid: Rc<RefCell<GlyphGrid>>,
+    width_label: Rc<RefCell<TextWidget>>,
+    width_slider: Rc<RefCell<Slider>>,
+    spacing_label: Rc<RefCell<TextWidget>>,
+    spacing_slider: Rc<RefCell<Slider>>,
+    new_char_field: Rc<RefCell<TextField>>,
+    sample_field: Rc<RefCell<TextField>>,
+    preview: Rc<RefCell<FontPreview>>,
+    ///File the font was last opened from or saved to.
+    path: Option<PathBuf>,
+    opener: WindowOpener,
+    screen_width: usize,
+    screen_height: usize,
+}
+
+impl FontEditorBody{
+    ///Show the given character in the grid and its metrics in the controls.
+    fn select(&mut self, c: char){
+        self.grid.borrow_mut().set_glyph(Some(c));
+        self.update_metrics();
+    }
+    ///Show the width and spacing of the selected character.
+    fn update_metrics(&mut self){
+        let c = match self.grid.borrow().get_glyph(){
+            Some(c) => c,
+            None => return
+        };
+        let (width, spacing) = match self.font.borrow().get_char(c){
+            Some(glyph) => (glyph.get_width(), glyph.right_offset),
+            None => return
+        };
+        let mut width_slider = self.width_slider.borrow_mut();
+        width_slider.set_range(0, MAX_GLYPH_WIDTH.max(width as i32));
+        width_slider.set_value(width as i32);
+        let mut spacing_slider = self.spacing_slider.borrow_mut();
+        spacing_slider.set_range(0, MAX_SPACING.max(spacing as i32));
+        spacing_slider.set_value(spacing as i32);
+        self.width_label.borrow_mut().set_text(&format!("Width {}", width));
+        self.spacing_label.borrow_mut().set_text(&format!("Spacing {}", spacing));
+    }
+    ///Fill the character list from the font, selecting the given character (or the first one).
+    fn reload_chars(&mut self, selected: Option<char>){
+        *self.chars.borrow_mut() = self.font.borrow().get_charset().keys().copied().collect();
+        let row = selected.and_then(|c| self.chars.borrow().iter().position(|d| *d == c)).unwrap_or(0);
+        let first = self.chars.borrow().get(row).copied();
+        let mut list = self.list.borrow_mut();
+        list.reload();
+        match first{
+            Some(c) => {
+                list.set_selection(&[row]);
+                list.scroll_to_row(row);
+                drop(list);
+                self.select(c);
+            },
+            None => {
+                drop(list);
+                self.grid.borrow_mut().set_glyph(None);
+            }
+        }
+        self.preview.borrow_mut().refresh();
+    }
+    ///Change the selected character.
+    fn change_glyph(&mut self, change: impl FnOnce(&mut PixelFontChar)){
+        let c = match self.grid.borrow().get_glyph(){
+            Some(c) => c,
+            None => return
+        };
+        if let Some(glyph) = self.font.borrow_mut().get_char_mut(c){
+            change(glyph);
+        }
+        self.grid.borrow_mut().refresh();
+        self.preview.borrow_mut().refresh();
+        self.update_metrics();
+    }
+    ///Add an empty character for the one typed into the new character field.
+    fn add_char(&mut self){
+        let c = match self.new_char_field.borrow().get_text().chars().next(){
+            Some(c) => c,
+            None => return
+        };
+        let mut font = self.font.borrow_mut();
+        if !font.has_char(c){
+            let height = font.get_line_height().max(1);
+            let mut glyph = PixelFontChar::new((height / 2).max(1), height, vec![FontPixel::new(0u8); (height / 2).max(1) * height]);
+            glyph.ascent = font.get_ascent() as isize;
+            glyph.right_offset = 1;
+            font.set_char(c, glyph);
+        }
+        drop(font);
+        self.new_char_field.borrow_mut().set_text("");
+        self.reload_chars(Some(c));
+    }
+    ///Show an alert about a file that couldn't be read or written.
+    fn show_error(&self, message: &str){
+        let alert = Alert::new(AlertIcon::Stop, message, &["OK"]);
+        self.opener.open(alert.into_window(self.screen_width, self.screen_height));
+    }
+    ///Get the directory the file dialogs start in: that of the font, or the working directory.
+    fn get_directory(&self) -> PathBuf{
+        self.path.as_ref().and_then(|p| p.parent()).map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("/"))
+    }
+    ///Ask for a file to open, with a dialog reporting back through the shared action.
+    fn show_file_dialog(&mut self, mut dialog: FileDialog, done: fn(PathBuf) -> EditorAction){
+        let reported = self.action.clone();
+        dialog.set_on_result(Box::new(move |path| {
+            if let Some(path) = path{
+                reported.set(Some(done(path)));
+            }
+        }));
+        match dialog.into_window(self.screen_width, self.screen_height){
+            Ok(window) => self.opener.open(window),
+            Err(e) => self.show_error(&format!("The folder can't be opened: {}", e))
+        }
+    }
+    ///Perform what the controls reported during the last event.
+    fn perform_action(&mut self){
+        let action = match self.action.take(){
+            Some(a) => a,
+            None => return
+        };
+        match action{
+            EditorAction::Select(row) => {
+                let c = self.chars.borrow().get(row).copied();
+                if let Some(c) = c{
+                    self.select(c);
+                }
+            },
+            EditorAction::Edited => self.preview.borrow_mut().refresh(),
+            EditorAction::Width(width) => self.change_glyph(|glyph| glyph.set_width(width.max(0) as usize)),
+            EditorAction::Spacing(spacing) => self.change_glyph(|glyph| glyph.right_offset = spacing.max(0) as usize),
+            EditorAction::SampleChanged => {
+                let text = String::from(self.sample_field.borrow().get_text());
+                self.preview.borrow_mut().set_text(&text);
+            },
+            EditorAction::AddChar => self.add_char(),
+            EditorAction::Open => {
+                let mut dialog = FileDialog::open(&self.get_directory());
+                dialog.set_filter(&["mpf", "bdf"]);
+                self.show_file_dialog(dialog, EditorAction::Load);
+            },
+            EditorAction::Save => {
+                let name = self.path.as_ref().and_then(|p| p.file_stem())
+                    .map(|stem| format!("{}.mpf", stem.to_string_lossy()))
+                    .unwrap_or_else(|| String::from("Untitled.mpf"));
+                let mut dialog = FileDialog::save(&self.get_directory(), &name);
+                dialog.set_filter(&["mpf"]);
+                self.show_file_dialog(dialog, EditorAction::Write);
+            },
+            EditorAction::Load(path) => {
+                match PixelFont::load(&path){
+                    Ok(font) => {
+                        *self.font.borrow_mut() = font;
+                        self.path = Some(path);
+                        self.reload_chars(None);
+                    },
+                    Err(e) => self.show_error(&format!("The font \"{}\" can't be opened: {}", path.display(), e))
+                }
+            },
+            EditorAction::Write(path) => {
+                match self.font.borrow().save(&path){
+                    Ok(()) => self.path = Some(path),
+                    Err(e) => self.show_error(&format!("The font \"{}\" can't be saved: {}", path.display(), e))
+                }
+            },
+        }
+    }
+}
+
+impl Widget for FontEditorBody{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.container.render(width, height)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        self.container.get_children()
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        self.container.get_min_bounds()
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        self.container.get_cache()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.container.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        //also runs on ticks, which perform the files chosen in the dialogs
+        let consumed = self.container.handle_event(event);
+        self.perform_action();
+        consumed
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.container.accepts_focus()
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        let performed = self.container.handle_edit_action(action, clipboard);
+        self.perform_action();
+        performed
+    }
+
+    fn get_caret_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        self.container.get_caret_rect()
+    }
+
+    fn handle_default_action(&mut self) -> bool {
+        let performed = self.container.handle_default_action();
+        self.perform_action();
+        performed
+    }
+
+    fn get_context_menu(&mut self, x: isize, y: isize) -> Option<Vec<MenuItem>> {
+        self.container.get_context_menu(x, y)
+    }
+
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        self.container.take_popup_request()
+    }
+}
+
+///A window for drawing the characters of a pixel font: a list of its characters, the selected
+/// one zoomed for toggling its pixels, sliders for its width and spacing, and a preview of a line
+/// of text in the font. Fonts are opened from BDF or binary files and saved in the binary format
+/// of font_file.
+pub struct FontEditor{
+    font: PixelFont,
+    path: Option<PathBuf>,
+    sample: String,
+}
+
+impl FontEditor{
+    ///Create an editor for the given font, e.g. a copy of the theme font.
+    pub fn new(font: PixelFont) -> Self{
+        FontEditor{
+            font,
+            path: None,
+            sample: String::from(DEFAULT_SAMPLE),
+        }
+    }
+    ///Create an editor for the font in the given file.
+    pub fn open(path: &Path) -> std::io::Result<Self>{
+        let mut editor = FontEditor::new(PixelFont::load(path)?);
+        editor.path = Some(path.to_path_buf());
+        Ok(editor)
+    }
+    ///Set the text shown in the preview at first.
+    pub fn set_sample(&mut self, sample: &str){
+        self.sample = String::from(sample);
+    }
+    ///Build the window showing the editor at the given position. Dialogs it opens are centered on
+    /// a screen of the given size.
+    pub fn into_window(self, x: usize, y: usize, screen_width: usize, screen_height: usize) -> WindowWidget{
+        let mut window = WindowWidget::new(" Font Editor ", EDITOR_WIDTH, EDITOR_HEIGHT, x, y);
+        let body_bounds = window.get_body_bounds();
+        let font = Rc::new(RefCell::new(self.font));
+        let chars = Rc::new(RefCell::new(vec![]));
+        let action = Rc::new(Cell::new(None));
+
+        let list = Rc::new(RefCell::new(ListView::with_columns(
+            Box::new(CharsetSource{chars: chars.clone()}),
+            vec![ListColumn::new("Char", CHAR_COLUMN_WIDTH), ListColumn::new("Code", CODE_COLUMN_WIDTH)],
+            LIST_WIDTH, LIST_HEIGHT)));
+        let reported = action.clone();
+        list.borrow_mut().set_on_selection_change(Box::new(move |rows| {
+            if let Some(row) = rows.first(){
+                reported.set(Some(EditorAction::Select(*row)));
+            }
+        }));
+        let grid = Rc::new(RefCell::new(GlyphGrid::new(font.clone(), GRID_SIZE, GRID_SIZE)));
+        let reported = action.clone();
+        grid.borrow_mut().set_on_change(Box::new(move |_| reported.set(Some(EditorAction::Edited))));
+        let label = |text: &str| Rc::new(RefCell::new(
+            TextWidget::new(Box::new(PixelFont::default()), false, text, Color::black(), Color::white())));
+        let width_label = label("Width");
+        let width_slider = Rc::new(RefCell::new(Slider::new(Orientation::Horizontal, CONTROLS_WIDTH, 0, MAX_GLYPH_WIDTH, 0)));
+        let reported = action.clone();
+        width_slider.borrow_mut().set_on_change(Box::new(move |value| reported.set(Some(EditorAction::Width(value)))));
+        let spacing_label = label("Spacing");
+        let spacing_slider = Rc::new(RefCell::new(Slider::new(Orientation::Horizontal, CONTROLS_WIDTH, 0, MAX_SPACING, 0)));
+        let reported = action.clone();
+        spacing_slider.borrow_mut().set_on_change(Box::new(move |value| reported.set(Some(EditorAction::Spacing(value)))));
+        let new_char_field = Rc::new(RefCell::new(TextField::new(40, "")));
+        let reported = action.clone();
+        new_char_field.borrow_mut().set_on_submit(Box::new(move |_| reported.set(Some(EditorAction::AddChar))));
+        let mut add_button = Button::new("Add", CONTROLS_WIDTH - 48);
+        let reported = action.clone();
+        add_button.set_on_click(Box::new(move || reported.set(Some(EditorAction::AddChar))));
+        let mut open_button = Button::new("Open", CONTROLS_WIDTH);
+        let reported = action.clone();
+        open_button.set_on_click(Box::new(move || reported.set(Some(EditorAction::Open))));
+        let mut save_button = Button::new("Save", CONTROLS_WIDTH);
+        let reported = action.clone();
+        save_button.set_on_click(Box::new(move || reported.set(Some(EditorAction::Save))));
+        let sample_width = body_bounds.width - EDITOR_MARGIN * 2;
+        let sample_field = Rc::new(RefCell::new(TextField::new(sample_width, &self.sample)));
+        let reported = action.clone();
+        sample_field.borrow_mut().set_on_change(Box::new(move |_| reported.set(Some(EditorAction::SampleChanged))));
+        let preview = Rc::new(RefCell::new(FontPreview{
+            font: font.clone(),
+            text: self.sample.clone(),
+            width: sample_width,
+            height: PREVIEW_HEIGHT,
+            cache: Box::new(vec![]),
+            needs_redraw: true,
+        }));
+
+        let mut container = ContainerWidget::new(body_bounds.width, body_bounds.height);
+        let grid_x = EDITOR_MARGIN * 2 + LIST_WIDTH;
+        let controls_x = grid_x + GRID_SIZE + EDITOR_MARGIN;
+        container.add_child(Box::new(list.clone()), EDITOR_MARGIN, EDITOR_MARGIN);
+        container.add_child(Box::new(grid.clone()), grid_x, EDITOR_MARGIN);
+        container.add_child(Box::new(width_label.clone()), controls_x, EDITOR_MARGIN);
+        container.add_child(Box::new(width_slider.clone()), controls_x, EDITOR_MARGIN + 18);
+        container.add_child(Box::new(spacing_label.clone()), controls_x, EDITOR_MARGIN + 48);
+        container.add_child(Box::new(spacing_slider.clone()), controls_x, EDITOR_MARGIN + 66);
+        container.add_child(Box::new(new_char_field.clone()), controls_x, EDITOR_MARGIN + 103);
+        container.add_child(Box::new(add_button), controls_x + 48, EDITOR_MARGIN + 100);
+        container.add_child(Box::new(open_button), controls_x, EDITOR_MARGIN + 136);
+        container.add_child(Box::new(save_button), controls_x, EDITOR_MARGIN + 168);
+        let sample_y = EDITOR_MARGIN * 2 + LIST_HEIGHT;
+        container.add_child(Box::new(sample_field.clone()), EDITOR_MARGIN, sample_y);
+        container.add_child(Box::new(preview.clone()), EDITOR_MARGIN, sample_y + 30);
+
+        let mut body = FontEditorBody{
+            container,
+            font,
+            chars,
+            action,
+            list,
+            grid,
+            width_label,
+            width_slider,
+            spacing_label,
+            spacing_slider,
+            new_char_field,
+            sample_field,
+            preview,
+            path: self.path,
+            opener: window.get_opener(),
+            screen_width,
+            screen_height,
+        };
+        body.reload_chars(None);
+        window.set_body(Box::new(body));
+        window
+    }
+}
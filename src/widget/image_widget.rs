@@ -0,0 +1,147 @@
+use crate::image::Image;
+use crate::widget;
+use crate::widget::{Widget, WidgetBounds};
+
+///How an ImageWidget fits its image into the space it is given. The image is always centered.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ImageScaling{
+    ///Shown at its own size, cut off if it doesn't fit.
+    None,
+    ///Scaled to the largest size that fits, keeping the aspect ratio.
+    Fit,
+    ///Scaled to the smallest size that covers the whole widget, keeping the aspect ratio. The parts
+    /// that stick out are cut off.
+    Fill,
+    ///Scaled by the largest whole number that fits (at least 1), so every pixel stays square.
+    Integer,
+}
+
+///A widget showing a bitmap image, scaled (nearest neighbour) and optionally dithered to a fixed
+/// palette, such as image::MONO_PALETTE for the 1-bit look.
+pub struct ImageWidget{
+    image: Image,
+    scaling: ImageScaling,
+    dither_palette: Option<Vec<[u8; 4]>>,
+    ///Colour shown where the image doesn't cover the widget or is transparent.
+    background: [u8; 4],
+    ///Preferred size, the image size unless set.
+    width: usize,
+    height: usize,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl ImageWidget{
+    ///Create a new widget showing the given image at its own size.
+    pub fn new(image: Image) -> Self{
+        ImageWidget{
+            width: image.width,
+            height: image.height,
+            image,
+            scaling: ImageScaling::None,
+            dither_palette: None,
+            background: [255u8; 4],
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Get the image shown.
+    pub fn get_image(&self) -> &Image{
+        &self.image
+    }
+    ///Show another image. The preferred size stays the same.
+    pub fn set_image(&mut self, image: Image){
+        self.image = image;
+        self.needs_redraw = true;
+    }
+    ///Get how the image is fit into the widget.
+    pub fn get_scaling(&self) -> ImageScaling{
+        self.scaling
+    }
+    ///Set how the image is fit into the widget.
+    pub fn set_scaling(&mut self, scaling: ImageScaling){
+        self.scaling = scaling;
+        self.needs_redraw = true;
+    }
+    ///Dither the image to the given palette after scaling it, or show it as it is with None.
+    pub fn set_dither_palette(&mut self, palette: Option<&[[u8; 4]]>){
+        self.dither_palette = palette.map(|p| p.to_vec());
+        self.needs_redraw = true;
+    }
+    ///Set the colour shown around and behind the image.
+    pub fn set_background(&mut self, background: [u8; 4]){
+        self.background = background;
+        self.needs_redraw = true;
+    }
+    ///Set the preferred size of the widget, so a scaled image can be shown larger or smaller than
+    /// its own size.
+    pub fn set_size(&mut self, width: usize, height: usize){
+        self.width = width;
+        self.height = height;
+    }
+    ///Get the size the image is drawn at in a widget of the given size.
+    fn get_scaled_size(&self, width: usize, height: usize) -> (usize, usize){
+        let (image_width, image_height) = (self.image.width.max(1), self.image.height.max(1));
+        match self.scaling{
+            ImageScaling::None => (self.image.width, self.image.height),
+            ImageScaling::Fit | ImageScaling::Fill => {
+                let (scale_x, scale_y) = (width as f32 / image_width as f32, height as f32 / image_height as f32);
+                let scale = if self.scaling == ImageScaling::Fit {scale_x.min(scale_y)} else {scale_x.max(scale_y)};
+                (((image_width as f32 * scale).round() as usize).max(1), ((image_height as f32 * scale).round() as usize).max(1))
+            },
+            ImageScaling::Integer => {
+                let scale = (width / image_width).min(height / image_height).max(1);
+                (self.image.width * scale, self.image.height * scale)
+            }
+        }
+    }
+}
+
+impl Widget for ImageWidget{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let buf = vec![self.background; width * height];
+        let (scaled_width, scaled_height) = self.get_scaled_size(width, height);
+        let buf = if self.image.pixels.is_empty(){
+            buf
+        } else {
+            let mut scaled = self.image.scaled(scaled_width, scaled_height);
+            if let Some(palette) = &self.dither_palette{
+                scaled = scaled.dithered(palette);
+            }
+            widget::draw_masked_at(
+                ((width as isize - scaled_width as isize) / 2, (height as isize - scaled_height as isize) / 2),
+                buf, (width, height),
+                &scaled.pixels, (scaled_width, scaled_height))
+        };
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+}
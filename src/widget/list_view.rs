@@ -0,0 +1,597 @@
+use std::collections::BTreeSet;
+use std::time::Instant;
+use crate::clipboard::Clipboard;
+use crate::icon::SMALL_ICON_SIZE;
+use crate::pixel_font::PixelFont;
+use crate::widget;
+use crate::widget::event::{EditAction, Key, WidgetEvent, DOUBLE_CLICK_TIME};
+use crate::widget::scroll_view::{ScrollBar, SCROLL_BAR_WIDTH};
+use crate::widget::text_widget::TextWidget;
+use crate::widget::{Color, Widget, WidgetBounds};
+
+///Height of a single row.
+pub const ROW_HEIGHT: usize = 18;
+///Height of the column header row of tables.
+const HEADER_HEIGHT: usize = 20;
+///Size of the icons shown in front of the first column.
+pub const ICON_SIZE: usize = SMALL_ICON_SIZE;
+///Space left of the content of each cell.
+const CELL_PADDING: usize = 4;
+
+///Callback run with the selected rows of a list.
+pub type SelectionCallback = Box<dyn FnMut(&[usize])>;
+
+///Provides the rows shown by a ListView. The list only asks for the rows it currently shows, so
+/// sources may hold many more rows than fit on screen.
+pub trait ListDataSource{
+    ///Get the number of rows.
+    fn get_row_count(&self) -> usize;
+    ///Get the text of the given cell. Lists without columns only ask for column 0.
+    fn get_text(&self, row: usize, column: usize) -> String;
+    ///Get the 16x16 icon shown in front of the first column of the given row, as RGBA pixels row
+    /// by row. Fully transparent pixels are not drawn.
+    fn get_icon(&self, _row: usize) -> Option<Vec<[u8; 4]>>{
+        None
+    }
+    ///Sort the rows by the given column. Called when the header of a table column is clicked.
+    fn sort(&mut self, _column: usize, _ascending: bool){}
+}
+
+///A plain list of strings.
+impl ListDataSource for Vec<String>{
+    fn get_row_count(&self) -> usize {
+        self.len()
+    }
+    fn get_text(&self, row: usize, _column: usize) -> String {
+        self[row].clone()
+    }
+    fn sort(&mut self, _column: usize, ascending: bool) {
+        self.sort_by(|a, b| if ascending {a.cmp(b)} else {b.cmp(a)});
+    }
+}
+
+///Rows of cells, sorted by comparing the text of the clicked column.
+impl ListDataSource for Vec<Vec<String>>{
+    fn get_row_count(&self) -> usize {
+        self.len()
+    }
+    fn get_text(&self, row: usize, column: usize) -> String {
+        self[row].get(column).cloned().unwrap_or_default()
+    }
+    fn sort(&mut self, column: usize, ascending: bool) {
+        self.sort_by(|a, b| {
+            let order = a.get(column).cmp(&b.get(column));
+            if ascending {order} else {order.reverse()}
+        });
+    }
+}
+
+///A column of a table: its header title and width in pixels.
+pub struct ListColumn{
+    pub title: String,
+    pub width: usize,
+}
+
+impl ListColumn{
+    ///Create a new column with the given header title and width.
+    pub fn new(title: &str, width: usize) -> Self{
+        ListColumn{
+            title: String::from(title),
+            width,
+        }
+    }
+}
+
+///How many rows can be selected at once.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SelectionMode{
+    Single,
+    ///Shift-click selects a range, command-click adds or removes single rows.
+    Multiple,
+}
+
+///A scrolling list of rows from a ListDataSource, optionally split into columns with clickable
+/// headers that sort the rows (a table). Only the visible rows are drawn.
+pub struct ListView{
+    width: usize,
+    height: usize,
+    source: Box<dyn ListDataSource>,
+    ///Columns of a table, empty for a plain list.
+    columns: Vec<ListColumn>,
+    ///Column the rows are sorted by and whether the order is ascending.
+    sort: Option<(usize, bool)>,
+    selection_mode: SelectionMode,
+    selected: BTreeSet<usize>,
+    ///Row range selections start from.
+    anchor: Option<usize>,
+    ///Row moved by the arrow keys.
+    cursor: Option<usize>,
+    bar: Box<ScrollBar>,
+    bar_captured: bool,
+    ///True while the mouse button is held after clicking a row, dragging the selection along.
+    dragging: bool,
+    focused: bool,
+    ///Time and row of the last click, to detect double clicks.
+    last_click: Option<(Instant, usize)>,
+    on_selection_change: Option<SelectionCallback>,
+    on_activate: Option<Box<dyn FnMut(usize)>>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl ListView{
+    ///Create a list of the given size showing the rows of the given source.
+    pub fn new(source: Box<dyn ListDataSource>, width: usize, height: usize) -> Self{
+        ListView::with_columns(source, vec![], width, height)
+    }
+    ///Create a table of the given size with the given columns. Clicking a column header sorts the
+    /// rows by that column, clicking it again reverses the order.
+    pub fn with_columns(source: Box<dyn ListDataSource>, columns: Vec<ListColumn>, width: usize, height: usize) -> Self{
+        let header_height = if columns.is_empty() {0} else {HEADER_HEIGHT};
+        let height = height.max(header_height + SCROLL_BAR_WIDTH * 3);
+        let mut list = ListView{
+            width: width.max(SCROLL_BAR_WIDTH * 2),
+            height,
+            source,
+            columns,
+            sort: None,
+            selection_mode: SelectionMode::Single,
+            selected: BTreeSet::new(),
+            anchor: None,
+            cursor: None,
+            bar: Box::new(ScrollBar::new(height - header_height, ROW_HEIGHT)),
+            bar_captured: false,
+            dragging: false,
+            focused: false,
+            last_click: None,
+            on_selection_change: None,
+            on_activate: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        };
+        list.update_range();
+        list
+    }
+    ///Set whether one or several rows can be selected at once. Clears the selection.
+    pub fn set_selection_mode(&mut self, mode: SelectionMode){
+        self.selection_mode = mode;
+        self.set_selection(&[]);
+    }
+    ///Set the callback run with the selected rows every time the user changes the selection.
+    pub fn set_on_selection_change(&mut self, callback: SelectionCallback){
+        self.on_selection_change = Some(callback);
+    }
+    ///Set the callback run with a row when it is double clicked, or Return is pressed on it.
+    pub fn set_on_activate(&mut self, callback: Box<dyn FnMut(usize)>){
+        self.on_activate = Some(callback);
+    }
+    ///Get the selected rows in ascending order.
+    pub fn get_selection(&self) -> Vec<usize>{
+        self.selected.iter().cloned().collect()
+    }
+    ///Select the given rows (only the first one in single selection mode). Rows out of range are
+    /// ignored. Does not run the selection callback.
+    pub fn set_selection(&mut self, rows: &[usize]){
+        let count = self.source.get_row_count();
+        let limit = if self.selection_mode == SelectionMode::Single {1} else {rows.len()};
+        self.selected = rows.iter().cloned().filter(|r| *r < count).take(limit).collect();
+        self.anchor = self.selected.iter().next().cloned();
+        self.cursor = self.anchor;
+        self.needs_redraw = true;
+    }
+    ///Get the data source.
+    pub fn get_data_source(&self) -> &dyn ListDataSource{
+        &*self.source
+    }
+    ///Get the data source to change its rows. Call reload afterwards.
+    pub fn get_data_source_mut(&mut self) -> &mut Box<dyn ListDataSource>{
+        &mut self.source
+    }
+    ///Replace the data source, clearing the selection and sort order and scrolling to the top.
+    pub fn set_data_source(&mut self, source: Box<dyn ListDataSource>){
+        self.source = source;
+        self.sort = None;
+        self.set_selection(&[]);
+        self.scroll_to_row(0);
+    }
+    ///Show the current rows of the data source, dropping selected rows that no longer exist.
+    pub fn reload(&mut self){
+        let count = self.source.get_row_count();
+        self.selected.retain(|r| *r < count);
+        self.anchor = self.anchor.filter(|r| *r < count);
+        self.cursor = self.cursor.filter(|r| *r < count);
+        self.update_range();
+        self.needs_redraw = true;
+    }
+    ///Scroll the least amount needed to show the given row.
+    pub fn scroll_to_row(&mut self, row: usize){
+        self.update_range();
+        self.bar.scroll_to_show(row * ROW_HEIGHT, ROW_HEIGHT);
+    }
+    ///Get the column the rows are sorted by and whether the order is ascending.
+    pub fn get_sort(&self) -> Option<(usize, bool)>{
+        self.sort
+    }
+    ///Sort the rows by the given column through the data source. The selection is cleared since
+    /// rows move around.
+    pub fn sort_by(&mut self, column: usize, ascending: bool){
+        self.source.sort(column, ascending);
+        self.sort = Some((column, ascending));
+        self.set_selection(&[]);
+    }
+    fn get_header_height(&self) -> usize{
+        if self.columns.is_empty() {0} else {HEADER_HEIGHT}
+    }
+    fn get_list_width(&self) -> usize{
+        self.width - SCROLL_BAR_WIDTH
+    }
+    fn get_viewport_height(&self) -> usize{
+        self.height - self.get_header_height()
+    }
+    fn update_range(&mut self){
+        let content = self.source.get_row_count() * ROW_HEIGHT;
+        self.bar.set_range(content, self.get_viewport_height());
+    }
+    ///Get the x offset and width of every column (a single one filling the list if there are no
+    /// columns).
+    fn get_column_rects(&self) -> Vec<(usize, usize)>{
+        if self.columns.is_empty(){
+            return vec![(0, self.get_list_width())]
+        }
+        let mut x = 0;
+        self.columns.iter().map(|c| {
+            let rect = (x, c.width);
+            x += c.width;
+            rect
+        }).collect()
+    }
+    ///Get the row at the given y offset (relative to the widget), even if it is past the last row.
+    fn row_at(&self, y: isize) -> Option<usize>{
+        let y = y - self.get_header_height() as isize + self.bar.get_offset() as isize;
+        if y < 0 {None} else {Some(y as usize / ROW_HEIGHT)}
+    }
+    ///Select the rows from the anchor to the given row, or only the given row if there is no
+    /// anchor or only one row may be selected.
+    fn select_to(&mut self, row: usize, extend: bool){
+        match (self.anchor, extend && self.selection_mode == SelectionMode::Multiple){
+            (Some(anchor), true) => {
+                self.selected = (anchor.min(row)..=anchor.max(row)).collect();
+            },
+            _ => {
+                self.selected = [row].into_iter().collect();
+                self.anchor = Some(row);
+            }
+        }
+        self.cursor = Some(row);
+        self.needs_redraw = true;
+    }
+    fn selection_changed(&mut self){
+        let selection = self.get_selection();
+        if let Some(callback) = &mut self.on_selection_change{
+            callback(&selection);
+        }
+    }
+    fn activate(&mut self, row: usize){
+        if let Some(callback) = &mut self.on_activate{
+            callback(row);
+        }
+    }
+    ///Handle a click on a row.
+    fn click_row(&mut self, row: usize, shift: bool, toggle: bool){
+        let count = self.source.get_row_count();
+        let old_selection = self.selected.clone();
+        if row >= count{
+            //clicking below the last row deselects everything
+            if !shift && !toggle{
+                self.set_selection(&[]);
+            }
+        } else if toggle && self.selection_mode == SelectionMode::Multiple{
+            if !self.selected.remove(&row){
+                self.selected.insert(row);
+            }
+            self.anchor = Some(row);
+            self.cursor = Some(row);
+            self.needs_redraw = true;
+        } else {
+            self.select_to(row, shift);
+            self.dragging = true;
+            let now = Instant::now();
+            let double_click = match self.last_click{
+                Some((time, last_row)) => last_row == row && now.duration_since(time) < DOUBLE_CLICK_TIME,
+                None => false
+            };
+            self.last_click = if double_click {None} else {Some((now, row))};
+            if double_click && !shift{
+                self.activate(row);
+            }
+        }
+        if self.selected != old_selection{
+            self.selection_changed();
+        }
+    }
+    ///Handle a click on the column header at the given x offset.
+    fn click_header(&mut self, x: isize){
+        let column = self.get_column_rects().iter()
+            .position(|(cx, cw)| x >= *cx as isize && x < (*cx + *cw) as isize);
+        if let Some(column) = column{
+            let ascending = match self.sort{
+                Some((c, ascending)) if c == column => !ascending,
+                _ => true
+            };
+            let had_selection = !self.selected.is_empty();
+            self.sort_by(column, ascending);
+            if had_selection{
+                self.selection_changed();
+            }
+        }
+    }
+    ///Render the given row into a buffer of the list's width.
+    fn render_row(&self, row: usize) -> Vec<[u8; 4]>{
+        let list_width = self.get_list_width();
+        let mut buf = vec![[255u8; 4]; list_width * ROW_HEIGHT];
+        for (column, (cell_x, cell_width)) in self.get_column_rects().into_iter().enumerate(){
+            let mut text_x = cell_x + CELL_PADDING;
+            if column == 0{
+                if let Some(icon) = self.source.get_icon(row){
+                    buf = widget::draw_masked_at(
+                        (text_x as isize, ((ROW_HEIGHT - ICON_SIZE) / 2) as isize),
+                        buf, (list_width, ROW_HEIGHT),
+                        &icon, (ICON_SIZE, ICON_SIZE));
+                    text_x += ICON_SIZE + CELL_PADDING;
+                }
+            }
+            let mut text = TextWidget::new(
+                Box::new(PixelFont::default()), false, &self.source.get_text(row, column),
+                Color::black(), Color::white());
+            let bounds = text.get_min_bounds();
+            let text_buf = match text.render(bounds.width, bounds.height){
+                Some(v) => v,
+                None => text.get_cache()
+            };
+            //clip the text to its cell
+            let visible_width = (cell_x + cell_width).saturating_sub(text_x + 1).min(bounds.width);
+            buf = widget::draw_on_top_at_clipped(
+                (text_x as isize, 1),
+                buf, (list_width, ROW_HEIGHT),
+                &widget::crop_width(&text_buf, bounds.width, visible_width), (visible_width, bounds.height));
+        }
+        if self.selected.contains(&row){
+            for p in buf.iter_mut(){
+                *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+            }
+        }
+        buf
+    }
+    ///Render the column headers, with an arrow on the sorted column.
+    fn render_header(&self) -> Vec<[u8; 4]>{
+        let black = [0u8, 0u8, 0u8, 255u8];
+        let list_width = self.get_list_width();
+        let width = self.width;
+        let mut buf = vec![[221u8, 221u8, 221u8, 255u8]; width * HEADER_HEIGHT];
+        for (column, (cell_x, cell_width)) in self.get_column_rects().into_iter().enumerate(){
+            let mut title = TextWidget::new(
+                Box::new(PixelFont::default()), false, &self.columns[column].title,
+                Color::black(), Color::rgb(221, 221, 221));
+            let bounds = title.get_min_bounds();
+            if let Some(title_buf) = title.render(bounds.width, bounds.height){
+                //leave room for the sort arrow
+                let visible_width = bounds.width.min(cell_width.saturating_sub(CELL_PADDING + 12));
+                buf = widget::draw_on_top_at_clipped(
+                    ((cell_x + CELL_PADDING) as isize, 2),
+                    buf, (width, HEADER_HEIGHT),
+                    &widget::crop_width(&title_buf, bounds.width, visible_width), (visible_width, bounds.height));
+            }
+            if let Some((sorted, ascending)) = self.sort{
+                if sorted == column && cell_width > 12{
+                    //small triangle, pointing up for ascending order
+                    let arrow_x = cell_x + cell_width - 11;
+                    for row in 0..4{
+                        let y = if ascending {7 + row} else {10 - row};
+                        for x in arrow_x + 3 - row..arrow_x + 4 + row{
+                            buf[y * width + x] = black;
+                        }
+                    }
+                }
+            }
+            let line_x = cell_x + cell_width - 1;
+            if line_x < list_width{
+                for y in 0..HEADER_HEIGHT{
+                    buf[y * width + line_x] = black;
+                }
+            }
+        }
+        for x in 0..width{
+            buf[(HEADER_HEIGHT - 1) * width + x] = black;
+        }
+        buf
+    }
+}
+
+impl Widget for ListView{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.update_range();
+        if !self.needs_redraw() && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let list_width = self.get_list_width();
+        let header_height = self.get_header_height();
+        let viewport_height = self.get_viewport_height();
+        let offset = self.bar.get_offset();
+        let mut viewport = vec![[255u8; 4]; list_width * viewport_height];
+        let first = offset / ROW_HEIGHT;
+        let last = ((offset + viewport_height) / ROW_HEIGHT + 1).min(self.source.get_row_count());
+        for row in first..last{
+            let row_buf = self.render_row(row);
+            viewport = widget::draw_on_top_at_clipped(
+                (0, (row * ROW_HEIGHT) as isize - offset as isize),
+                viewport, (list_width, viewport_height),
+                &row_buf, (list_width, ROW_HEIGHT));
+        }
+        if let (true, Some(cursor), SelectionMode::Multiple) = (self.focused, self.cursor, self.selection_mode){
+            //dotted line under the row moved by the arrow keys
+            let y = ((cursor + 1) * ROW_HEIGHT) as isize - offset as isize - 1;
+            if y >= 0 && (y as usize) < viewport_height{
+                for x in (0..list_width).step_by(2){
+                    let p = &mut viewport[y as usize * list_width + x];
+                    *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+                }
+            }
+        }
+        let mut buf = vec![[255u8; 4]; width * height];
+        if header_height > 0{
+            let header = self.render_header();
+            buf = widget::draw_on_top_at_clipped(
+                (0, 0),
+                buf, (width, height),
+                &header, (self.width, HEADER_HEIGHT));
+        }
+        buf = widget::draw_on_top_at_clipped(
+            (0, header_height as isize),
+            buf, (width, height),
+            &viewport, (list_width, viewport_height));
+        let bar = match self.bar.render(SCROLL_BAR_WIDTH, viewport_height){
+            Some(v) => v,
+            None => self.bar.get_cache()
+        };
+        buf = widget::draw_on_top_at_clipped(
+            (list_width as isize, header_height as isize),
+            buf, (width, height),
+            &bar, (SCROLL_BAR_WIDTH, viewport_height));
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.bar.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        let list_width = self.get_list_width() as isize;
+        let header_height = self.get_header_height() as isize;
+        match *event{
+            WidgetEvent::MouseDown{x, y, ..} if x >= list_width && y >= header_height => {
+                self.bar_captured = true;
+                self.bar.handle_event(&event.translated(-list_width, -header_height))
+            },
+            WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} if self.bar_captured => {
+                if let WidgetEvent::MouseUp{..} = event{
+                    self.bar_captured = false;
+                }
+                self.bar.handle_event(&event.translated(-list_width, -header_height))
+            },
+            WidgetEvent::MouseDown{x, y, modifiers, ..} => {
+                if y < header_height{
+                    self.click_header(x);
+                } else if let Some(row) = self.row_at(y){
+                    self.click_row(row, modifiers.shift, modifiers.is_shortcut());
+                }
+                true
+            },
+            WidgetEvent::MouseMove{y, pressed, ..} => {
+                if !self.dragging || !pressed{
+                    return false
+                }
+                let count = self.source.get_row_count();
+                let row = match self.row_at(y){
+                    Some(r) => r.min(count.saturating_sub(1)),
+                    None => 0
+                };
+                if count > 0 && Some(row) != self.cursor{
+                    self.select_to(row, true);
+                    self.scroll_to_row(row);
+                    self.selection_changed();
+                }
+                true
+            },
+            WidgetEvent::MouseUp{..} => {
+                self.dragging = false;
+                true
+            },
+            WidgetEvent::Scroll{..} => {
+                self.update_range();
+                self.bar.handle_event(event)
+            },
+            WidgetEvent::KeyDown{key, modifiers} => {
+                let count = self.source.get_row_count();
+                if count == 0{
+                    return false
+                }
+                let page = (self.get_viewport_height() / ROW_HEIGHT).max(1);
+                let row = match (key, self.cursor){
+                    (Key::Return, Some(row)) => {
+                        self.activate(row);
+                        return true
+                    },
+                    (Key::Up, Some(row)) => row.saturating_sub(1),
+                    (Key::Up, None) => count - 1,
+                    (Key::Down, Some(row)) => (row + 1).min(count - 1),
+                    (Key::Down, None) => 0,
+                    (Key::Home, _) => 0,
+                    (Key::End, _) => count - 1,
+                    (Key::PageUp, row) => row.unwrap_or(0).saturating_sub(page),
+                    (Key::PageDown, row) => (row.unwrap_or(0) + page).min(count - 1),
+                    _ => return false
+                };
+                self.select_to(row, modifiers.shift);
+                self.scroll_to_row(row);
+                self.selection_changed();
+                true
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        match action{
+            EditAction::SelectAll if self.selection_mode == SelectionMode::Multiple => {
+                let all: Vec<usize> = (0..self.source.get_row_count()).collect();
+                self.set_selection(&all);
+                self.selection_changed();
+                true
+            },
+            EditAction::Copy if !self.selected.is_empty() => {
+                //one line per row, cells separated by tabs
+                let column_count = self.columns.len().max(1);
+                let text: Vec<String> = self.selected.iter()
+                    .map(|row| (0..column_count)
+                        .map(|column| self.source.get_text(*row, column))
+                        .collect::<Vec<String>>()
+                        .join("\t"))
+                    .collect();
+                clipboard.set_text(&text.join("\n"));
+                true
+            },
+            _ => false
+        }
+    }
+}
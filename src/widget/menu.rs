@@ -0,0 +1,416 @@
+use crate::pixel_font::PixelFont;
+use crate::widget;
+use crate::widget::event::{EditAction, WidgetEvent};
+use crate::widget::text_widget::TextWidget;
+use crate::widget::{Color, Widget, WidgetBounds};
+
+///Height of a single menu item.
+const ITEM_HEIGHT: usize = 18;
+///Height of a separator line.
+const SEPARATOR_HEIGHT: usize = 8;
+///Space left of the item labels (where check marks would go).
+const LEFT_PADDING: usize = 14;
+///Width reserved for the shortcut column.
+const SHORTCUT_WIDTH: usize = 30;
+///The command key symbol drawn in front of shortcuts, 7x7.
+const COMMAND_SYMBOL: [&str; 7] = [
+    "##...##",
+    "#.#.#.#",
+    ".#####.",
+    "..#.#..",
+    ".#####.",
+    "#.#.#.#",
+    "##...##",
+];
+///The shift key symbol drawn in front of the command key symbol of shift shortcuts, 7x7.
+const SHIFT_SYMBOL: [&str; 7] = [
+    "...#...",
+    "..#.#..",
+    ".#...#.",
+    "##...##",
+    ".#...#.",
+    ".#...#.",
+    ".#####.",
+];
+///Extra width of the shortcut column if a shortcut needs shift.
+const SHIFT_WIDTH: usize = 9;
+
+///What happens when a menu item is chosen.
+pub enum MenuAction{
+    ///Run a closure.
+    Callback(Box<dyn Fn()>),
+    ///Send an Edit menu action to the widget holding keyboard focus.
+    Edit(EditAction),
+}
+
+///A single entry in a menu.
+pub struct MenuItem{
+    pub label: String,
+    ///None for separators.
+    pub action: Option<MenuAction>,
+    pub enabled: bool,
+    ///Key that chooses the item when pressed with command.
+    pub shortcut: Option<char>,
+    ///True if shift has to be held too for the shortcut.
+    pub shortcut_shift: bool,
+}
+
+impl MenuItem{
+    ///Create an item running the given closure.
+    pub fn new(label: &str, callback: Box<dyn Fn()>) -> Self{
+        MenuItem{
+            label: String::from(label),
+            action: Some(MenuAction::Callback(callback)),
+            enabled: true,
+            shortcut: None,
+            shortcut_shift: false,
+        }
+    }
+    ///Create an item sending an edit action to the focused widget.
+    pub fn edit(label: &str, action: EditAction) -> Self{
+        MenuItem{
+            label: String::from(label),
+            action: Some(MenuAction::Edit(action)),
+            enabled: true,
+            shortcut: None,
+            shortcut_shift: false,
+        }
+    }
+    ///Create a separator line.
+    pub fn separator() -> Self{
+        MenuItem{
+            label: String::from("-"),
+            action: None,
+            enabled: false,
+            shortcut: None,
+            shortcut_shift: false,
+        }
+    }
+    ///Set the shortcut key of the item.
+    pub fn with_shortcut(mut self, shortcut: char) -> Self{
+        self.shortcut = Some(shortcut);
+        self.shortcut_shift = false;
+        self
+    }
+    ///Set the shortcut key of the item, chosen with command and shift.
+    pub fn with_shift_shortcut(mut self, shortcut: char) -> Self{
+        self.shortcut = Some(shortcut);
+        self.shortcut_shift = true;
+        self
+    }
+    ///Returns true if the item is a separator.
+    pub fn is_separator(&self) -> bool{
+        self.action.is_none()
+    }
+    fn get_height(&self) -> usize{
+        if self.is_separator() {SEPARATOR_HEIGHT} else {ITEM_HEIGHT}
+    }
+}
+
+///Create the context menu of editable text. Cut, Copy and Clear are only enabled if there is a
+/// selection; Undo is only added if the widget supports it.
+pub fn text_context_menu(has_selection: bool, can_undo: Option<bool>) -> Vec<MenuItem>{
+    let mut items = vec![];
+    if let Some(can_undo) = can_undo{
+        let mut undo = MenuItem::edit("Undo", EditAction::Undo);
+        undo.enabled = can_undo;
+        items.push(undo);
+        items.push(MenuItem::separator());
+    }
+    for (label, action) in [("Cut", EditAction::Cut), ("Copy", EditAction::Copy)]{
+        let mut item = MenuItem::edit(label, action);
+        item.enabled = has_selection;
+        items.push(item);
+    }
+    items.push(MenuItem::edit("Paste", EditAction::Paste));
+    let mut clear = MenuItem::edit("Clear", EditAction::Clear);
+    clear.enabled = has_selection;
+    items.push(clear);
+    items.push(MenuItem::separator());
+    items.push(MenuItem::edit("Select All", EditAction::SelectAll));
+    items
+}
+
+///A menu to show above all windows, such as an opened pop-up menu or a context menu. Widgets hand
+/// it to MainWidget through Widget::take_popup_request.
+pub struct PopupRequest{
+    pub menu: Menu,
+    ///Position of the menu, relative to the widget making the request. Containers translate it
+    /// as the request is passed up.
+    pub x: isize,
+    pub y: isize,
+    ///Run with the index of the chosen item, or None if the menu was dismissed.
+    pub on_close: Option<Box<dyn FnMut(Option<usize>)>>,
+}
+
+impl PopupRequest{
+    ///Create a request to show the given menu at the given position.
+    pub fn new(menu: Menu, x: isize, y: isize) -> Self{
+        PopupRequest{
+            menu,
+            x,
+            y,
+            on_close: None,
+        }
+    }
+    ///Move the position of the menu by the given amount.
+    pub fn translated(mut self, dx: isize, dy: isize) -> Self{
+        self.x += dx;
+        self.y += dy;
+        self
+    }
+}
+
+///A vertical list of menu items with a drop shadow, as shown by pull-down, pop-up and context
+/// menus. Highlights the item under the mouse; the owner decides when an item is chosen.
+pub struct Menu{
+    items: Vec<MenuItem>,
+    labels: Vec<TextWidget>,
+    shortcut_labels: Vec<Option<TextWidget>>,
+    highlighted: Option<usize>,
+    min_width: usize,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl Menu{
+    ///Create a menu with the given items.
+    pub fn new(items: Vec<MenuItem>) -> Self{
+        let mut menu = Menu{
+            items: vec![],
+            labels: vec![],
+            shortcut_labels: vec![],
+            highlighted: None,
+            min_width: 0,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        };
+        for item in items{
+            menu.add_item(item);
+        }
+        menu
+    }
+    ///Append an item to the menu.
+    pub fn add_item(&mut self, item: MenuItem){
+        let foreground = if item.enabled {Color::black()} else {Color::rgb(136, 136, 136)};
+        self.labels.push(TextWidget::new(
+            Box::new(PixelFont::default()), false, &item.label, foreground, Color::white()));
+        self.shortcut_labels.push(item.shortcut.map(|c| TextWidget::new(
+            Box::new(PixelFont::default()), false, &c.to_uppercase().to_string(), foreground, Color::white())));
+        self.items.push(item);
+        self.needs_redraw = true;
+    }
+    ///Get the items of the menu.
+    pub fn get_items(&self) -> &Vec<MenuItem>{
+        &self.items
+    }
+    ///Enable or disable the item at the given index.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool){
+        if let Some(item) = self.items.get_mut(index){
+            if item.enabled != enabled && !item.is_separator(){
+                item.enabled = enabled;
+                let foreground = if enabled {Color::black()} else {Color::rgb(136, 136, 136)};
+                self.labels[index] = TextWidget::new(
+                    Box::new(PixelFont::default()), false, &item.label, foreground, Color::white());
+                self.needs_redraw = true;
+            }
+        }
+    }
+    ///Get the index of the highlighted item.
+    pub fn get_highlighted(&self) -> Option<usize>{
+        self.highlighted
+    }
+    ///Highlight the given item (None removes the highlight).
+    pub fn set_highlighted(&mut self, index: Option<usize>){
+        if index != self.highlighted{
+            self.highlighted = index;
+            self.needs_redraw = true;
+        }
+    }
+    ///Make the menu at least the given width, e.g. to cover the pop-up menu control it belongs to.
+    pub fn set_min_width(&mut self, width: usize){
+        self.min_width = width;
+        self.needs_redraw = true;
+    }
+    ///Get the y offset of the item at the given index (relative to the menu).
+    pub fn get_item_y(&self, index: usize) -> usize{
+        1 + self.items.iter().take(index).map(|i| i.get_height()).sum::<usize>()
+    }
+    ///Move the highlight to the next (or previous) enabled item, wrapping around.
+    pub fn move_highlight(&mut self, backwards: bool){
+        let count = self.items.len();
+        if count == 0{
+            return
+        }
+        let start = match self.highlighted{
+            Some(i) => i,
+            None => if backwards {0} else {count - 1}
+        };
+        for step in 1..=count{
+            let idx = if backwards {(start + count * 2 - step) % count} else {(start + step) % count};
+            if self.items[idx].enabled{
+                self.set_highlighted(Some(idx));
+                return
+            }
+        }
+    }
+    ///Get the index of the enabled item at the given point (relative to the menu).
+    pub fn get_item_at(&self, x: isize, y: isize) -> Option<usize>{
+        let bounds = self.get_min_bounds();
+        //the last pixel row and column are the drop shadow
+        if x < 0 || y < 1 || x >= bounds.width as isize - 2{
+            return None
+        }
+        let mut item_y = 1;
+        for (idx, item) in self.items.iter().enumerate(){
+            let height = item.get_height();
+            if y < (item_y + height) as isize{
+                return if item.enabled {Some(idx)} else {None}
+            }
+            item_y += height;
+        }
+        None
+    }
+    ///Get the index of the enabled item with the given shortcut key, pressed with or without shift.
+    pub fn find_shortcut(&self, c: char, shift: bool) -> Option<usize>{
+        self.items.iter().position(|i| i.enabled && i.shortcut_shift == shift
+            && i.shortcut.map(|s| s.to_ascii_lowercase()) == Some(c.to_ascii_lowercase()))
+    }
+    ///Choose the item at the given index: callbacks are run, edit actions are returned for the
+    /// owner to send to the focused widget.
+    pub fn activate(&mut self, index: usize) -> Option<EditAction>{
+        match self.items.get(index){
+            Some(MenuItem{action: Some(action), enabled: true, ..}) => {
+                match action{
+                    MenuAction::Callback(callback) => {
+                        callback();
+                        None
+                    },
+                    MenuAction::Edit(edit) => Some(*edit)
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+impl Widget for Menu{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && self.cache.len() > 0
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let black = [0u8, 0u8, 0u8, 255u8];
+        let mut buf = vec![[255u8; 4]; width * height];
+        //outline, with a one pixel drop shadow to the right and bottom
+        let (box_width, box_height) = (width - 1, height - 1);
+        for x in 0..box_width{
+            buf[x] = black;
+            buf[(box_height - 1) * width + x] = black;
+            buf[(height - 1) * width + x + 1] = black;
+        }
+        for y in 0..box_height{
+            buf[y * width] = black;
+            buf[y * width + box_width - 1] = black;
+            buf[(y + 1) * width + width - 1] = black;
+        }
+        buf[width - 1] = [255u8; 4];
+        buf[(height - 1) * width] = [255u8; 4];
+        let mut item_y = 1;
+        for idx in 0..self.items.len(){
+            let item_height = self.items[idx].get_height();
+            if self.items[idx].is_separator(){
+                let y = item_y + item_height / 2;
+                for x in 1..box_width - 1{
+                    if x % 2 == 0{
+                        buf[y * width + x] = black;
+                    }
+                }
+                item_y += item_height;
+                continue
+            }
+            let bounds = self.labels[idx].get_min_bounds();
+            if let Some(label) = self.labels[idx].render(bounds.width, bounds.height){
+                buf = widget::draw_on_top_at_clipped(
+                    (LEFT_PADDING as isize, (item_y + 1) as isize),
+                    buf, (width, height),
+                    &label, (bounds.width, bounds.height));
+            }
+            if let Some(shortcut) = &mut self.shortcut_labels[idx]{
+                let shortcut_x = box_width - SHORTCUT_WIDTH + 2;
+                let mut symbols = vec![(shortcut_x, &COMMAND_SYMBOL)];
+                if self.items[idx].shortcut_shift{
+                    symbols.push((shortcut_x - SHIFT_WIDTH, &SHIFT_SYMBOL));
+                }
+                for (symbol_x, symbol) in symbols{
+                    for (row, line) in symbol.iter().enumerate(){
+                        for (col, c) in line.chars().enumerate(){
+                            if c == '#'{
+                                buf[(item_y + 6 + row) * width + symbol_x + col] = black;
+                            }
+                        }
+                    }
+                }
+                let bounds = shortcut.get_min_bounds();
+                if let Some(label) = shortcut.render(bounds.width, bounds.height){
+                    buf = widget::draw_on_top_at_clipped(
+                        ((shortcut_x + 10) as isize, (item_y + 1) as isize),
+                        buf, (width, height),
+                        &label, (bounds.width, bounds.height));
+                }
+            }
+            if self.highlighted == Some(idx){
+                for y in item_y..item_y + item_height{
+                    for x in 1..box_width - 1{
+                        let p = &mut buf[y * width + x];
+                        *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+                    }
+                }
+            }
+            item_y += item_height;
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        let label_width = self.labels.iter().map(|l| l.get_min_bounds().width).max().unwrap_or(0);
+        let shortcut_width = match (self.items.iter().any(|i| i.shortcut.is_some()), self.items.iter().any(|i| i.shortcut_shift)){
+            (true, true) => SHORTCUT_WIDTH + SHIFT_WIDTH,
+            (true, false) => SHORTCUT_WIDTH,
+            _ => 10
+        };
+        WidgetBounds{
+            width: (LEFT_PADDING + label_width + shortcut_width + 3).max(self.min_width),
+            height: self.items.iter().map(|i| i.get_height()).sum::<usize>() + 3,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{x, y, ..} | WidgetEvent::MouseMove{x, y, ..} => {
+                let item = self.get_item_at(x, y);
+                self.set_highlighted(item);
+                true
+            },
+            _ => false
+        }
+    }
+}
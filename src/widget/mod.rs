@@ -1,14 +1,50 @@
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use top_bar::TopBarWidget;
 use window::WindowWidget;
+use crate::clipboard::Clipboard;
 use crate::pixel_font::{FontPixel, PixelFont};
+use crate::widget::event::{EditAction, Key, WidgetEvent};
+use crate::widget::alert::Alert;
+use crate::widget::application::{AppContext, AppId, Application, RunningApp};
+use crate::widget::background::Background;
+use crate::widget::desktop::Desktop;
+use crate::widget::event::MouseButton;
+use crate::widget::menu::{Menu, MenuItem, PopupRequest};
 use crate::widget::text_widget::TextWidget;
+use crate::widget::theme::Theme;
 
+pub use color::Color;
+
+pub mod alert;
+pub mod application;
+pub mod background;
+pub mod browser;
+pub mod button;
+pub mod color;
+pub mod container;
+pub mod desktop;
+pub mod event;
+pub mod file_dialog;
+pub mod font_editor;
+pub mod image_widget;
+pub mod list_view;
+pub mod menu;
+pub mod popup_menu;
+pub mod progress_bar;
+pub mod scroll_view;
+pub mod slider;
+pub mod text_area;
+pub mod text_field;
 pub mod text_widget;
+pub mod theme;
 pub mod top_bar;
 pub mod window;
 
-//TODO: click handling
 //TODO: redraw only if necessary (WIP)
 
 ///A trait defining functions every widget must have. A widget is the basic building block of
@@ -23,41 +59,104 @@ pub trait Widget{
     fn get_min_bounds(&self) -> WidgetBounds;
     ///Gets the cache for the widget (previously drawn)
     fn get_cache(&mut self) -> Vec<[u8; 4]>;
+    ///Returns true if the widget (or one of its children) changed since it was last rendered,
+    /// meaning any cache holding it is stale.
+    fn needs_redraw(&self) -> bool{
+        false
+    }
+    ///Handles an input event (see WidgetEvent for the coordinate convention). Returns true if the
+    /// widget consumed the event.
+    fn handle_event(&mut self, _event: &WidgetEvent) -> bool{
+        false
+    }
+    ///Returns true if the widget wants keyboard focus when clicked or tabbed to.
+    fn accepts_focus(&self) -> bool{
+        false
+    }
+    ///Performs an Edit menu action, using the given clipboard for cut/copy/paste. Containers pass
+    /// it on to the child holding keyboard focus. Returns true if the action was performed.
+    fn handle_edit_action(&mut self, _action: EditAction, _clipboard: &mut Clipboard) -> bool{
+        false
+    }
+    ///Returns the rect (x, y, width, height) of the text caret or similar, if the widget has one.
+    /// Scroll views keep it visible.
+    fn get_caret_rect(&self) -> Option<(usize, usize, usize, usize)>{
+        None
+    }
+    ///Performs the default action (the default button is clicked) when Return was not consumed by
+    /// the focused widget. Containers pass it on to their children. Returns true if it was performed.
+    fn handle_default_action(&mut self) -> bool{
+        false
+    }
+    ///Performs the cancel action (the cancel button is clicked) when Escape was not consumed by
+    /// the focused widget. Containers pass it on to their children. Returns true if it was performed.
+    fn handle_cancel_action(&mut self) -> bool{
+        false
+    }
+    ///Returns the items of the context menu for the given point, shown on right-click or
+    /// control-click. Containers ask the child under the point.
+    fn get_context_menu(&mut self, _x: isize, _y: isize) -> Option<Vec<MenuItem>>{
+        None
+    }
+    ///Takes the menu the widget (or one of its children) wants shown above all windows, such as
+    /// an opened pop-up menu. MainWidget asks for it after every event.
+    fn take_popup_request(&mut self) -> Option<PopupRequest>{
+        None
+    }
+}
+
+///A shared widget, so the code that added it to a container can keep a handle to change it later
+/// (e.g. a dialog refilling its list). Must not be borrowed while it handles events or renders.
+impl<T: Widget> Widget for Rc<RefCell<T>>{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.borrow_mut().render(width, height)
+    }
+    ///The children can't be borrowed through the shared handle.
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        self.borrow().get_min_bounds()
+    }
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        self.borrow_mut().get_cache()
+    }
+    fn needs_redraw(&self) -> bool {
+        self.borrow().needs_redraw()
+    }
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        self.borrow_mut().handle_event(event)
+    }
+    fn accepts_focus(&self) -> bool {
+        self.borrow().accepts_focus()
+    }
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        self.borrow_mut().handle_edit_action(action, clipboard)
+    }
+    fn get_caret_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        self.borrow().get_caret_rect()
+    }
+    fn handle_default_action(&mut self) -> bool {
+        self.borrow_mut().handle_default_action()
+    }
+    fn handle_cancel_action(&mut self) -> bool {
+        self.borrow_mut().handle_cancel_action()
+    }
+    fn get_context_menu(&mut self, x: isize, y: isize) -> Option<Vec<MenuItem>> {
+        self.borrow_mut().get_context_menu(x, y)
+    }
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        self.borrow_mut().take_popup_request()
+    }
 }
 
 ///A structure for returning 2d rect boundaries of widgets.
 pub struct WidgetBounds{
-    width: usize,
-    height: usize
+    pub width: usize,
+    pub height: usize
 }
 
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-///A colour, in RGB. 8-bit depth per component.
-pub struct Color{
-    r: u8,
-    g:u8,
-    b:u8,
-}
-impl Color{
-    ///Returns a black Color struct
-    pub fn black()->Color{
-        Color{
-            r: 0u8,
-            g: 0u8,
-            b: 0u8
-        }
-    }
-    ///Returns a white Color struct
-    pub fn white()-> Color{
-        Color{
-            r: 255u8,
-            g: 255u8,
-            b: 255u8
-        }
-    }
-}
-
 ///Enum representing text alignment. Possible values: Right, Left, Center.
 pub enum TextAlignment{
     Right,
@@ -89,44 +188,806 @@ impl Widget for RectWidget{
     }
 }
 
+///How long the global top bar flashes when a click is refused.
+const FLASH_DURATION: Duration = Duration::from_millis(150);
+
+///A menu shown by MainWidget above all windows, with its position in screen coordinates.
+struct OpenPopup{
+    request: PopupRequest,
+    ///True once the mouse button was released without choosing an item; the menu then stays
+    /// open until the next click.
+    sticky: bool,
+    ///True once the mouse moved after the menu was opened.
+    moved: bool,
+}
+
+///Something chosen from the application menu, performed after the event.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum AppMenuChoice{
+    Activate(AppId),
+    Quit(AppId),
+}
+
+///Whose menus are shown in the global top bar.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum MenuBarOwner{
+    ///The running application at the given index.
+    App(usize),
+    ///The window at the given index, when no application is active.
+    Window(usize),
+}
+
+///Name shown in the application menu while no application is active.
+const DESKTOP_APP_NAME: &str = "Desktop";
+
 ///Master widget holding the open windows in Modesto Desktop. Should only be instantiated once.
 pub struct MainWidget{
     width: usize,
     height: usize,
     pub windows: Box<Vec<Box<WindowWidget>>>,
+    pub clipboard: Clipboard,
+    mouse_captured: bool,
+    ///Pop-up or context menu shown above everything, it takes all input while open.
+    popup: Option<OpenPopup>,
+    ///The global top bar is shown inverted until then, see beep.
+    flash_until: Option<Instant>,
+    ///Makes the sound of beep.
+    on_beep: Option<Box<dyn FnMut()>>,
+    ///Icons on the background, behind all windows.
+    desktop: Option<Desktop>,
+    ///True while the mouse button pressed on the desktop is held.
+    desktop_captured: bool,
+    ///What is drawn behind everything, rendered once for the screen size.
+    background: Background,
+    background_cache: Box<Vec<[u8; 4]>>,
+    background_cache_width: usize,
+    background_cache_height: usize,
+    ///Last rendered frame, reused by the next render so the background is copied into it instead
+    /// of into a new buffer.
+    frame: Vec<[u8; 4]>,
+    ///Look of the windows and top bar, handed down to every window when rendering.
+    theme: Rc<Theme>,
+    ///The theme as seen by the contexts of the applications.
+    app_theme: Rc<RefCell<Rc<Theme>>>,
+    ///Running applications, in the order they were launched.
+    apps: Vec<RunningApp>,
+    ///Application whose menus are shown in the global top bar.
+    active_app: Option<AppId>,
+    next_app_id: AppId,
+    ///Applications started through an AppContext, launched after the current event.
+    launched: Rc<RefCell<Vec<Box<dyn Application>>>>,
+    ///Menu at the left of the global top bar listing the running applications.
+    app_menu: Box<TopBarWidget>,
+    app_menu_choice: Rc<Cell<Option<AppMenuChoice>>>,
 }
 impl MainWidget{
     pub fn new(width: usize, height: usize)->Self{
         MainWidget{
             width, height, windows: Box::new(Vec::new()),
+            clipboard: Clipboard::new(),
+            mouse_captured: false,
+            popup: None,
+            flash_until: None,
+            on_beep: None,
+            desktop: None,
+            desktop_captured: false,
+            background: Background::default(),
+            background_cache: Box::new(vec![]),
+            background_cache_width: 0,
+            background_cache_height: 0,
+            frame: vec![],
+            theme: Rc::new(Theme::default()),
+            app_theme: Rc::new(RefCell::new(Rc::new(Theme::default()))),
+            apps: vec![],
+            active_app: None,
+            next_app_id: 0,
+            launched: Rc::new(RefCell::new(vec![])),
+            app_menu: Box::new(TopBarWidget::new(Box::new(vec![]))),
+            app_menu_choice: Rc::new(Cell::new(None)),
+        }
+    }
+    ///Get the theme windows are drawn in.
+    pub fn get_theme(&self) -> &Theme{
+        &self.theme
+    }
+    ///Switch to another theme, redrawing all windows and the top bar in it. The desktop
+    /// background is replaced by the one of the theme.
+    pub fn set_theme(&mut self, theme: Theme){
+        self.set_background(theme.desktop.clone());
+        self.theme = Rc::new(theme);
+        *self.app_theme.borrow_mut() = self.theme.clone();
+        for window in self.windows.iter_mut(){
+            window.set_theme(&self.theme);
         }
     }
-    pub fn reg_window(&mut self, window: Box<WindowWidget>){
+    ///Get what is drawn behind the desktop icons and windows.
+    pub fn get_background(&self) -> &Background{
+        &self.background
+    }
+    ///Set what is drawn behind the desktop icons and windows.
+    pub fn set_background(&mut self, background: Background){
+        self.background = background;
+        self.background_cache = Box::new(vec![]);
+    }
+    ///Start a frame for a screen of the given size with the background, only rendering it again if
+    /// the background or the size changed. The buffer of the last frame is reused.
+    fn render_background(&mut self, width: usize, height: usize) -> Vec<[u8; 4]>{
+        if self.background_cache.is_empty() || self.background_cache_width != width || self.background_cache_height != height{
+            self.background_cache = Box::new(self.background.render(width, height));
+            self.background_cache_width = width;
+            self.background_cache_height = height;
+        }
+        let mut buf = std::mem::take(&mut self.frame);
+        buf.resize(width * height, [0u8; 4]);
+        buf.copy_from_slice(&self.background_cache);
+        buf
+    }
+    ///Show the files and folders of the given directory as icons on the desktop. Fails if the
+    /// directory can't be read.
+    pub fn set_desktop_directory(&mut self, directory: &Path) -> io::Result<()>{
+        self.desktop = Some(Desktop::new(directory, self.width, self.height)?);
+        Ok(())
+    }
+    ///Get the desktop, if a desktop directory was set.
+    pub fn get_desktop_mut(&mut self) -> Option<&mut Desktop>{
+        self.desktop.as_mut()
+    }
+    ///Opens a folder double clicked on the desktop in a new window in front of all others.
+    fn open_desktop_request(&mut self){
+        let entry = match self.desktop.as_mut().and_then(|d| d.take_open_request()){
+            Some(e) => e,
+            None => return
+        };
+        if !entry.is_dir{
+            return
+        }
+        let offset = self.windows.len() * 20;
+        let (x, y) = (40 + offset % 200, top_bar::TOP_BAR_HEIGHT + 20 + offset % 200);
+        match browser::open_folder_window(&entry.path, x, y){
+            Ok(window) => self.open_window(window),
+            Err(_) => self.beep()
+        }
+    }
+    ///Sends an Edit menu action to the widget holding keyboard focus in the front window.
+    /// Returns true if a widget performed it.
+    pub fn perform_edit_action(&mut self, action: EditAction) -> bool{
+        match self.windows.first_mut(){
+            Some(w) => w.handle_edit_action(action, &mut self.clipboard),
+            None => false
+        }
+    }
+    ///Performs the edit action chosen from the global menu, if any.
+    fn perform_menu_action(&mut self){
+        let action = match self.get_menu_bar_mut(){
+            Some(top_bar) => top_bar.take_edit_action(),
+            None => None
+        };
+        if let Some(action) = action{
+            self.perform_edit_action(action);
+        }
+    }
+    ///Adds a window to the desktop. Modal windows are put in front of all others and take the
+    /// keyboard focus, other windows are put at the back.
+    pub fn reg_window(&mut self, mut window: Box<WindowWidget>){
+        window.set_theme(&self.theme);
+        if window.is_modal(){
+            if let Some(front) = self.windows.first_mut(){
+                front.handle_event(&WidgetEvent::FocusLost);
+            }
+            window.handle_event(&WidgetEvent::FocusGained);
+            self.windows.insert(0, window);
+            return
+        }
+        if self.windows.is_empty(){
+            window.handle_event(&WidgetEvent::FocusGained);
+        }
         self.windows.push(window);
     }
-    ///Renders the main widget
-    pub fn render(&mut self, width: usize, height: usize) -> Vec<[u8; 4]> {
-        if self.windows.len() > 0 {
-            let mut buf = vec![[128u8; 4]; width * height];
-            let top_bar = self.windows[0].render_top_bar(width, 30);
-            buf = draw_on_top_at(
-                0, 0,
+    ///Starts an application: it opens its windows and sets its menus in its launch hook, then
+    /// becomes the active application. Returns the id it runs under.
+    pub fn launch(&mut self, app: Box<dyn Application>) -> AppId{
+        let id = self.next_app_id;
+        self.next_app_id += 1;
+        let context = AppContext::new(self.launched.clone(), self.app_theme.clone(), self.width, self.height);
+        self.apps.push(RunningApp{
+            id,
+            app,
+            context,
+            top_bar: Box::new(TopBarWidget::new(Box::new(vec![]))),
+        });
+        let running = self.apps.last_mut().unwrap();
+        running.app.launch(&running.context);
+        self.perform_app_requests();
+        self.set_active_app(Some(id));
+        id
+    }
+    ///Get the application whose menus are shown in the global top bar, if any.
+    pub fn get_active_app(&self) -> Option<AppId>{
+        self.active_app
+    }
+    ///Get the ids and names of the running applications, in the order they were launched.
+    pub fn get_running_apps(&self) -> Vec<(AppId, &str)>{
+        self.apps.iter().map(|running| (running.id, running.app.get_name())).collect()
+    }
+    ///Makes the given application the active one, showing its menus in the global top bar.
+    /// Deactivates the one active before.
+    fn set_active_app(&mut self, id: Option<AppId>){
+        let id = id.filter(|id| self.apps.iter().any(|running| running.id == *id));
+        if id == self.active_app{
+            return
+        }
+        if let Some(old) = self.apps.iter_mut().find(|running| Some(running.id) == self.active_app){
+            old.top_bar.close_menu();
+            old.app.deactivate(&old.context);
+        }
+        self.active_app = id;
+        if let Some(new) = self.apps.iter_mut().find(|running| Some(running.id) == id){
+            new.app.activate(&new.context);
+        }
+        self.update_app_menu();
+    }
+    ///Fills the application menu: the running applications, and quitting the active one.
+    fn update_app_menu(&mut self){
+        let mut items = vec![];
+        for running in self.apps.iter(){
+            let choice = self.app_menu_choice.clone();
+            let id = running.id;
+            items.push(MenuItem::new(running.app.get_name(), Box::new(move || choice.set(Some(AppMenuChoice::Activate(id))))));
+        }
+        let active = self.apps.iter().find(|running| Some(running.id) == self.active_app);
+        if let Some(active) = active{
+            let choice = self.app_menu_choice.clone();
+            let id = active.id;
+            items.push(MenuItem::separator());
+            items.push(MenuItem::new(&format!("Quit {}", active.app.get_name()), Box::new(move || choice.set(Some(AppMenuChoice::Quit(id))))));
+        }
+        let name = active.map_or(DESKTOP_APP_NAME, |running| running.app.get_name());
+        self.app_menu = Box::new(TopBarWidget::new(Box::new(vec![Box::new(top_bar::TopBarButton::with_menu(name, items))])));
+    }
+    ///Performs what was chosen from the application menu, if anything.
+    fn perform_app_menu_choice(&mut self){
+        match self.app_menu_choice.take(){
+            Some(AppMenuChoice::Activate(id)) => {
+                //all windows of the application come to the front, keeping their order
+                let front = self.windows.first().map(|w| w.get_app());
+                if front.is_some() && front != Some(Some(id)){
+                    self.windows[0].handle_event(&WidgetEvent::FocusLost);
+                    self.windows.sort_by_key(|w| w.get_app() != Some(id));
+                    if self.windows[0].get_app() == Some(id){
+                        self.windows[0].handle_event(&WidgetEvent::FocusGained);
+                    }
+                }
+                self.set_active_app(Some(id));
+            },
+            Some(AppMenuChoice::Quit(id)) => self.quit_app(id),
+            None => {}
+        }
+    }
+    ///Quits an application, closing all of its windows.
+    fn quit_app(&mut self, id: AppId){
+        let index = match self.apps.iter().position(|running| running.id == id){
+            Some(i) => i,
+            None => return
+        };
+        let mut running = self.apps.remove(index);
+        running.app.quit(&running.context);
+        for window in self.windows.iter().filter(|w| w.get_app() == Some(id)){
+            window.get_closer().close();
+        }
+        if self.active_app == Some(id){
+            self.active_app = None;
+        }
+        self.remove_closed_windows();
+        if self.active_app.is_none(){
+            let app = self.windows.iter().find(|w| !w.is_modal()).and_then(|w| w.get_app());
+            self.set_active_app(app);
+        }
+        self.update_app_menu();
+    }
+    ///Performs what the applications asked for through their contexts: new menus, windows to
+    /// open, quitting and launching other applications.
+    fn perform_app_requests(&mut self){
+        let mut opened = vec![];
+        let mut quitting = vec![];
+        for running in self.apps.iter_mut(){
+            if let Some(mut menu) = running.context.take_menu(){
+                menu.set_theme(&self.theme);
+                running.top_bar = Box::new(menu);
+            }
+            for mut window in running.context.take_opened_windows(){
+                window.set_app(Some(running.id));
+                opened.push(window);
+            }
+            if running.context.is_quit_requested(){
+                quitting.push(running.id);
+            }
+        }
+        for window in opened{
+            self.open_window(window);
+        }
+        for id in quitting{
+            self.quit_app(id);
+        }
+        let launched: Vec<Box<dyn Application>> = self.launched.borrow_mut().drain(..).collect();
+        for app in launched{
+            self.launch(app);
+        }
+    }
+    ///Get whose menus are shown in the global top bar: those of the active application, or else
+    /// those of the front window. Modal windows have no menus, unless for_events is set the ones
+    /// behind them are shown; events go to the (empty) menus of the modal window.
+    fn get_menu_bar_owner(&self, for_events: bool) -> Option<MenuBarOwner>{
+        if for_events && self.is_modal_open(){
+            return Some(MenuBarOwner::Window(0))
+        }
+        if let Some(index) = self.apps.iter().position(|running| Some(running.id) == self.active_app){
+            return Some(MenuBarOwner::App(index))
+        }
+        if self.windows.is_empty(){
+            return None
+        }
+        Some(MenuBarOwner::Window(self.windows.iter().position(|w| !w.is_modal()).unwrap_or(0)))
+    }
+    ///Get the menus events in the global top bar go to.
+    fn get_menu_bar_mut(&mut self) -> Option<&mut TopBarWidget>{
+        match self.get_menu_bar_owner(true)?{
+            MenuBarOwner::App(i) => Some(&mut self.apps[i].top_bar),
+            MenuBarOwner::Window(i) => Some(self.windows[i].get_top_bar_mut()),
+        }
+    }
+    ///Returns true while a menu of the global top bar or the application menu is open.
+    fn is_menu_bar_open(&self) -> bool{
+        let open = match self.get_menu_bar_owner(true){
+            Some(MenuBarOwner::App(i)) => self.apps[i].top_bar.is_menu_open(),
+            Some(MenuBarOwner::Window(i)) => self.windows[i].top_bar_menu_open(),
+            None => false
+        };
+        open || self.app_menu.is_menu_open()
+    }
+    ///Get the x offset of the menus in the global top bar: they follow the application menu while
+    /// applications are running.
+    fn get_menu_bar_offset(&self) -> usize{
+        if self.apps.is_empty(){
+            return 0
+        }
+        self.app_menu.get_min_bounds().width.saturating_sub(self.theme.top_bar_x)
+    }
+    ///Adds a window opened by the user, in front of all others.
+    pub fn open_window(&mut self, window: WindowWidget){
+        self.reg_window(Box::new(window));
+        self.bring_to_front(self.windows.len() - 1);
+    }
+    ///Shows an alert as a modal window centered on screen.
+    pub fn show_alert(&mut self, alert: Alert){
+        let window = alert.into_window(self.width, self.height);
+        self.reg_window(Box::new(window));
+    }
+    ///Returns true if a modal window is open, blocking all other windows.
+    pub fn is_modal_open(&self) -> bool{
+        self.windows.first().is_some_and(|w| w.is_modal())
+    }
+    ///Removes the windows that were asked to close, giving the focus to the new front window if
+    /// the front one was closed.
+    fn remove_closed_windows(&mut self){
+        let front_closed = self.windows.first().is_some_and(|w| w.is_close_requested());
+        self.windows.retain(|w| !w.is_close_requested());
+        if front_closed{
+            self.mouse_captured = false;
+            if let Some(front) = self.windows.first_mut(){
+                front.handle_event(&WidgetEvent::FocusGained);
+                let app = front.get_app();
+                self.set_active_app(app);
+            }
+        }
+    }
+    ///Set the callback making the sound of beep, e.g. ringing the terminal bell.
+    pub fn set_on_beep(&mut self, callback: Box<dyn FnMut()>){
+        self.on_beep = Some(callback);
+    }
+    ///Signals a refused action, e.g. a click outside of a modal window: flashes the global top bar
+    /// and runs the beep callback, if one is set.
+    pub fn beep(&mut self){
+        if let Some(callback) = &mut self.on_beep{
+            callback();
+        }
+        self.flash_until = Some(Instant::now() + FLASH_DURATION);
+    }
+    ///Returns the index of the front-most window containing the given point, if any.
+    fn window_at(&self, x: isize, y: isize) -> Option<usize>{
+        self.windows.iter().position(|w| {
+            let bounds = w.get_min_bounds();
+            x >= w.x_position as isize && y >= w.y_position as isize &&
+                x < (w.x_position + bounds.width) as isize && y < (w.y_position + bounds.height) as isize
+        })
+    }
+    ///Moves the window at the given index to the front, moving keyboard focus along with it.
+    /// The application of the window becomes the active one.
+    pub fn bring_to_front(&mut self, index: usize){
+        if index >= self.windows.len(){
+            return
+        }
+        self.set_active_app(self.windows[index].get_app());
+        if index == 0{
+            return
+        }
+        self.windows[0].handle_event(&WidgetEvent::FocusLost);
+        let window = self.windows.remove(index);
+        self.windows.insert(0, window);
+        self.windows[0].handle_event(&WidgetEvent::FocusGained);
+    }
+    ///Shows a menu above all windows, moved to fit on screen. Its position is in screen
+    /// coordinates.
+    pub fn show_popup(&mut self, mut request: PopupRequest){
+        let bounds = request.menu.get_min_bounds();
+        request.x = request.x.min(self.width as isize - bounds.width as isize).max(0);
+        request.y = request.y.min(self.height as isize - bounds.height as isize).max(top_bar::TOP_BAR_HEIGHT as isize);
+        self.popup = Some(OpenPopup{
+            request,
+            sticky: false,
+            moved: false,
+        });
+    }
+    ///Returns true while a pop-up or context menu is open.
+    pub fn is_popup_open(&self) -> bool{
+        self.popup.is_some()
+    }
+    ///Closes the open pop-up or context menu, choosing the given item.
+    fn close_popup(&mut self, choice: Option<usize>){
+        let mut popup = match self.popup.take(){
+            Some(p) => p,
+            None => return
+        };
+        let action = choice.and_then(|i| popup.request.menu.activate(i));
+        if let Some(on_close) = &mut popup.request.on_close{
+            on_close(choice);
+        }
+        if let Some(action) = action{
+            self.perform_edit_action(action);
+        }
+    }
+    ///Handles an event while a pop-up or context menu is open: it is dismissed by clicking
+    /// outside of it or pressing Escape.
+    fn handle_popup_event(&mut self, event: &WidgetEvent) -> bool{
+        let popup = match &mut self.popup{
+            Some(p) => p,
+            None => return false
+        };
+        let (menu_x, menu_y) = (popup.request.x, popup.request.y);
+        let item = match event.position(){
+            Some((x, y)) => popup.request.menu.get_item_at(x - menu_x, y - menu_y),
+            None => None
+        };
+        match *event{
+            WidgetEvent::MouseDown{..} => {
+                if item.is_some(){
+                    popup.request.menu.set_highlighted(item);
+                } else {
+                    self.close_popup(None);
+                }
+            },
+            WidgetEvent::MouseMove{..} => {
+                popup.moved = true;
+                popup.request.menu.handle_event(&event.translated(-menu_x, -menu_y));
+            },
+            WidgetEvent::MouseUp{..} => {
+                if !popup.sticky && !popup.moved{
+                    //released where it was opened, keep the menu open until the next click
+                    popup.sticky = true;
+                } else if item.is_some(){
+                    self.close_popup(item);
+                } else {
+                    popup.sticky = true;
+                }
+            },
+            WidgetEvent::KeyDown{key: Key::Escape, ..} => self.close_popup(None),
+            WidgetEvent::KeyDown{key: Key::Up, ..} => popup.request.menu.move_highlight(true),
+            WidgetEvent::KeyDown{key: Key::Down, ..} => popup.request.menu.move_highlight(false),
+            WidgetEvent::KeyDown{key: Key::Return, ..} => {
+                let highlighted = popup.request.menu.get_highlighted();
+                self.close_popup(highlighted);
+            },
+            _ => {}
+        }
+        true
+    }
+    ///Opens the context menu of the widget at the given point, if it has one.
+    fn open_context_menu(&mut self, x: isize, y: isize) -> bool{
+        let idx = match self.window_at(x, y){
+            Some(i) => i,
+            None => return false
+        };
+        self.bring_to_front(idx);
+        let window = &mut self.windows[0];
+        let (window_x, window_y) = (window.x_position as isize, window.y_position as isize);
+        match window.get_context_menu(x - window_x, y - window_y){
+            Some(items) => {
+                self.show_popup(PopupRequest::new(Menu::new(items), x, y));
+                true
+            },
+            None => false
+        }
+    }
+    ///Shows the menu a widget of the front window asked for (e.g. a pop-up menu that was clicked).
+    fn open_requested_popup(&mut self, event: &WidgetEvent){
+        let window = match self.windows.first_mut(){
+            Some(w) => w,
+            None => return
+        };
+        if let Some(request) = window.take_popup_request(){
+            let request = request.translated(window.x_position as isize, window.y_position as isize);
+            //the menu tracks the mouse from now on, release it in the window
+            if let WidgetEvent::MouseDown{x, y, button, modifiers} = *event{
+                self.mouse_captured = false;
+                self.dispatch_to_front(&WidgetEvent::MouseUp{x, y, button, modifiers});
+            }
+            self.show_popup(request);
+        }
+    }
+    ///Dispatches an input event with screen coordinates. Mouse events go to the window under the
+    /// cursor (clicking a window brings it to the front), keyboard events to the front window.
+    /// Right-clicks and control-clicks open context menus. While a modal window is open, all
+    /// input goes to it and clicking elsewhere beeps. Returns true if the event was consumed.
+    pub fn handle_event(&mut self, event: WidgetEvent) -> bool{
+        let consumed = self.route_event(event);
+        if let WidgetEvent::Tick(_) = event{
+            for running in self.apps.iter_mut(){
+                running.app.tick(&running.context);
+            }
+        }
+        self.remove_closed_windows();
+        self.open_requested_windows();
+        self.open_desktop_request();
+        self.perform_app_requests();
+        consumed
+    }
+    ///Adds the windows opened by widgets inside other windows (e.g. a confirmation alert).
+    fn open_requested_windows(&mut self){
+        //they belong to the application of the window opening them
+        let opened: Vec<(Option<AppId>, WindowWidget)> = self.windows.iter_mut()
+            .flat_map(|w| {
+                let app = w.get_app();
+                w.take_opened_windows().into_iter().map(move |opened| (app, opened))
+            })
+            .collect();
+        for (app, mut window) in opened{
+            window.set_app(app);
+            self.open_window(window);
+        }
+    }
+    ///Routes an event to the open menu, the modal window or the top bar and windows.
+    fn route_event(&mut self, event: WidgetEvent) -> bool{
+        if self.popup.is_some() && !matches!(event, WidgetEvent::Tick(_)){
+            return self.handle_popup_event(&event)
+        }
+        if self.is_modal_open(){
+            match event{
+                WidgetEvent::MouseDown{x, y, ..} if self.window_at(x, y) != Some(0) => {
+                    self.beep();
+                    return true
+                },
+                WidgetEvent::Scroll{x, y, ..} if self.window_at(x, y) != Some(0) => return false,
+                _ => {}
+            }
+        }
+        if let WidgetEvent::MouseDown{x, y, button, modifiers} = event{
+            if button == MouseButton::Right || modifiers.control{
+                if self.open_context_menu(x, y){
+                    return true
+                }
+                if button == MouseButton::Right{
+                    return false
+                }
+            }
+        }
+        let consumed = self.dispatch_event(event);
+        self.open_requested_popup(&event);
+        consumed
+    }
+    ///Dispatches an event to the top bar or the windows.
+    fn dispatch_event(&mut self, event: WidgetEvent) -> bool{
+        //the global menu takes all mouse events while one of its menus is open
+        if let Some((x, y)) = event.position(){
+            let starts_tracking = matches!(event, WidgetEvent::MouseDown{..}) && y < top_bar::TOP_BAR_HEIGHT as isize;
+            let offset = self.get_menu_bar_offset();
+            if !self.apps.is_empty() && (self.app_menu.is_menu_open() || (starts_tracking && x < offset as isize)){
+                let consumed = self.app_menu.handle_event(&event);
+                self.perform_app_menu_choice();
+                return consumed
+            }
+            if let Some(top_bar) = self.get_menu_bar_mut(){
+                if top_bar.is_menu_open() || starts_tracking{
+                    let consumed = top_bar.handle_event(&event.translated(-(offset as isize), 0));
+                    self.perform_menu_action();
+                    return consumed
+                }
+            }
+        }
+        match event{
+            WidgetEvent::Tick(_) => {
+                let mut consumed = false;
+                for window in self.windows.iter_mut(){
+                    consumed |= window.handle_event(&event);
+                }
+                consumed
+            },
+            WidgetEvent::MouseDown{x, y, ..} => {
+                match (self.window_at(x, y), &mut self.desktop){
+                    (Some(idx), _) => {
+                        self.bring_to_front(idx);
+                        self.mouse_captured = true;
+                        self.dispatch_to_front(&event)
+                    },
+                    (None, Some(desktop)) => {
+                        self.desktop_captured = true;
+                        desktop.handle_event(&event)
+                    },
+                    (None, None) => false
+                }
+            },
+            WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} if self.desktop_captured => {
+                if let WidgetEvent::MouseUp{..} = event{
+                    self.desktop_captured = false;
+                }
+                match &mut self.desktop{
+                    Some(desktop) => desktop.handle_event(&event),
+                    None => false
+                }
+            },
+            WidgetEvent::MouseUp{..} => {
+                if self.mouse_captured{
+                    self.mouse_captured = false;
+                    self.dispatch_to_front(&event)
+                } else {
+                    false
+                }
+            },
+            WidgetEvent::MouseMove{..} => {
+                //the front window gets moves even when the mouse is elsewhere, to update hover states
+                self.dispatch_to_front(&event)
+            },
+            WidgetEvent::Scroll{x, y, ..} => {
+                match self.window_at(x, y){
+                    Some(idx) => {
+                        let w = &mut self.windows[idx];
+                        let translated = event.translated(-(w.x_position as isize), -(w.y_position as isize));
+                        w.handle_event(&translated)
+                    },
+                    None => false
+                }
+            },
+            WidgetEvent::KeyDown{key: Key::Escape, ..} if self.is_menu_bar_open() => {
+                self.app_menu.close_menu();
+                if let Some(top_bar) = self.get_menu_bar_mut(){
+                    top_bar.close_menu();
+                }
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Char(c), modifiers} if modifiers.is_shortcut() => {
+                let found = match self.get_menu_bar_mut(){
+                    Some(top_bar) => top_bar.trigger_shortcut(c, modifiers.shift),
+                    None => false
+                };
+                if found{
+                    self.perform_menu_action();
+                    return true
+                }
+                match EditAction::from_shortcut(c, modifiers.shift){
+                    Some(action) => self.perform_edit_action(action),
+                    None => false
+                }
+            },
+            _ => {
+                match self.windows.first_mut(){
+                    Some(w) => w.handle_event(&event),
+                    None => false
+                }
+            }
+        }
+    }
+    ///Sends an event to the front window, translated into its coordinates.
+    fn dispatch_to_front(&mut self, event: &WidgetEvent) -> bool{
+        match self.windows.first_mut(){
+            Some(w) => {
+                let translated = event.translated(-(w.x_position as isize), -(w.y_position as isize));
+                w.handle_event(&translated)
+            },
+            None => false
+        }
+    }
+    ///Renders the main widget. The frame stays borrowed until the next render, which draws into
+    /// the same buffer.
+    pub fn render(&mut self, width: usize, height: usize) -> &[[u8; 4]] {
+        let mut buf = self.render_background(width, height);
+        if let Some(desktop) = &mut self.desktop{
+            buf = desktop.render_onto(buf, width, height);
+        }
+        if self.windows.is_empty() && self.apps.is_empty(){
+            self.frame = buf;
+            return &self.frame
+        }
+        for window in self.windows.iter_mut(){
+            window.set_theme(&self.theme);
+        }
+        for running in self.apps.iter_mut(){
+            running.top_bar.set_theme(&self.theme);
+        }
+        self.app_menu.set_theme(&self.theme);
+        //the application menu is drawn across the whole bar, the menus of the active application
+        // (or front window) on top of it
+        let offset = self.get_menu_bar_offset();
+        let mut top_bar = match self.apps.is_empty(){
+            true => vec![self.theme.top_bar_background; width * top_bar::TOP_BAR_HEIGHT],
+            false => match self.app_menu.render(width, top_bar::TOP_BAR_HEIGHT){
+                Some(v) => v,
+                None => self.app_menu.get_cache()
+            }
+        };
+        let menus = match self.get_menu_bar_owner(false){
+            Some(MenuBarOwner::App(i)) => {
+                let menu_bar = &mut self.apps[i].top_bar;
+                Some(match menu_bar.render(width - offset, top_bar::TOP_BAR_HEIGHT){
+                    Some(v) => v,
+                    None => menu_bar.get_cache()
+                })
+            },
+            Some(MenuBarOwner::Window(i)) => Some(self.windows[i].render_top_bar(width - offset, top_bar::TOP_BAR_HEIGHT)),
+            None => None
+        };
+        if let Some(menus) = menus{
+            top_bar = draw_on_top_at(
+                offset, 0,
+                top_bar, width, top_bar::TOP_BAR_HEIGHT,
+                &menus, width - offset, top_bar::TOP_BAR_HEIGHT);
+        }
+        match self.flash_until{
+            Some(until) if Instant::now() < until => {
+                for p in top_bar.iter_mut(){
+                    *p = [255 - p[0], 255 - p[1], 255 - p[2], p[3]];
+                }
+            },
+            _ => self.flash_until = None
+        }
+        buf = draw_on_top_at(
+            0, 0,
+            buf, width, height,
+            &top_bar, width, top_bar::TOP_BAR_HEIGHT);
+        for window in self.windows.iter_mut().rev(){
+            let bounds = window.get_min_bounds();
+            buf= draw_on_top_at(
+                window.x_position, window.y_position,
                 buf, width, height,
-                &top_bar, width, 30);
-            for window in self.windows.iter_mut().rev(){
-                let bounds = window.get_min_bounds();
-                buf= draw_on_top_at(
-                    window.x_position, window.y_position,
-                    buf, width, height,
-                    &match window.render(bounds.width, bounds.height){
-                        Some(v) => v,
-                        None => panic!("Window.render should never return None")
-                    }, bounds.width, bounds.height);
-            }
-            buf
-        } else {
-            vec![[255u8;4];width * height]
+                &match window.render(bounds.width, bounds.height){
+                    Some(v) => v,
+                    None => panic!("Window.render should never return None")
+                }, bounds.width, bounds.height);
+        }
+        let open_menu = self.get_menu_bar_mut().and_then(|top_bar| top_bar.render_open_menu());
+        if let Some((x, y, bounds, menu)) = open_menu{
+            buf = draw_on_top_at_clipped(
+                ((x + offset) as isize, y as isize),
+                buf, (width, height),
+                &menu, (bounds.width, bounds.height));
         }
+        if let Some((x, y, bounds, menu)) = self.app_menu.render_open_menu(){
+            buf = draw_on_top_at_clipped(
+                (x as isize, y as isize),
+                buf, (width, height),
+                &menu, (bounds.width, bounds.height));
+        }
+        if let Some(popup) = &mut self.popup{
+            let menu = &mut popup.request.menu;
+            let bounds = menu.get_min_bounds();
+            let menu_buf = match menu.render(bounds.width, bounds.height){
+                Some(v) => v,
+                None => menu.get_cache()
+            };
+            buf = draw_on_top_at_clipped(
+                (popup.request.x, popup.request.y),
+                buf, (width, height),
+                &menu_buf, (bounds.width, bounds.height));
+        }
+        self.frame = buf;
+        &self.frame
     }
 }
 
@@ -155,20 +1016,178 @@ pub fn draw_on_top_at(
     base
 }
 
+///Like draw_on_top_at, but the offsets may be negative and any part of top falling outside of base
+/// is cut off instead of failing. Offsets are (x, y), sizes are (width, height).
+pub fn draw_on_top_at_clipped(
+    offset: (isize, isize),
+    mut base: Vec<[u8; 4]>,
+    base_size: (usize, usize),
+    top: &[[u8; 4]],
+    top_size: (usize, usize)
+) -> Vec<[u8; 4]>{
+    let ((x_offset, y_offset), (base_width, base_height), (top_width, top_height)) = (offset, base_size, top_size);
+    let x_start = (-x_offset).max(0) as usize;
+    let y_start = (-y_offset).max(0) as usize;
+    let x_end = (base_width as isize - x_offset).clamp(0, top_width as isize) as usize;
+    let y_end = (base_height as isize - y_offset).clamp(0, top_height as isize) as usize;
+    if x_start >= x_end{
+        return base
+    }
+    for y in y_start..y_end{
+        let base_row = (y as isize + y_offset) as usize * base_width;
+        let base_x = (x_start as isize + x_offset) as usize;
+        base[base_row + base_x..base_row + base_x + (x_end - x_start)]
+            .copy_from_slice(&top[y * top_width + x_start..y * top_width + x_end]);
+    }
+    base
+}
+
+///Like draw_on_top_at_clipped, but top is blended onto base by its alpha: fully transparent pixels
+/// (those outside the mask of an icon) leave base untouched.
+pub fn draw_masked_at(
+    offset: (isize, isize),
+    mut base: Vec<[u8; 4]>,
+    base_size: (usize, usize),
+    top: &[[u8; 4]],
+    top_size: (usize, usize)
+) -> Vec<[u8; 4]>{
+    let ((x_offset, y_offset), (base_width, base_height), (top_width, top_height)) = (offset, base_size, top_size);
+    let x_start = (-x_offset).max(0) as usize;
+    let y_start = (-y_offset).max(0) as usize;
+    let x_end = (base_width as isize - x_offset).clamp(0, top_width as isize) as usize;
+    let y_end = (base_height as isize - y_offset).clamp(0, top_height as isize) as usize;
+    for y in y_start..y_end{
+        for x in x_start..x_end{
+            let t = top[y * top_width + x];
+            let b = &mut base[(y as isize + y_offset) as usize * base_width + (x as isize + x_offset) as usize];
+            match t[3]{
+                0 => {},
+                255 => *b = t,
+                a => {
+                    let a = a as u32;
+                    for c in 0..3{
+                        b[c] = ((t[c] as u32 * a + b[c] as u32 * (255 - a)) / 255) as u8;
+                    }
+                    b[3] = b[3].max(t[3]);
+                }
+            }
+        }
+    }
+    base
+}
+
+///Cut every row of a buffer down to the given width.
+pub fn crop_width(buf: &[[u8; 4]], width: usize, new_width: usize) -> Vec<[u8; 4]>{
+    buf.chunks(width.max(1))
+        .flat_map(|row| row[..new_width.min(row.len())].iter().cloned())
+        .collect()
+}
+
+///Fill a rect of the buffer with a rounded outline of the given thickness and an optional fill.
+pub fn draw_rounded_rect(
+    buf: &mut [[u8; 4]], buf_width: usize,
+    rect: (usize, usize, usize, usize),
+    radius: usize, thickness: usize,
+    outline: [u8; 4], fill: Option<[u8; 4]>
+){
+    let (x, y, w, h) = rect;
+    let radius = radius as f32;
+    for py in 0..h{
+        for px in 0..w{
+            let (fx, fy) = (px as f32 + 0.5, py as f32 + 0.5);
+            let dx = (radius - fx).max(fx - (w as f32 - radius)).max(0.0);
+            let dy = (radius - fy).max(fy - (h as f32 - radius)).max(0.0);
+            //distance to the outside of the shape
+            let edge_distance = if dx > 0.0 && dy > 0.0{
+                radius - (dx * dx + dy * dy).sqrt()
+            } else {
+                fx.min(w as f32 - fx).min(fy).min(h as f32 - fy)
+            };
+            if edge_distance < 0.0{
+                continue
+            }
+            let idx = (y + py) * buf_width + x + px;
+            if edge_distance < thickness as f32{
+                buf[idx] = outline;
+            } else if let Some(f) = fill{
+                buf[idx] = f;
+            }
+        }
+    }
+}
+
+///Draw a dotted rectangle outline, used to show keyboard focus.
+pub fn draw_focus_rect(buf: &mut [[u8; 4]], buf_width: usize, rect: (usize, usize, usize, usize)){
+    let (x, y, w, h) = rect;
+    for px in (x..x + w).step_by(2){
+        buf[y * buf_width + px] = [0u8, 0u8, 0u8, 255u8];
+        buf[(y + h - 1) * buf_width + px] = [0u8, 0u8, 0u8, 255u8];
+    }
+    for py in (y..y + h).step_by(2){
+        buf[py * buf_width + x] = [0u8, 0u8, 0u8, 255u8];
+        buf[py * buf_width + x + w - 1] = [0u8, 0u8, 0u8, 255u8];
+    }
+}
+
 ///Convert a buffer from FontPixels to an interpolation between the given foreground and background colours.
+/// A transparent background gives pixels with the coverage as alpha, to be drawn with draw_masked_at.
 pub fn from_font_to_pixbuf(
     foreground: Color,
     background: Color,
-    buffer: &Vec<FontPixel>
+    buffer: &Vec<FontPixel>,
+    gamma: f32
 ) -> Vec<[u8;4]>{
-    buffer.iter().map(|p| {
-        [
-            ((foreground.r as i32 - background.r as i32) * p.alpha as i32 + background.r as i32) as u8,
-            ((foreground.g as i32 - background.g as i32) * p.alpha as i32 + background.g as i32) as u8,
-            ((foreground.b as i32 - background.b as i32) * p.alpha as i32 + background.b as i32) as u8,
-            255u8
-        ]
-
-        //[p.alpha, p.alpha, p.alpha, 255u8]
-    }).collect()
+    let background: [u8; 4] = background.into();
+    buffer.iter().map(|p| blend_coverage(foreground, background, p.alpha, gamma)).collect()
+}
+
+///Draw the foreground colour over a pixel, covering it by the given 8-bit coverage (scaled by the
+/// alpha of the foreground). A gamma other than 1 blends the components raised to its power, e.g.
+/// 2.2 to blend in roughly linear light, which keeps anti-aliased edges from looking too thin on
+/// dark backgrounds and too bold on light ones.
+pub fn blend_coverage(foreground: Color, destination: [u8; 4], coverage: u8, gamma: f32) -> [u8; 4]{
+    let a = coverage as u32 * foreground.a as u32 / 255;
+    if a == 0{
+        return destination
+    }
+    //the part of the destination still showing, and the alpha of both together
+    let da = destination[3] as u32 * (255 - a) / 255;
+    let out_a = a + da;
+    let fg = [foreground.r, foreground.g, foreground.b];
+    let mut out = [0u8, 0u8, 0u8, out_a as u8];
+    for c in 0..3{
+        out[c] = if gamma == 1.0{
+            ((fg[c] as u32 * a + destination[c] as u32 * da + out_a / 2) / out_a) as u8
+        } else {
+            let linear = |v: u8| (v as f32 / 255.0).powf(gamma);
+            let v = (linear(fg[c]) * a as f32 + linear(destination[c]) * da as f32) / out_a as f32;
+            (v.powf(1.0 / gamma) * 255.0).round().clamp(0.0, 255.0) as u8
+        };
+    }
+    out
+}
+
+///Like draw_masked_at, but blends the foreground colour onto base by the coverage of the given
+/// FontPixels, so text is drawn onto whatever lies below it (patterns, images).
+pub fn draw_coverage_at(
+    offset: (isize, isize),
+    mut base: Vec<[u8; 4]>,
+    base_size: (usize, usize),
+    top: &[FontPixel],
+    top_size: (usize, usize),
+    foreground: Color,
+    gamma: f32
+) -> Vec<[u8; 4]>{
+    let ((x_offset, y_offset), (base_width, base_height), (top_width, top_height)) = (offset, base_size, top_size);
+    let x_start = (-x_offset).max(0) as usize;
+    let y_start = (-y_offset).max(0) as usize;
+    let x_end = (base_width as isize - x_offset).clamp(0, top_width as isize) as usize;
+    let y_end = (base_height as isize - y_offset).clamp(0, top_height as isize) as usize;
+    for y in y_start..y_end{
+        for x in x_start..x_end{
+            let b = &mut base[(y as isize + y_offset) as usize * base_width + (x as isize + x_offset) as usize];
+            *b = blend_coverage(foreground, *b, top[y * top_width + x].alpha, gamma);
+        }
+    }
+    base
 }
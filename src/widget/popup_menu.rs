@@ -0,0 +1,248 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use crate::pixel_font::PixelFont;
+use crate::widget;
+use crate::widget::event::{Key, WidgetEvent};
+use crate::widget::menu::{Menu, MenuItem, PopupRequest};
+use crate::widget::text_widget::TextWidget;
+use crate::widget::{draw_focus_rect, Color, Widget, WidgetBounds};
+
+///Height of the pop-up menu control, including its drop shadow.
+const POPUP_HEIGHT: usize = 21;
+///Offset of the label, the same as the labels of the opened menu so they line up.
+const LABEL_X: usize = 14;
+
+///Callback run with the index of the chosen item.
+pub type ChangeCallback = Box<dyn FnMut(usize)>;
+
+///A pop-up menu control: a box showing the chosen item, which opens a menu of all items over
+/// itself when clicked.
+pub struct PopupMenu{
+    items: Vec<String>,
+    label: Box<TextWidget>,
+    width: usize,
+    ///Shared with the opened menu, which changes them when it is closed.
+    selected: Rc<Cell<usize>>,
+    open: Rc<Cell<bool>>,
+    on_change: Rc<RefCell<Option<ChangeCallback>>>,
+    ///State last drawn, to notice changes made by the opened menu.
+    drawn_selected: usize,
+    drawn_open: bool,
+    enabled: bool,
+    focused: bool,
+    ///Menu waiting to be shown by MainWidget.
+    request: Option<PopupRequest>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl PopupMenu{
+    ///Create a new pop-up menu of the given width choosing between the given items.
+    pub fn new(items: &[&str], selected: usize, width: usize) -> Self{
+        let selected = selected.min(items.len().saturating_sub(1));
+        let label = items.get(selected).cloned().unwrap_or("");
+        PopupMenu{
+            items: items.iter().map(|i| String::from(*i)).collect(),
+            label: Box::new(TextWidget::new(
+                Box::new(PixelFont::default()), false, label, Color::black(), Color::white())),
+            width: width.max(LABEL_X + 30),
+            selected: Rc::new(Cell::new(selected)),
+            open: Rc::new(Cell::new(false)),
+            on_change: Rc::new(RefCell::new(None)),
+            drawn_selected: selected,
+            drawn_open: false,
+            enabled: true,
+            focused: false,
+            request: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the callback run with the index of the newly chosen item when the user changes it.
+    pub fn set_on_change(&mut self, callback: ChangeCallback){
+        *self.on_change.borrow_mut() = Some(callback);
+    }
+    ///Get the index of the chosen item.
+    pub fn get_selected(&self) -> usize{
+        self.selected.get()
+    }
+    ///Choose the given item. Does not run the change callback.
+    pub fn set_selected(&mut self, selected: usize){
+        if selected < self.items.len(){
+            self.selected.set(selected);
+        }
+    }
+    ///Replace the items, choosing the given one. Does not run the change callback.
+    pub fn set_items(&mut self, items: &[&str], selected: usize){
+        self.items = items.iter().map(|i| String::from(*i)).collect();
+        let selected = selected.min(self.items.len().saturating_sub(1));
+        self.selected.set(selected);
+        self.label.set_text(self.items.get(selected).map_or("", |i| i.as_str()));
+        self.needs_redraw = true;
+    }
+    ///Enable or disable the control. Disabled pop-up menus are greyed out and cannot be opened.
+    pub fn set_enabled(&mut self, enabled: bool){
+        self.enabled = enabled;
+        self.needs_redraw = true;
+    }
+    ///Choose an item as the user did, running the change callback.
+    fn change_selected(&mut self, selected: usize){
+        if selected < self.items.len() && selected != self.selected.get(){
+            self.selected.set(selected);
+            if let Some(callback) = self.on_change.borrow_mut().as_mut(){
+                callback(selected);
+            }
+        }
+    }
+    ///Ask MainWidget to open the menu, with the chosen item over the control.
+    fn open_menu(&mut self){
+        if self.items.is_empty() || self.open.get(){
+            return
+        }
+        let mut menu = Menu::new(self.items.iter()
+            .map(|i| MenuItem::new(i, Box::new(|| {})))
+            .collect());
+        menu.set_min_width(self.width);
+        let selected = self.selected.get();
+        menu.set_highlighted(Some(selected));
+        let mut request = PopupRequest::new(menu, 0, 0);
+        request.y = 1 - request.menu.get_item_y(selected) as isize;
+        let (selected_cell, open, on_change) = (self.selected.clone(), self.open.clone(), self.on_change.clone());
+        request.on_close = Some(Box::new(move |choice| {
+            open.set(false);
+            if let Some(choice) = choice{
+                if choice != selected_cell.get(){
+                    selected_cell.set(choice);
+                    if let Some(callback) = on_change.borrow_mut().as_mut(){
+                        callback(choice);
+                    }
+                }
+            }
+        }));
+        self.open.set(true);
+        self.request = Some(request);
+    }
+}
+
+impl Widget for PopupMenu{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw() && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        if self.drawn_selected != self.selected.get(){
+            self.label.set_text(&self.items[self.selected.get()]);
+        }
+        let color = if self.enabled {[0u8, 0u8, 0u8, 255u8]} else {[136u8, 136u8, 136u8, 255u8]};
+        let mut buf = vec![[255u8; 4]; width * height];
+        //box with a one pixel drop shadow, like the menu it opens
+        let (box_width, box_height) = (width - 1, height - 1);
+        for x in 0..box_width{
+            buf[x] = color;
+            buf[(box_height - 1) * width + x] = color;
+            buf[(height - 1) * width + x + 1] = color;
+        }
+        for y in 0..box_height{
+            buf[y * width] = color;
+            buf[y * width + box_width - 1] = color;
+            buf[(y + 1) * width + width - 1] = color;
+        }
+        let bounds = self.label.get_min_bounds();
+        let label = match self.label.render(bounds.width, bounds.height){
+            Some(v) => v,
+            None => self.label.get_cache()
+        };
+        //leave room for the arrow
+        let visible_width = bounds.width.min(box_width - LABEL_X - 20);
+        buf = widget::draw_on_top_at_clipped(
+            (LABEL_X as isize, 2),
+            buf, (width, box_height - 1),
+            &widget::crop_width(&label, bounds.width, visible_width), (visible_width, bounds.height));
+        if !self.enabled{
+            for p in buf.iter_mut(){
+                if p[0] == 0{
+                    *p = color;
+                }
+            }
+        }
+        //down pointing triangle at the right
+        let arrow_x = box_width - 18;
+        for row in 0..6{
+            for x in arrow_x + row..arrow_x + 11 - row{
+                buf[(7 + row) * width + x] = color;
+            }
+        }
+        if self.open.get(){
+            for y in 1..box_height - 1{
+                for x in 1..box_width - 1{
+                    let p = &mut buf[y * width + x];
+                    *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+                }
+            }
+        } else if self.focused{
+            draw_focus_rect(&mut buf, width, (2, 2, box_width - 4, box_height - 4));
+        }
+        self.drawn_selected = self.selected.get();
+        self.drawn_open = self.open.get();
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: POPUP_HEIGHT,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.drawn_selected != self.selected.get() || self.drawn_open != self.open.get()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        if !self.enabled{
+            return false
+        }
+        match *event{
+            WidgetEvent::MouseDown{..} | WidgetEvent::KeyDown{key: Key::Space, ..} => {
+                self.open_menu();
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Up, ..} => {
+                self.change_selected(self.selected.get().saturating_sub(1));
+                true
+            },
+            WidgetEvent::KeyDown{key: Key::Down, ..} => {
+                self.change_selected(self.selected.get() + 1);
+                true
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.enabled
+    }
+
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        self.request.take()
+    }
+}
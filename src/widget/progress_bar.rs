@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+use crate::widget::event::WidgetEvent;
+use crate::widget::{Widget, WidgetBounds};
+
+///Height of progress bars.
+const BAR_HEIGHT: usize = 12;
+///Width of a single stripe of the indeterminate progress bar.
+const STRIPE_WIDTH: usize = 8;
+///Time between two frames of the indeterminate progress bar animation.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+///Draw the outline of a progress bar and return the buffer, ready to be filled inside.
+fn draw_bar_frame(width: usize, height: usize) -> Vec<[u8; 4]>{
+    let mut buf = vec![[255u8; 4]; width * height];
+    for x in 0..width{
+        buf[x] = [0u8, 0u8, 0u8, 255u8];
+        buf[(height - 1) * width + x] = [0u8, 0u8, 0u8, 255u8];
+    }
+    for y in 0..height{
+        buf[y * width] = [0u8, 0u8, 0u8, 255u8];
+        buf[y * width + width - 1] = [0u8, 0u8, 0u8, 255u8];
+    }
+    buf
+}
+
+///A progress bar showing how much of a long operation is done, filling up from the left.
+pub struct ProgressBar{
+    width: usize,
+    ///Fraction done, from 0 to 1.
+    value: f32,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl ProgressBar{
+    ///Create a new, empty progress bar of the given width.
+    pub fn new(width: usize) -> Self{
+        ProgressBar{
+            width: width.max(3),
+            value: 0.0,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Get the fraction done, from 0 to 1.
+    pub fn get_value(&self) -> f32{
+        self.value
+    }
+    ///Set the fraction done, clamped to 0..1. The bar is only redrawn if the filled part changed
+    /// by at least a pixel.
+    pub fn set_value(&mut self, value: f32){
+        let value = value.clamp(0.0, 1.0);
+        if self.get_filled_width(value) != self.get_filled_width(self.value){
+            self.needs_redraw = true;
+        }
+        self.value = value;
+    }
+    ///Get the width of the filled part inside the outline for the given fraction.
+    fn get_filled_width(&self, value: f32) -> usize{
+        ((self.width - 2) as f32 * value).round() as usize
+    }
+}
+
+impl Widget for ProgressBar{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf = draw_bar_frame(width, height);
+        let filled = self.get_filled_width(self.value).min(width - 2);
+        for y in 1..height - 1{
+            for x in 1..filled + 1{
+                buf[y * width + x] = [0u8, 0u8, 0u8, 255u8];
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: BAR_HEIGHT,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+}
+
+///A progress bar for operations of unknown length: diagonal stripes ("barber pole") moving along
+/// the bar while it is animating. Only the bar itself is redrawn for every frame.
+pub struct IndeterminateProgressBar{
+    width: usize,
+    animating: bool,
+    ///Offset of the stripes, advanced every frame.
+    phase: usize,
+    last_frame: Option<Instant>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl IndeterminateProgressBar{
+    ///Create a new, animating progress bar of the given width.
+    pub fn new(width: usize) -> Self{
+        IndeterminateProgressBar{
+            width: width.max(3),
+            animating: true,
+            phase: 0,
+            last_frame: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Returns true if the stripes are moving.
+    pub fn is_animating(&self) -> bool{
+        self.animating
+    }
+    ///Start or stop the animation. A stopped bar keeps showing its stripes.
+    pub fn set_animating(&mut self, animating: bool){
+        self.animating = animating;
+        self.last_frame = None;
+    }
+}
+
+impl Widget for IndeterminateProgressBar{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf = draw_bar_frame(width, height);
+        for y in 1..height - 1{
+            for x in 1..width - 1{
+                //stripes leaning to the right, moving right as the phase grows
+                let stripe = (x + STRIPE_WIDTH * 2 * height - y - self.phase) / STRIPE_WIDTH;
+                if stripe.is_multiple_of(2){
+                    buf[y * width + x] = [0u8, 0u8, 0u8, 255u8];
+                }
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: BAR_HEIGHT,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::Tick(now) if self.animating => {
+                let last = match self.last_frame{
+                    Some(l) => l,
+                    None => {
+                        self.last_frame = Some(now);
+                        return false
+                    }
+                };
+                if now.duration_since(last) < FRAME_INTERVAL{
+                    return false
+                }
+                self.last_frame = Some(now);
+                self.phase = (self.phase + 1) % (STRIPE_WIDTH * 2);
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use crate::clipboard::Clipboard;
+use crate::widget;
+use crate::widget::event::{EditAction, Key, WidgetEvent};
+use crate::widget::menu::{MenuItem, PopupRequest};
+use crate::widget::{Widget, WidgetBounds};
+
+///Width of a scroll bar, which is also the size of its arrow boxes and thumb.
+pub const SCROLL_BAR_WIDTH: usize = 16;
+
+///A vertical scroll bar with arrows at both ends and a draggable thumb. Only tracks the scroll
+/// offset, the owner reads it with get_offset and moves its content accordingly.
+pub struct ScrollBar{
+    length: usize,
+    content_size: usize,
+    viewport_size: usize,
+    offset: usize,
+    ///Amount scrolled by clicking an arrow.
+    step: usize,
+    ///Where on the thumb it was grabbed while it is being dragged.
+    drag_grab: Option<isize>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl ScrollBar{
+    ///Create a new scroll bar of the given length, scrolling by step pixels per arrow click.
+    pub fn new(length: usize, step: usize) -> Self{
+        ScrollBar{
+            length,
+            content_size: 0,
+            viewport_size: 0,
+            offset: 0,
+            step,
+            drag_grab: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the size of the scrolled content and of the visible part of it, clamping the offset.
+    pub fn set_range(&mut self, content_size: usize, viewport_size: usize){
+        if content_size != self.content_size || viewport_size != self.viewport_size{
+            self.content_size = content_size;
+            self.viewport_size = viewport_size;
+            self.needs_redraw = true;
+            self.set_offset(self.offset);
+        }
+    }
+    ///Get the largest possible offset.
+    pub fn get_max_offset(&self) -> usize{
+        self.content_size.saturating_sub(self.viewport_size)
+    }
+    ///Get the current scroll offset.
+    pub fn get_offset(&self) -> usize{
+        self.offset
+    }
+    ///Scroll to the given offset, clamped to the scrollable range.
+    pub fn set_offset(&mut self, offset: usize){
+        let offset = offset.min(self.get_max_offset());
+        if offset != self.offset{
+            self.offset = offset;
+            self.needs_redraw = true;
+        }
+    }
+    ///Scroll by the given amount of pixels (negative values scroll up).
+    pub fn scroll_by(&mut self, amount: isize){
+        self.set_offset((self.offset as isize + amount).max(0) as usize);
+    }
+    ///Scroll the least amount needed to make the given range of the content visible.
+    pub fn scroll_to_show(&mut self, start: usize, size: usize){
+        if start < self.offset{
+            self.set_offset(start);
+        } else if start + size > self.offset + self.viewport_size{
+            self.set_offset((start + size).saturating_sub(self.viewport_size));
+        }
+    }
+    ///Length of the track the thumb moves along.
+    fn get_track_length(&self) -> usize{
+        self.length.saturating_sub(SCROLL_BAR_WIDTH * 3)
+    }
+    ///Position of the top of the thumb, or None if there is nothing to scroll.
+    fn get_thumb_position(&self) -> Option<usize>{
+        let max = self.get_max_offset();
+        if max == 0 || self.get_track_length() == 0{
+            return None
+        }
+        Some(SCROLL_BAR_WIDTH + self.offset * self.get_track_length() / max)
+    }
+}
+
+impl Widget for ScrollBar{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && self.cache.len() > 0
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let black = [0u8, 0u8, 0u8, 255u8];
+        let mut buf = vec![[255u8; 4]; width * height];
+        let thumb = self.get_thumb_position();
+        //grey dither in the track if scrolling is possible, white otherwise
+        if thumb.is_some(){
+            for y in SCROLL_BAR_WIDTH..height.saturating_sub(SCROLL_BAR_WIDTH){
+                for x in 0..width{
+                    if (x + y) % 2 == 0{
+                        buf[y * width + x] = black;
+                    }
+                }
+            }
+        }
+        //outline and separators of the arrow boxes
+        for y in 0..height{
+            buf[y * width] = black;
+            buf[y * width + width - 1] = black;
+        }
+        for x in 0..width{
+            buf[x] = black;
+            buf[(height - 1) * width + x] = black;
+            if height > SCROLL_BAR_WIDTH * 2{
+                buf[(SCROLL_BAR_WIDTH - 1) * width + x] = black;
+                buf[(height - SCROLL_BAR_WIDTH) * width + x] = black;
+            }
+        }
+        //arrow outlines
+        for row in 0..6{
+            let (left, right) = (width / 2 - 1 - row, width / 2 + row);
+            let up_y = 4 + row;
+            let down_y = height.saturating_sub(5 + row);
+            for x in [left, right]{
+                buf[up_y * width + x] = black;
+                buf[down_y * width + x] = black;
+            }
+            if row == 5{
+                for x in left..=right{
+                    buf[up_y * width + x] = black;
+                    buf[down_y * width + x] = black;
+                }
+            }
+        }
+        if let Some(thumb_y) = thumb{
+            for y in thumb_y..(thumb_y + SCROLL_BAR_WIDTH).min(height){
+                for x in 0..width{
+                    let edge = y == thumb_y || y == thumb_y + SCROLL_BAR_WIDTH - 1 || x == 0 || x == width - 1;
+                    buf[y * width + x] = if edge {black} else {[255u8; 4]};
+                }
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: SCROLL_BAR_WIDTH,
+            height: self.length,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{y, ..} => {
+                let page = self.viewport_size.saturating_sub(self.step).max(self.step) as isize;
+                if y < SCROLL_BAR_WIDTH as isize{
+                    self.scroll_by(-(self.step as isize));
+                } else if y >= self.length.saturating_sub(SCROLL_BAR_WIDTH) as isize{
+                    self.scroll_by(self.step as isize);
+                } else if let Some(thumb_y) = self.get_thumb_position(){
+                    let thumb_y = thumb_y as isize;
+                    if y < thumb_y{
+                        self.scroll_by(-page);
+                    } else if y >= thumb_y + SCROLL_BAR_WIDTH as isize{
+                        self.scroll_by(page);
+                    } else {
+                        self.drag_grab = Some(y - thumb_y);
+                    }
+                }
+                true
+            },
+            WidgetEvent::MouseMove{y, pressed, ..} => {
+                if let (Some(grab), true) = (self.drag_grab, pressed){
+                    let track = self.get_track_length() as isize;
+                    if track > 0{
+                        let thumb_y = (y - grab - SCROLL_BAR_WIDTH as isize).clamp(0, track);
+                        self.set_offset(thumb_y as usize * self.get_max_offset() / track as usize);
+                    }
+                }
+                true
+            },
+            WidgetEvent::MouseUp{..} => {
+                self.drag_grab = None;
+                true
+            },
+            WidgetEvent::Scroll{delta_y, ..} => {
+                self.scroll_by(-delta_y);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+///Widget showing a vertical slice of a taller child, with a scroll bar on the right. The child is
+/// rendered at its min bounds. If the child has a caret (see Widget::get_caret_rect) it is kept
+/// visible while the child is edited.
+pub struct ScrollView{
+    width: usize,
+    height: usize,
+    child: Box<dyn Widget>,
+    bar: ScrollBar,
+    ///True while the mouse button pressed on the scroll bar is held.
+    bar_captured: bool,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl ScrollView{
+    ///Create a new scroll view of the given size around the given child.
+    pub fn new(child: Box<dyn Widget>, width: usize, height: usize) -> Self{
+        let mut view = ScrollView{
+            width,
+            height,
+            child,
+            bar: ScrollBar::new(height, 16),
+            bar_captured: false,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        };
+        view.update_range();
+        view
+    }
+    ///Get the scrolled child.
+    pub fn get_child_mut(&mut self) -> &mut Box<dyn Widget>{
+        &mut self.child
+    }
+    ///Get the current vertical scroll offset.
+    pub fn get_offset(&self) -> usize{
+        self.bar.get_offset()
+    }
+    ///Scroll to the given vertical offset.
+    pub fn set_offset(&mut self, offset: usize){
+        self.update_range();
+        self.bar.set_offset(offset);
+    }
+    fn update_range(&mut self){
+        let content = self.child.get_min_bounds().height;
+        self.bar.set_range(content, self.height);
+    }
+    ///Scroll so the child's caret (if any) is visible.
+    fn scroll_to_caret(&mut self){
+        self.update_range();
+        if let Some((_, y, _, h)) = self.child.get_caret_rect(){
+            self.bar.scroll_to_show(y, h);
+        }
+    }
+}
+
+impl Widget for ScrollView{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        self.update_range();
+        if !self.needs_redraw() && self.cache.len() > 0
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let child_bounds = self.child.get_min_bounds();
+        let child_buf = match self.child.render(child_bounds.width, child_bounds.height){
+            Some(v) => v,
+            None => self.child.get_cache()
+        };
+        let viewport_width = width.saturating_sub(SCROLL_BAR_WIDTH);
+        let viewport = widget::draw_on_top_at_clipped(
+            (0, -(self.bar.get_offset() as isize)),
+            vec![[255u8; 4]; viewport_width * height], (viewport_width, height),
+            &child_buf, (child_bounds.width, child_bounds.height));
+        let mut buf = widget::draw_on_top_at(
+            0, 0,
+            vec![[255u8; 4]; width * height], width, height,
+            &viewport, viewport_width, height);
+        let bar = match self.bar.render(SCROLL_BAR_WIDTH, height){
+            Some(v) => v,
+            None => self.bar.get_cache()
+        };
+        buf = widget::draw_on_top_at_clipped(
+            (viewport_width as isize, 0),
+            buf, (width, height),
+            &bar, (SCROLL_BAR_WIDTH, height));
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.child.needs_redraw() || self.bar.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        let bar_x = self.width.saturating_sub(SCROLL_BAR_WIDTH) as isize;
+        let offset = self.bar.get_offset() as isize;
+        match *event{
+            WidgetEvent::MouseDown{x, ..} if x >= bar_x => {
+                self.bar_captured = true;
+                self.update_range();
+                self.bar.handle_event(&event.translated(-bar_x, 0))
+            },
+            WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} if self.bar_captured => {
+                if let WidgetEvent::MouseUp{..} = event{
+                    self.bar_captured = false;
+                }
+                self.bar.handle_event(&event.translated(-bar_x, 0))
+            },
+            WidgetEvent::Scroll{..} => {
+                self.update_range();
+                self.bar.handle_event(event)
+            },
+            WidgetEvent::MouseDown{..} | WidgetEvent::MouseMove{..} | WidgetEvent::MouseUp{..} => {
+                let consumed = self.child.handle_event(&event.translated(0, offset));
+                if consumed{
+                    self.scroll_to_caret();
+                }
+                consumed
+            },
+            WidgetEvent::KeyDown{key, ..} => {
+                if self.child.handle_event(event){
+                    self.scroll_to_caret();
+                    return true
+                }
+                self.update_range();
+                match key{
+                    Key::PageUp => self.bar.scroll_by(-(self.height as isize)),
+                    Key::PageDown => self.bar.scroll_by(self.height as isize),
+                    _ => return false
+                }
+                true
+            },
+            WidgetEvent::TextInput(_) => {
+                let consumed = self.child.handle_event(event);
+                if consumed{
+                    self.scroll_to_caret();
+                }
+                consumed
+            },
+            _ => self.child.handle_event(event)
+        }
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        let performed = self.child.handle_edit_action(action, clipboard);
+        if performed{
+            self.scroll_to_caret();
+        }
+        performed
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.child.accepts_focus()
+    }
+
+    fn get_context_menu(&mut self, x: isize, y: isize) -> Option<Vec<MenuItem>> {
+        if x >= self.width.saturating_sub(SCROLL_BAR_WIDTH) as isize{
+            return None
+        }
+        self.child.get_context_menu(x, y + self.bar.get_offset() as isize)
+    }
+
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        let offset = self.bar.get_offset() as isize;
+        self.child.take_popup_request().map(|r| r.translated(0, -offset))
+    }
+}
@@ -0,0 +1,300 @@
+use crate::widget::event::{Key, WidgetEvent};
+use crate::widget::{draw_focus_rect, Widget, WidgetBounds};
+
+///Size of the slider across its track (thumb and ticks).
+const SLIDER_THICKNESS: usize = 24;
+///Size of the thumb along the track.
+const THUMB_LENGTH: usize = 11;
+///Size of the thumb across the track, including its point.
+const THUMB_HEIGHT: usize = 16;
+///Position and size of the track across the slider.
+const TRACK_OFFSET: usize = 5;
+const TRACK_HEIGHT: usize = 5;
+///Position and length of the tick marks across the slider.
+const TICK_OFFSET: usize = 18;
+const TICK_LENGTH: usize = 4;
+
+///Direction a slider moves in.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Orientation{
+    ///The minimum is on the left.
+    Horizontal,
+    ///The minimum is at the bottom.
+    Vertical,
+}
+
+///Returns true if the given pixel of the thumb (along, across) is inside its shape: a rectangle
+/// with a point towards the tick marks.
+fn thumb_contains(along: isize, across: isize) -> bool{
+    let point_start = (THUMB_HEIGHT - THUMB_LENGTH / 2) as isize;
+    if along < 0 || across < 0 || along >= THUMB_LENGTH as isize || across >= THUMB_HEIGHT as isize{
+        return false
+    }
+    let inset = (across - point_start + 1).max(0);
+    along >= inset && along < THUMB_LENGTH as isize - inset
+}
+
+///A slider choosing an integer value in a range by dragging a thumb along a track, with optional
+/// tick marks. Can be moved with the arrow keys when focused.
+pub struct Slider{
+    orientation: Orientation,
+    length: usize,
+    min: i32,
+    max: i32,
+    value: i32,
+    ///Spacing of the tick marks in values.
+    ticks: Option<i32>,
+    snap_to_ticks: bool,
+    enabled: bool,
+    focused: bool,
+    ///Offset of the mouse from the thumb center while it is dragged.
+    drag_grab: Option<isize>,
+    on_change: Option<Box<dyn FnMut(i32)>>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl Slider{
+    ///Create a new slider with the given orientation, length (in pixels), range and value.
+    pub fn new(orientation: Orientation, length: usize, min: i32, max: i32, value: i32) -> Self{
+        Slider{
+            orientation,
+            length: length.max(THUMB_LENGTH * 2),
+            min,
+            max: max.max(min),
+            value: value.clamp(min, max.max(min)),
+            ticks: None,
+            snap_to_ticks: false,
+            enabled: true,
+            focused: false,
+            drag_grab: None,
+            on_change: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set the callback run with the new value when the user moves the slider.
+    pub fn set_on_change(&mut self, callback: Box<dyn FnMut(i32)>){
+        self.on_change = Some(callback);
+    }
+    ///Get the current value.
+    pub fn get_value(&self) -> i32{
+        self.value
+    }
+    ///Set the value, clamped to the range. Does not run the change callback.
+    pub fn set_value(&mut self, value: i32){
+        let value = value.clamp(self.min, self.max);
+        if value != self.value{
+            self.value = value;
+            self.needs_redraw = true;
+        }
+    }
+    ///Change the range of the slider, clamping the value into it.
+    pub fn set_range(&mut self, min: i32, max: i32){
+        self.min = min;
+        self.max = max.max(min);
+        self.value = self.value.clamp(self.min, self.max);
+        self.needs_redraw = true;
+    }
+    ///Show tick marks every given number of values (None hides them). If snap is set, the thumb
+    /// only stops on tick marks.
+    pub fn set_ticks(&mut self, spacing: Option<i32>, snap: bool){
+        self.ticks = spacing.filter(|s| *s > 0);
+        self.snap_to_ticks = snap && self.ticks.is_some();
+        self.set_value(self.snap(self.value));
+        self.needs_redraw = true;
+    }
+    ///Enable or disable the slider. Disabled sliders are greyed out and ignore input.
+    pub fn set_enabled(&mut self, enabled: bool){
+        self.enabled = enabled;
+        self.drag_grab = None;
+        self.needs_redraw = true;
+    }
+    ///Round a value to the nearest tick mark if snapping is enabled.
+    fn snap(&self, value: i32) -> i32{
+        match (self.ticks, self.snap_to_ticks){
+            (Some(spacing), true) => {
+                let snapped = self.min + ((value - self.min) as f32 / spacing as f32).round() as i32 * spacing;
+                snapped.clamp(self.min, self.max)
+            },
+            _ => value.clamp(self.min, self.max)
+        }
+    }
+    ///Get the position along the track of the center of the thumb for the given value.
+    fn position_of(&self, value: i32) -> usize{
+        let travel = self.length - THUMB_LENGTH;
+        let range = (self.max - self.min).max(1) as usize;
+        THUMB_LENGTH / 2 + (value - self.min) as usize * travel / range
+    }
+    ///Get the value whose thumb center is closest to the given position along the track.
+    fn value_at(&self, position: isize) -> i32{
+        let travel = (self.length - THUMB_LENGTH) as f32;
+        let fraction = ((position - (THUMB_LENGTH / 2) as isize) as f32 / travel).clamp(0.0, 1.0);
+        self.snap(self.min + (fraction * (self.max - self.min) as f32).round() as i32)
+    }
+    ///Convert widget coordinates to coordinates along and across the track.
+    fn to_track(&self, x: isize, y: isize) -> (isize, isize){
+        match self.orientation{
+            Orientation::Horizontal => (x, y),
+            Orientation::Vertical => (self.length as isize - 1 - y, x)
+        }
+    }
+    ///Change the value as the user did and run the change callback.
+    fn change_value(&mut self, value: i32){
+        let old = self.value;
+        self.set_value(value);
+        if self.value != old{
+            if let Some(callback) = &mut self.on_change{
+                callback(self.value);
+            }
+        }
+    }
+    ///Get the amount the arrow keys move the slider by.
+    fn get_step(&self) -> i32{
+        self.ticks.unwrap_or(1)
+    }
+}
+
+impl Widget for Slider{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && !self.cache.is_empty()
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let color = if self.enabled {[0u8, 0u8, 0u8, 255u8]} else {[136u8, 136u8, 136u8, 255u8]};
+        //draw horizontally, turned afterwards for vertical sliders
+        let mut buf = vec![[255u8; 4]; self.length * SLIDER_THICKNESS];
+        let stride = self.length;
+        let (track_start, track_end) = (THUMB_LENGTH / 2 - 2, self.length - THUMB_LENGTH / 2 + 2);
+        for along in track_start..track_end{
+            for across in TRACK_OFFSET..TRACK_OFFSET + TRACK_HEIGHT{
+                let edge = along == track_start || along == track_end - 1
+                    || across == TRACK_OFFSET || across == TRACK_OFFSET + TRACK_HEIGHT - 1;
+                if edge{
+                    buf[across * stride + along] = color;
+                } else if (along + across) % 2 == 0{
+                    //classic 50% grey pattern inside the track
+                    buf[across * stride + along] = [136u8, 136u8, 136u8, 255u8];
+                }
+            }
+        }
+        if let Some(spacing) = self.ticks{
+            let mut value = self.min;
+            loop{
+                let along = self.position_of(value);
+                for across in TICK_OFFSET..TICK_OFFSET + TICK_LENGTH{
+                    buf[across * stride + along] = color;
+                }
+                if value >= self.max{
+                    break
+                }
+                value = (value + spacing).min(self.max);
+            }
+        }
+        let thumb_start = self.position_of(self.value) - THUMB_LENGTH / 2;
+        let fill = if self.drag_grab.is_some() {color} else {[255u8; 4]};
+        for across in 0..THUMB_HEIGHT as isize{
+            for along in 0..THUMB_LENGTH as isize{
+                if !thumb_contains(along, across){
+                    continue
+                }
+                let outline = !thumb_contains(along - 1, across) || !thumb_contains(along + 1, across)
+                    || !thumb_contains(along, across - 1) || !thumb_contains(along, across + 1);
+                buf[across as usize * stride + thumb_start + along as usize] = if outline {color} else {fill};
+            }
+        }
+        if self.focused{
+            draw_focus_rect(&mut buf, stride, (0, 0, self.length, SLIDER_THICKNESS));
+        }
+        if self.orientation == Orientation::Vertical{
+            let mut turned = vec![[255u8; 4]; self.length * SLIDER_THICKNESS];
+            for along in 0..self.length{
+                for across in 0..SLIDER_THICKNESS{
+                    turned[(self.length - 1 - along) * SLIDER_THICKNESS + across] = buf[across * stride + along];
+                }
+            }
+            buf = turned;
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        match self.orientation{
+            Orientation::Horizontal => WidgetBounds{width: self.length, height: SLIDER_THICKNESS},
+            Orientation::Vertical => WidgetBounds{width: SLIDER_THICKNESS, height: self.length}
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        if !self.enabled{
+            return false
+        }
+        match *event{
+            WidgetEvent::MouseDown{x, y, ..} => {
+                let (along, _) = self.to_track(x, y);
+                let center = self.position_of(self.value) as isize;
+                if (along - center).abs() <= (THUMB_LENGTH / 2) as isize{
+                    self.drag_grab = Some(along - center);
+                } else {
+                    //clicking the track moves the thumb there
+                    self.drag_grab = Some(0);
+                    self.change_value(self.value_at(along));
+                }
+                self.needs_redraw = true;
+                true
+            },
+            WidgetEvent::MouseMove{x, y, ..} => {
+                match self.drag_grab{
+                    Some(grab) => {
+                        let (along, _) = self.to_track(x, y);
+                        self.change_value(self.value_at(along - grab));
+                        true
+                    },
+                    None => false
+                }
+            },
+            WidgetEvent::MouseUp{..} => {
+                self.needs_redraw |= self.drag_grab.is_some();
+                self.drag_grab.take().is_some()
+            },
+            WidgetEvent::KeyDown{key, ..} => {
+                match key{
+                    Key::Left | Key::Down => self.change_value(self.value - self.get_step()),
+                    Key::Right | Key::Up => self.change_value(self.value + self.get_step()),
+                    Key::Home => self.change_value(self.min),
+                    Key::End => self.change_value(self.max),
+                    _ => return false
+                }
+                true
+            },
+            WidgetEvent::FocusGained | WidgetEvent::FocusLost => {
+                self.focused = *event == WidgetEvent::FocusGained;
+                self.needs_redraw = true;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.enabled
+    }
+}
@@ -0,0 +1,613 @@
+use std::time::{Duration, Instant};
+use crate::clipboard::Clipboard;
+use crate::pixel_font::{PixelFont, TextStyle};
+use crate::text;
+use crate::widget;
+use crate::widget::event::{EditAction, Key, WidgetEvent};
+use crate::widget::menu::{self, MenuItem};
+use crate::widget::text_field::TextCallback;
+use crate::widget::text_widget::TextWidget;
+use crate::widget::{Color, Widget, WidgetBounds};
+
+///Space between the edge of the area and the text.
+const PADDING: usize = 3;
+///Time between caret blinks.
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+///Maximum number of edits kept for undo.
+const UNDO_LIMIT: usize = 200;
+
+///A single undoable change: the characters removed at start, and the ones inserted instead.
+struct Edit{
+    start: usize,
+    removed: Vec<char>,
+    inserted: Vec<char>,
+    caret_before: usize,
+}
+
+///What a visual line looked like when it was last drawn, to skip redrawing unchanged lines.
+#[derive(Clone, Eq, PartialEq)]
+struct DrawnLine{
+    text: Vec<char>,
+    ///Highlighted pixel range.
+    selection: Option<(usize, usize)>,
+    ///x offset of the caret, if drawn on this line.
+    caret: Option<usize>,
+}
+
+///A multi-line editable text area. Text is wrapped at word boundaries to the width of the area
+/// and the area grows vertically with its content, so it is meant to be placed in a ScrollView.
+/// Only lines that changed are redrawn.
+pub struct TextArea{
+    text: Vec<char>,
+    font: Box<PixelFont>,
+    ///Used to draw the individual lines.
+    line_renderer: Box<TextWidget>,
+    width: usize,
+    min_height: usize,
+    ///Visual lines as character ranges (start inclusive, end exclusive, newlines excluded).
+    lines: Vec<(usize, usize)>,
+    caret: usize,
+    anchor: Option<usize>,
+    ///x offset the caret tries to keep when moving up and down.
+    preferred_x: Option<usize>,
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+    ///True while the user types characters, which are merged into one undo step.
+    typing: bool,
+    focused: bool,
+    selecting: bool,
+    caret_visible: bool,
+    last_blink: Instant,
+    on_change: Option<TextCallback>,
+    drawn_lines: Vec<DrawnLine>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl TextArea{
+    ///Create a new text area of the given width and minimum height containing the given text.
+    pub fn new(width: usize, min_height: usize, text: &str) -> Self{
+        let font = Box::new(PixelFont::default());
+        let mut area = TextArea{
+            text: text.chars().collect(),
+            line_renderer: Box::new(TextWidget::new(font.clone(), false, "", Color::black(), Color::white())),
+            font,
+            width,
+            min_height,
+            lines: vec![],
+            caret: 0,
+            anchor: None,
+            preferred_x: None,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            typing: false,
+            focused: false,
+            selecting: false,
+            caret_visible: true,
+            last_blink: Instant::now(),
+            on_change: None,
+            drawn_lines: vec![],
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        };
+        area.layout();
+        area
+    }
+    ///Set a callback run with the new text every time the user edits it.
+    pub fn set_on_change(&mut self, callback: TextCallback){
+        self.on_change = Some(callback);
+    }
+    ///Get the current text.
+    pub fn get_text(&self) -> String{
+        self.text.iter().collect()
+    }
+    ///Replace the text, clearing the undo history. Does not run the change callback.
+    pub fn set_text(&mut self, text: &str){
+        self.text = text.chars().collect();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.anchor = None;
+        self.layout();
+        self.set_caret(0);
+    }
+    ///Get the selected range of characters (start inclusive, end exclusive), if any.
+    pub fn get_selection(&self) -> Option<(usize, usize)>{
+        match self.anchor{
+            Some(a) if a != self.caret => Some((a.min(self.caret), a.max(self.caret))),
+            _ => None
+        }
+    }
+    ///Select the given range of characters.
+    pub fn set_selection(&mut self, start: usize, end: usize){
+        self.anchor = Some(start.min(self.text.len()));
+        self.set_caret(end);
+    }
+    ///Returns true if there is an edit to undo.
+    pub fn can_undo(&self) -> bool{
+        !self.undo_stack.is_empty()
+    }
+    ///Returns true if there is an undone edit to redo.
+    pub fn can_redo(&self) -> bool{
+        !self.redo_stack.is_empty()
+    }
+    ///Revert the last edit.
+    pub fn undo(&mut self){
+        if let Some(edit) = self.undo_stack.pop(){
+            self.text.splice(edit.start..edit.start + edit.inserted.len(), edit.removed.iter().cloned());
+            self.anchor = None;
+            self.typing = false;
+            self.layout();
+            self.set_caret(edit.caret_before);
+            self.redo_stack.push(edit);
+            self.changed();
+        }
+    }
+    ///Reapply the last undone edit.
+    pub fn redo(&mut self){
+        if let Some(edit) = self.redo_stack.pop(){
+            self.text.splice(edit.start..edit.start + edit.removed.len(), edit.inserted.iter().cloned());
+            self.anchor = None;
+            self.typing = false;
+            self.layout();
+            self.set_caret(edit.start + edit.inserted.len());
+            self.undo_stack.push(edit);
+            self.changed();
+        }
+    }
+    ///Run the change callback.
+    fn changed(&mut self){
+        let text = self.get_text();
+        if let Some(callback) = &mut self.on_change{
+            callback(&text);
+        }
+    }
+    ///Replace the given range of characters, recording the edit for undo.
+    fn replace_range(&mut self, start: usize, end: usize, insert: &[char]){
+        let removed: Vec<char> = self.text.splice(start..end, insert.iter().cloned()).collect();
+        let merge = match self.undo_stack.last(){
+            Some(last) => self.typing && removed.is_empty() && insert.len() == 1 && insert[0] != '\n'
+                && last.removed.is_empty() && last.start + last.inserted.len() == start,
+            None => false
+        };
+        if merge{
+            self.undo_stack.last_mut().unwrap().inserted.extend_from_slice(insert);
+        } else {
+            self.undo_stack.push(Edit{
+                start,
+                removed,
+                inserted: insert.to_vec(),
+                caret_before: self.caret,
+            });
+            if self.undo_stack.len() > UNDO_LIMIT{
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.typing = insert.len() == 1 && insert[0] != '\n';
+        self.anchor = None;
+        self.layout();
+        self.set_caret(start + insert.len());
+        self.changed();
+    }
+    ///Replace the selection (or insert at the caret) with the given text.
+    fn insert(&mut self, text: &[char]){
+        let (start, end) = self.get_selection().unwrap_or((self.caret, self.caret));
+        self.replace_range(start, end, text);
+    }
+    ///Delete the selection, or the grapheme cluster before (or after) the caret if nothing is
+    /// selected.
+    fn delete(&mut self, forward: bool){
+        match self.get_selection(){
+            Some((start, end)) => self.replace_range(start, end, &[]),
+            None => {
+                if forward && self.caret < self.text.len(){
+                    self.replace_range(self.caret, text::next_grapheme_boundary(&self.text, self.caret), &[]);
+                } else if !forward && self.caret > 0{
+                    self.replace_range(text::previous_grapheme_boundary(&self.text, self.caret), self.caret, &[]);
+                }
+            }
+        }
+    }
+    fn get_text_width(&self) -> usize{
+        self.width.saturating_sub(PADDING * 2 + 1).max(1)
+    }
+    ///Split the text into visual lines, wrapping at spaces where possible.
+    fn layout(&mut self){
+        let max_width = self.get_text_width();
+        let mut lines = vec![];
+        let mut start = 0;
+        let mut x = 0;
+        //index after the last space in the current line
+        let mut last_break = None;
+        //characters within a grapheme cluster are drawn over its first one
+        let boundaries = text::get_grapheme_boundaries(&self.text);
+        let mut i = 0;
+        while i < self.text.len(){
+            let c = self.text[i];
+            if c == '\n'{
+                lines.push((start, i));
+                start = i + 1;
+                x = 0;
+                last_break = None;
+                i += 1;
+                continue
+            }
+            let kerning = if i > start {self.font.get_kerning(self.text[i - 1], c)} else {0};
+            let advance = match boundaries.binary_search(&i){
+                Ok(_) => (self.font.get_char_advance(c) as isize + kerning).max(0) as usize,
+                Err(_) => 0
+            };
+            //spaces may hang over the edge
+            if x + advance > max_width && i > start && c != ' '{
+                let line_end = match last_break{
+                    Some(b) if b > start => b,
+                    _ => i
+                };
+                lines.push((start, line_end));
+                start = line_end;
+                x = *self.font.get_text_offsets(&self.text[start..i], TextStyle::PLAIN).last().unwrap();
+                last_break = None;
+                continue
+            }
+            x += advance;
+            if c == ' '{
+                last_break = Some(i + 1);
+            }
+            i += 1;
+        }
+        lines.push((start, self.text.len()));
+        self.lines = lines;
+        self.needs_redraw = true;
+    }
+    fn get_line_height(&self) -> usize{
+        self.font.get_line_height()
+    }
+    ///Index of the visual line the given character index is shown on.
+    fn get_line_of(&self, index: usize) -> usize{
+        for (i, (start, end)) in self.lines.iter().enumerate(){
+            let wraps_into_next = i + 1 < self.lines.len() && self.lines[i + 1].0 == *end;
+            if index >= *start && (index < *end || (index == *end && !wraps_into_next)){
+                return i
+            }
+        }
+        self.lines.len() - 1
+    }
+    ///x offset of the given character index within its visual line.
+    fn get_x_of(&self, index: usize) -> usize{
+        let (start, end) = self.lines[self.get_line_of(index)];
+        self.font.get_text_offsets(&self.text[start..end], TextStyle::PLAIN)[index.clamp(start, end) - start]
+    }
+    ///Character index in the given visual line closest to the given x offset.
+    fn get_index_in_line(&self, line: usize, x: isize) -> usize{
+        let (start, end) = self.lines[line];
+        let offsets = self.font.get_text_offsets(&self.text[start..end], TextStyle::PLAIN);
+        //offsets go both ways in lines with right-to-left text, so take the closest one; the
+        // characters of a cluster share an offset, the first of them wins
+        let mut best = 0;
+        for (idx, off) in offsets.iter().enumerate(){
+            if (*off as isize - x).abs() < (offsets[best] as isize - x).abs(){
+                best = idx;
+            }
+        }
+        start + best
+    }
+    ///Character index under the given point (relative to the area).
+    fn get_index_at(&self, x: isize, y: isize) -> usize{
+        let line = ((y - PADDING as isize).max(0) as usize / self.get_line_height()).min(self.lines.len() - 1);
+        self.get_index_in_line(line, x - PADDING as isize)
+    }
+    ///Move the caret and restart the blink cycle.
+    fn set_caret(&mut self, index: usize){
+        self.caret = index.min(self.text.len());
+        self.caret_visible = true;
+        self.last_blink = Instant::now();
+        self.needs_redraw = true;
+    }
+    ///Move the caret, either extending the selection or dropping it.
+    fn move_caret(&mut self, index: usize, extend: bool){
+        if extend{
+            if self.anchor.is_none(){
+                self.anchor = Some(self.caret);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.typing = false;
+        self.preferred_x = None;
+        self.set_caret(index);
+    }
+    ///Move the caret to the visual line above or below, keeping its x offset.
+    fn move_vertically(&mut self, down: bool, extend: bool){
+        let x = self.preferred_x.unwrap_or_else(|| self.get_x_of(self.caret));
+        let line = self.get_line_of(self.caret);
+        let target = if down && line + 1 < self.lines.len(){
+            self.get_index_in_line(line + 1, x as isize)
+        } else if !down && line > 0{
+            self.get_index_in_line(line - 1, x as isize)
+        } else if down{
+            self.text.len()
+        } else {
+            0
+        };
+        self.move_caret(target, extend);
+        self.preferred_x = Some(x);
+    }
+    ///Describe how the given visual line should currently look.
+    fn get_line_state(&self, line: usize) -> DrawnLine{
+        let (start, end) = self.lines[line];
+        let mut state = DrawnLine{
+            text: self.text[start..end].to_vec(),
+            selection: None,
+            caret: None,
+        };
+        if !self.focused{
+            return state
+        }
+        match self.get_selection(){
+            Some((sel_start, sel_end)) => {
+                //a selection starting at the end of a wrapped line starts on the next line
+                let wraps_into_next = line + 1 < self.lines.len() && self.lines[line + 1].0 == end;
+                let reaches_line = if wraps_into_next {sel_start < end} else {sel_start <= end};
+                if reaches_line && sel_end > start{
+                    let offsets = self.font.get_text_offsets(&self.text[start..end], TextStyle::PLAIN);
+                    let x_of = |idx: usize| -> usize{
+                        offsets[idx.clamp(start, end) - start]
+                    };
+                    //selections continuing past the end of a line highlight up to the edge, in
+                    // right-to-left text the start may be right of the end
+                    let x_end = if sel_end > end {self.get_text_width()} else {x_of(sel_end)};
+                    let (x_start, x_end) = (x_of(sel_start).min(x_end), x_of(sel_start).max(x_end));
+                    if x_end > x_start{
+                        state.selection = Some((x_start, x_end));
+                    }
+                }
+            },
+            None => {
+                if self.caret_visible && self.get_line_of(self.caret) == line{
+                    state.caret = Some(self.get_x_of(self.caret));
+                }
+            }
+        }
+        state
+    }
+    ///Draw a visual line onto the buffer, replacing what was drawn there before.
+    fn draw_line(&mut self, line: usize, state: &DrawnLine, mut buf: Vec<[u8; 4]>, width: usize, height: usize) -> Vec<[u8; 4]>{
+        let line_height = self.get_line_height();
+        let y = PADDING + line * line_height;
+        let mut line_buf = vec![[255u8; 4]; width * line_height];
+        let text: String = state.text.iter().collect();
+        self.line_renderer.set_text(&text);
+        let bounds = self.line_renderer.get_min_bounds();
+        if let Some(text_buf) = self.line_renderer.render(bounds.width, bounds.height){
+            line_buf = widget::draw_on_top_at_clipped(
+                (PADDING as isize, 0),
+                line_buf, (width, line_height),
+                &text_buf, (bounds.width, bounds.height));
+        }
+        if let Some((x_start, x_end)) = state.selection{
+            for row in 0..line_height{
+                for x in (PADDING + x_start)..(PADDING + x_end).min(width){
+                    let p = &mut line_buf[row * width + x];
+                    *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+                }
+            }
+        }
+        if let Some(x) = state.caret{
+            let x = (PADDING + x).min(width - 1);
+            for row in 0..line_height{
+                line_buf[row * width + x] = [0u8, 0u8, 0u8, 255u8];
+            }
+        }
+        buf = widget::draw_on_top_at_clipped(
+            (0, y as isize),
+            buf, (width, height),
+            &line_buf, (width, line_height));
+        buf
+    }
+}
+
+impl Widget for TextArea{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw && self.cache.len() > 0
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf;
+        if self.cache.len() > 0 && self.cache_width == width{
+            //keep the lines already drawn, the height only changes at the bottom
+            buf = *self.cache.clone();
+            buf.resize(width * height, [255u8; 4]);
+        } else {
+            buf = vec![[255u8; 4]; width * height];
+            self.drawn_lines.clear();
+        }
+        let line_height = self.get_line_height();
+        let visible_lines = height.saturating_sub(PADDING) / line_height.max(1);
+        self.drawn_lines.truncate(visible_lines);
+        for line in 0..self.lines.len().min(visible_lines){
+            let state = self.get_line_state(line);
+            if self.drawn_lines.get(line) != Some(&state){
+                buf = self.draw_line(line, &state, buf, width, height);
+                if line < self.drawn_lines.len(){
+                    self.drawn_lines[line] = state;
+                } else {
+                    self.drawn_lines.push(state);
+                }
+            }
+        }
+        //clear lines that no longer exist
+        if self.drawn_lines.len() > self.lines.len(){
+            let y_start = PADDING + self.lines.len() * line_height;
+            let y_end = (PADDING + self.drawn_lines.len() * line_height).min(height);
+            for p in buf[y_start * width..y_end * width].iter_mut(){
+                *p = [255u8; 4];
+            }
+            self.drawn_lines.truncate(self.lines.len());
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: (self.lines.len() * self.get_line_height() + PADDING * 2).max(self.min_height),
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{x, y, modifiers, ..} => {
+                let index = self.get_index_at(x, y);
+                self.move_caret(index, modifiers.shift);
+                if self.anchor.is_none(){
+                    self.anchor = Some(index);
+                }
+                self.selecting = true;
+                true
+            },
+            WidgetEvent::MouseMove{x, y, ..} => {
+                if self.selecting{
+                    let index = self.get_index_at(x, y);
+                    self.set_caret(index);
+                }
+                self.selecting
+            },
+            WidgetEvent::MouseUp{..} => {
+                self.selecting = false;
+                true
+            },
+            WidgetEvent::KeyDown{key, modifiers} => {
+                match key{
+                    Key::Left => {
+                        match self.get_selection(){
+                            Some((start, _)) if !modifiers.shift => self.move_caret(start, false),
+                            _ => self.move_caret(text::previous_grapheme_boundary(&self.text, self.caret), modifiers.shift)
+                        }
+                    },
+                    Key::Right => {
+                        match self.get_selection(){
+                            Some((_, end)) if !modifiers.shift => self.move_caret(end, false),
+                            _ => self.move_caret(text::next_grapheme_boundary(&self.text, self.caret), modifiers.shift)
+                        }
+                    },
+                    Key::Up => self.move_vertically(false, modifiers.shift),
+                    Key::Down => self.move_vertically(true, modifiers.shift),
+                    Key::Home => {
+                        let (start, _) = self.lines[self.get_line_of(self.caret)];
+                        self.move_caret(start, modifiers.shift);
+                    },
+                    Key::End => {
+                        let (_, end) = self.lines[self.get_line_of(self.caret)];
+                        self.move_caret(end, modifiers.shift);
+                    },
+                    Key::Backspace => self.delete(false),
+                    Key::Delete => self.delete(true),
+                    Key::Return => self.insert(&['\n']),
+                    _ => return false
+                }
+                true
+            },
+            WidgetEvent::TextInput(c) => {
+                if c.is_control(){
+                    return false
+                }
+                self.insert(&[c]);
+                true
+            },
+            WidgetEvent::FocusGained => {
+                self.focused = true;
+                self.caret_visible = true;
+                self.last_blink = Instant::now();
+                self.needs_redraw = true;
+                true
+            },
+            WidgetEvent::FocusLost => {
+                self.focused = false;
+                self.selecting = false;
+                self.needs_redraw = true;
+                true
+            },
+            WidgetEvent::Tick(now) => {
+                if self.focused && now.duration_since(self.last_blink) >= BLINK_INTERVAL{
+                    self.caret_visible = !self.caret_visible;
+                    self.last_blink = now;
+                    self.needs_redraw = true;
+                }
+                false
+            },
+            WidgetEvent::Scroll{..} => false,
+        }
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        let selection = self.get_selection();
+        match action{
+            EditAction::Undo => self.undo(),
+            EditAction::Redo => self.redo(),
+            EditAction::Cut | EditAction::Copy => {
+                let (start, end) = match selection{
+                    Some(s) => s,
+                    None => return false
+                };
+                let selected: String = self.text[start..end].iter().collect();
+                clipboard.set_text(&selected);
+                if action == EditAction::Cut{
+                    self.typing = false;
+                    self.replace_range(start, end, &[]);
+                }
+            },
+            EditAction::Paste => {
+                let text: Vec<char> = match clipboard.get_text(){
+                    Some(t) => t.chars().collect(),
+                    None => return false
+                };
+                self.typing = false;
+                self.insert(&text);
+            },
+            EditAction::Clear => {
+                match selection{
+                    Some((start, end)) => self.replace_range(start, end, &[]),
+                    None => return false
+                }
+            },
+            EditAction::SelectAll => self.set_selection(0, self.text.len()),
+        }
+        true
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn get_context_menu(&mut self, _x: isize, _y: isize) -> Option<Vec<MenuItem>> {
+        Some(menu::text_context_menu(self.get_selection().is_some(), Some(!self.undo_stack.is_empty())))
+    }
+
+    fn get_caret_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        let line_height = self.get_line_height();
+        Some((
+            PADDING + self.get_x_of(self.caret),
+            PADDING + self.get_line_of(self.caret) * line_height,
+            1,
+            line_height,
+        ))
+    }
+}
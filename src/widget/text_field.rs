@@ -0,0 +1,393 @@
+use std::time::{Duration, Instant};
+use crate::clipboard::Clipboard;
+use crate::pixel_font::PixelFont;
+use crate::text;
+use crate::widget;
+use crate::widget::event::{EditAction, Key, WidgetEvent};
+use crate::widget::menu::{self, MenuItem};
+use crate::widget::text_widget::TextWidget;
+use crate::widget::{Color, Widget, WidgetBounds};
+
+///Space between the border of the field and the text.
+const PADDING: usize = 3;
+///Time between caret blinks.
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+///Callback run with the text of a field, e.g. when it changes.
+pub type TextCallback = Box<dyn FnMut(&str)>;
+
+///An editable single line of text with a blinking caret and mouse/keyboard selection. Text that
+/// does not fit the width of the field scrolls horizontally to keep the caret visible.
+pub struct TextField{
+    text: Box<TextWidget>,
+    width: usize,
+    ///Caret position as a character index.
+    caret: usize,
+    ///Other end of the selection; the selection spans from here to the caret.
+    anchor: Option<usize>,
+    ///Horizontal scroll of the text in pixels.
+    scroll: usize,
+    focused: bool,
+    selecting: bool,
+    caret_visible: bool,
+    last_blink: Instant,
+    on_change: Option<TextCallback>,
+    on_submit: Option<TextCallback>,
+    cache: Box<Vec<[u8; 4]>>,
+    cache_width: usize,
+    cache_height: usize,
+    needs_redraw: bool,
+}
+
+impl TextField{
+    ///Create a new text field of the given width containing the given text.
+    pub fn new(width: usize, text: &str) -> Self{
+        TextField{
+            text: Box::new(TextWidget::new(
+                Box::new(PixelFont::default()),
+                false,
+                text,
+                Color::black(),
+                Color::white())),
+            width,
+            caret: text.chars().count(),
+            anchor: None,
+            scroll: 0,
+            focused: false,
+            selecting: false,
+            caret_visible: true,
+            last_blink: Instant::now(),
+            on_change: None,
+            on_submit: None,
+            cache: Box::new(vec![]),
+            cache_width: 0,
+            cache_height: 0,
+            needs_redraw: true,
+        }
+    }
+    ///Set a callback run with the new text every time the user edits it.
+    pub fn set_on_change(&mut self, callback: TextCallback){
+        self.on_change = Some(callback);
+    }
+    ///Set a callback run with the text when the user presses Return. Without one, Return clicks
+    /// the default button.
+    pub fn set_on_submit(&mut self, callback: TextCallback){
+        self.on_submit = Some(callback);
+    }
+    ///Get the current text.
+    pub fn get_text(&self) -> &str{
+        self.text.get_text()
+    }
+    ///Replace the text, moving the caret to its end. Does not run the change callback.
+    pub fn set_text(&mut self, text: &str){
+        self.text.set_text(text);
+        self.anchor = None;
+        self.set_caret(text.chars().count());
+    }
+    ///Get the selected range of characters (start inclusive, end exclusive), if any.
+    pub fn get_selection(&self) -> Option<(usize, usize)>{
+        match self.anchor{
+            Some(a) if a != self.caret => Some((a.min(self.caret), a.max(self.caret))),
+            _ => None
+        }
+    }
+    ///Select the given range of characters.
+    pub fn set_selection(&mut self, start: usize, end: usize){
+        let count = self.char_count();
+        self.anchor = Some(start.min(count));
+        self.set_caret(end.min(count));
+    }
+    fn char_count(&self) -> usize{
+        self.text.get_text().chars().count()
+    }
+    ///Get the start of the grapheme cluster before the caret, so the caret never ends up between
+    /// a character and its combining marks.
+    fn previous_boundary(&self) -> usize{
+        let chars: Vec<char> = self.text.get_text().chars().collect();
+        text::previous_grapheme_boundary(&chars, self.caret)
+    }
+    ///Get the end of the grapheme cluster after the caret.
+    fn next_boundary(&self) -> usize{
+        let chars: Vec<char> = self.text.get_text().chars().collect();
+        text::next_grapheme_boundary(&chars, self.caret)
+    }
+    fn inner_width(&self) -> usize{
+        self.width.saturating_sub(PADDING * 2).max(1)
+    }
+    ///Move the caret, scroll it into view and restart the blink cycle.
+    fn set_caret(&mut self, index: usize){
+        self.caret = index.min(self.char_count());
+        let offsets = self.text.get_char_offsets();
+        let caret_x = offsets[self.caret];
+        let inner = self.inner_width();
+        //right-to-left text doesn't end on the right
+        let text_width = offsets.iter().copied().max().unwrap_or(0);
+        //don't keep scrolled space past the end of the text
+        self.scroll = self.scroll.min((text_width + 1).saturating_sub(inner));
+        if caret_x < self.scroll{
+            self.scroll = caret_x;
+        } else if caret_x >= self.scroll + inner{
+            self.scroll = caret_x + 1 - inner;
+        }
+        self.caret_visible = true;
+        self.last_blink = Instant::now();
+        self.needs_redraw = true;
+    }
+    ///Move the caret, either extending the selection or dropping it.
+    fn move_caret(&mut self, index: usize, extend: bool){
+        if extend{
+            if self.anchor.is_none(){
+                self.anchor = Some(self.caret);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.set_caret(index);
+    }
+    ///Replace the given range of characters with new text and run the change callback.
+    fn replace_range(&mut self, start: usize, end: usize, insert: &str){
+        let text = self.text.get_text();
+        let byte_start = byte_index(text, start);
+        let byte_end = byte_index(text, end);
+        let new_text = format!("{}{}{}", &text[..byte_start], insert, &text[byte_end..]);
+        self.text.set_text(&new_text);
+        self.anchor = None;
+        self.set_caret(start + insert.chars().count());
+        if let Some(callback) = &mut self.on_change{
+            callback(&new_text);
+        }
+    }
+    ///Replace the selection (or insert at the caret) with the given text.
+    fn insert(&mut self, text: &str){
+        let (start, end) = self.get_selection().unwrap_or((self.caret, self.caret));
+        self.replace_range(start, end, text);
+    }
+    ///Character index under the given x coordinate (relative to the field).
+    fn index_at(&self, x: isize) -> usize{
+        self.text.get_index_at(x - PADDING as isize + self.scroll as isize)
+    }
+}
+
+///Convert a character index into a byte index of the given string.
+fn byte_index(text: &str, char_index: usize) -> usize{
+    text.char_indices().nth(char_index).map(|(b, _)| b).unwrap_or(text.len())
+}
+
+impl Widget for TextField{
+    fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
+        if !self.needs_redraw() && self.cache.len() > 0
+            && self.cache_width == width && self.cache_height == height{
+            return None
+        }
+        let mut buf = vec![[255u8; 4]; width * height];
+        for i in 0..width{
+            buf[i] = [0u8, 0u8, 0u8, 255u8];
+            buf[(height - 1) * width + i] = [0u8, 0u8, 0u8, 255u8];
+        }
+        for i in 0..height{
+            buf[i * width] = [0u8, 0u8, 0u8, 255u8];
+            buf[i * width + (width - 1)] = [0u8, 0u8, 0u8, 255u8];
+        }
+        //draw the visible part of the text into the inner area
+        let inner_width = self.inner_width().min(width.saturating_sub(PADDING * 2));
+        let line_height = self.text.get_line_height().min(height.saturating_sub(PADDING * 2));
+        let text_bounds = self.text.get_min_bounds();
+        let text_buf = match self.text.render(text_bounds.width, text_bounds.height){
+            Some(v) => v,
+            None => self.text.get_cache()
+        };
+        let mut inner = widget::draw_on_top_at_clipped(
+            (-(self.scroll as isize), 0),
+            vec![[255u8; 4]; inner_width * line_height], (inner_width, line_height),
+            &text_buf, (text_bounds.width, text_bounds.height));
+        if self.focused{
+            let offsets = self.text.get_char_offsets();
+            match self.get_selection(){
+                Some((start, end)) => {
+                    //highlight the selection by inverting it
+                    let x_start = offsets[start].saturating_sub(self.scroll).min(inner_width);
+                    let x_end = offsets[end].saturating_sub(self.scroll).min(inner_width);
+                    for y in 0..line_height{
+                        for x in x_start..x_end{
+                            let p = &mut inner[y * inner_width + x];
+                            *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+                        }
+                    }
+                },
+                None => {
+                    if self.caret_visible && inner_width > 0{
+                        let x = offsets[self.caret].saturating_sub(self.scroll).min(inner_width - 1);
+                        for y in 0..line_height{
+                            inner[y * inner_width + x] = [0u8, 0u8, 0u8, 255u8];
+                        }
+                    }
+                }
+            }
+        }
+        buf = widget::draw_on_top_at(
+            PADDING, PADDING,
+            buf, width, height,
+            &inner, inner_width, line_height);
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
+        self.needs_redraw = false;
+        Some(buf)
+    }
+    fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
+        None
+    }
+    fn get_min_bounds(&self) -> WidgetBounds {
+        WidgetBounds{
+            width: self.width,
+            height: self.text.get_line_height() + PADDING * 2,
+        }
+    }
+
+    fn get_cache(&mut self) -> Vec<[u8; 4]> {
+        *self.cache.clone()
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.text.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{x, modifiers, ..} => {
+                let index = self.index_at(x);
+                self.move_caret(index, modifiers.shift);
+                if self.anchor.is_none(){
+                    self.anchor = Some(index);
+                }
+                self.selecting = true;
+                true
+            },
+            WidgetEvent::MouseMove{x, ..} => {
+                if self.selecting{
+                    let index = self.index_at(x);
+                    self.set_caret(index);
+                }
+                self.selecting
+            },
+            WidgetEvent::MouseUp{..} => {
+                self.selecting = false;
+                true
+            },
+            WidgetEvent::KeyDown{key, modifiers} => {
+                match key{
+                    Key::Left => {
+                        match self.get_selection(){
+                            Some((start, _)) if !modifiers.shift => self.move_caret(start, false),
+                            _ => self.move_caret(self.previous_boundary(), modifiers.shift)
+                        }
+                    },
+                    Key::Right => {
+                        match self.get_selection(){
+                            Some((_, end)) if !modifiers.shift => self.move_caret(end, false),
+                            _ => self.move_caret(self.next_boundary(), modifiers.shift)
+                        }
+                    },
+                    Key::Home | Key::Up => self.move_caret(0, modifiers.shift),
+                    Key::End | Key::Down => self.move_caret(self.char_count(), modifiers.shift),
+                    Key::Backspace => {
+                        match self.get_selection(){
+                            Some((start, end)) => self.replace_range(start, end, ""),
+                            None if self.caret > 0 => self.replace_range(self.previous_boundary(), self.caret, ""),
+                            None => {}
+                        }
+                    },
+                    Key::Delete => {
+                        match self.get_selection(){
+                            Some((start, end)) => self.replace_range(start, end, ""),
+                            None if self.caret < self.char_count() => self.replace_range(self.caret, self.next_boundary(), ""),
+                            None => {}
+                        }
+                    },
+                    Key::Return => {
+                        //without a submit callback, Return is left to the default button
+                        let text = String::from(self.text.get_text());
+                        match &mut self.on_submit{
+                            Some(callback) => callback(&text),
+                            None => return false
+                        }
+                    },
+                    _ => return false
+                }
+                true
+            },
+            WidgetEvent::TextInput(c) => {
+                if c.is_control(){
+                    return false
+                }
+                self.insert(&c.to_string());
+                true
+            },
+            WidgetEvent::FocusGained => {
+                self.focused = true;
+                self.caret_visible = true;
+                self.last_blink = Instant::now();
+                self.needs_redraw = true;
+                true
+            },
+            WidgetEvent::FocusLost => {
+                self.focused = false;
+                self.selecting = false;
+                self.needs_redraw = true;
+                true
+            },
+            WidgetEvent::Tick(now) => {
+                if self.focused && now.duration_since(self.last_blink) >= BLINK_INTERVAL{
+                    self.caret_visible = !self.caret_visible;
+                    self.last_blink = now;
+                    self.needs_redraw = true;
+                }
+                false
+            },
+            WidgetEvent::Scroll{..} => false,
+        }
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        let selection = self.get_selection();
+        match action{
+            EditAction::Cut | EditAction::Copy => {
+                let (start, end) = match selection{
+                    Some(s) => s,
+                    None => return false
+                };
+                let text = self.text.get_text();
+                let selected = &text[byte_index(text, start)..byte_index(text, end)];
+                clipboard.set_text(selected);
+                if action == EditAction::Cut{
+                    self.replace_range(start, end, "");
+                }
+            },
+            EditAction::Paste => {
+                //the field only holds a single line
+                let text = match clipboard.get_text(){
+                    Some(t) => t.replace('\n', " "),
+                    None => return false
+                };
+                self.insert(&text);
+            },
+            EditAction::Clear => {
+                match selection{
+                    Some((start, end)) => self.replace_range(start, end, ""),
+                    None => return false
+                }
+            },
+            EditAction::SelectAll => self.set_selection(0, self.char_count()),
+            EditAction::Undo | EditAction::Redo => return false
+        }
+        true
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn get_context_menu(&mut self, _x: isize, _y: isize) -> Option<Vec<MenuItem>> {
+        Some(menu::text_context_menu(self.get_selection().is_some(), None))
+    }
+}
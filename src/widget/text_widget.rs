@@ -1,4 +1,4 @@
-use crate::pixel_font::PixelFont;
+use crate::pixel_font::{FontPixel, PixelFont, TextStyle};
 use crate::widget;
 use crate::widget::{Color, Widget, WidgetBounds};
 
@@ -6,56 +6,37 @@ use crate::widget::{Color, Widget, WidgetBounds};
 pub struct TextWidget{
     font: Box<PixelFont>,
     wrap: bool,
-    text: &'static str,
+    text: String,
     foreground_col: Color,
     background_col: Color,
+    ///Gamma the glyphs are blended with, 1 blends the colour components as they are.
+    gamma: f32,
+    style: TextStyle,
+    needs_redraw: bool,
 }
 
 impl Widget for TextWidget{
     fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
-        let mut buf = vec![[255u8; 4]; width * height];
-        let mut xoff : usize = 0;
-        for char in self.text.chars(){
-            let mut w = 8;
-            let mut h = 16;
-            let mut right_off = 1;
-            buf = widget::draw_on_top_at(
-                xoff, 0,
-                buf, width, height,
-                &match self.font.charset.get(&char){
-                    Some(c) =>
-                        {
-                            w = c.width; h = c.height; right_off = c.right_offset;
-                            widget::from_font_to_pixbuf(self.foreground_col, self.background_col, &*c.pixels)
-                        },
-                    None =>
-                        vec![[0u8;4]; w*h]
-                }, w, h);
-            xoff += (w + right_off) as usize;
-        }
-        Some(buf)
+        self.needs_redraw = false;
+        let buf = vec![self.background_col.into(); width * height];
+        Some(self.draw_onto(buf, width, height, 0, 0))
     }
     fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
         return None
     }
     fn get_min_bounds(&self) -> WidgetBounds {
+        let chars: Vec<char> = self.text.chars().collect();
+        let placed = self.font.layout(&chars, self.style);
         let mut w = 0;
-        let mut h = 0;
-        for c in self.text.chars(){
-            w += match self.font.charset.get(&c){
-                Some(c) => {
-                    if c.height > h{h = c.height};
-                    c.width + c.right_offset
-                },
-                None => {
-                    if 16 > h {h = 16}
-                    9
-                }
-            }
+        for p in placed.iter(){
+            //characters may reach past their advance, e.g. slanted ones
+            let glyph = self.font.get_styled_glyph(p.glyph, self.style);
+            let right = p.x + glyph.left_bearing + glyph.width as isize;
+            w = w.max(right.max(p.x + p.advance as isize).max(0) as usize);
         }
         WidgetBounds{
-            width:w,
-            height: h,
+            width: w,
+            height: if chars.is_empty() {0} else {self.get_line_height()},
         }
     }
 
@@ -65,18 +46,101 @@ impl Widget for TextWidget{
             None => panic!("TextWidget should never return None for render, since it is a bottom level widget"),
         }
     }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
 }
 
 impl TextWidget{
     pub fn new(
         font: Box<PixelFont>,
         wrap: bool,
-        text: &'static str,
+        text: &str,
         foreground_col: Color,
         background_col: Color,
     ) -> Self{
         TextWidget{
-            font, wrap, text, foreground_col, background_col,
+            font, wrap, text: String::from(text), foreground_col, background_col,
+            gamma: 1.0,
+            style: TextStyle::PLAIN,
+            needs_redraw: true,
+        }
+    }
+    ///Set the gamma glyph edges are blended with, see widget::blend_coverage.
+    pub fn set_gamma(&mut self, gamma: f32){
+        if self.gamma != gamma{
+            self.gamma = gamma;
+            self.needs_redraw = true;
+        }
+    }
+    pub fn get_style(&self) -> TextStyle{
+        self.style
+    }
+    ///Draw the text in the given style, synthesized from the plain characters of the font.
+    pub fn set_style(&mut self, style: TextStyle){
+        if self.style != style{
+            self.style = style;
+            self.needs_redraw = true;
+        }
+    }
+    ///Draw the text onto the given buffer at the given offset, blending the glyphs with the pixels
+    /// already there instead of the background colour. Parts outside of the buffer are cut off.
+    pub fn draw_onto(&self, mut base: Vec<[u8; 4]>, base_width: usize, base_height: usize, x: isize, y: isize) -> Vec<[u8; 4]>{
+        let chars: Vec<char> = self.text.chars().collect();
+        let placed = self.font.layout(&chars, self.style);
+        //characters stand on the baseline, the ring of outlined ones reaches a row above the line
+        let baseline = y + self.font.get_ascent() as isize + if self.style.get_ring_size() > 0 {1} else {0};
+        for p in placed.iter(){
+            let glyph = self.font.get_styled_glyph(p.glyph, self.style);
+            base = widget::draw_coverage_at(
+                (x + p.x + glyph.left_bearing, baseline - glyph.ascent),
+                base, (base_width, base_height),
+                &glyph.pixels, (glyph.width, glyph.height),
+                self.foreground_col, self.gamma);
+        }
+        if self.style.contains(TextStyle::UNDERLINE) && !placed.is_empty(){
+            let underline_y = y + self.font.get_styled_line_height(self.style) as isize - 1;
+            let length = placed.iter().map(|p| (p.x + p.advance as isize).max(0) as usize).max().unwrap_or(0);
+            base = widget::draw_coverage_at(
+                (x, underline_y),
+                base, (base_width, base_height),
+                &vec![FontPixel::new(255u8); length], (length, 1),
+                self.foreground_col, self.gamma);
+        }
+        base
+    }
+    ///Get the text currently displayed by the widget.
+    pub fn get_text(&self) -> &str{
+        &self.text
+    }
+    ///Get the x offset of every character boundary in the text, from 0 up to the full width of
+    /// the text (one entry more than there are characters).
+    pub fn get_char_offsets(&self) -> Vec<usize>{
+        let chars: Vec<char> = self.text.chars().collect();
+        self.font.get_text_offsets(&chars, self.style)
+    }
+    ///Get the index of the character boundary closest to the given x offset.
+    pub fn get_index_at(&self, x: isize) -> usize{
+        let offsets = self.get_char_offsets();
+        let mut best = 0;
+        for (idx, off) in offsets.iter().enumerate(){
+            if (*off as isize - x).abs() < (offsets[best] as isize - x).abs(){
+                best = idx;
+            }
+        }
+        best
+    }
+    ///Get the height of a line of text in the widget's font and style.
+    pub fn get_line_height(&self) -> usize{
+        self.font.get_styled_line_height(self.style)
+    }
+    ///Replace the displayed text. Marks the widget as needing a redraw, so caching parents
+    /// will re-render it.
+    pub fn set_text(&mut self, text: &str){
+        if self.text != text{
+            self.text = String::from(text);
+            self.needs_redraw = true;
         }
     }
 }
@@ -0,0 +1,463 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use crate::font_registry::{FontRegistry, SizeMatch};
+use crate::image::{invalid_data, Image};
+use crate::pixel_font::PixelFont;
+use crate::widget::background::{Background, ImagePlacement};
+use crate::widget::Color;
+use crate::widget::top_bar::TOP_BAR_HEIGHT;
+
+///How often a ThemeWatcher looks at the modification times of its files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+///Family the font of a theme file is registered under, next to the built-in fallback.
+const THEME_FAMILY: &str = "Theme";
+
+///The look of windows, title bars and the top bar: colours, the font, metrics and the desktop
+/// pattern. MainWidget hands its theme down to every window when rendering, so switching themes
+/// at runtime redraws everything in the new look.
+///
+///The top bar keeps its height (TOP_BAR_HEIGHT) in every theme, since windows and menus are
+/// placed below it.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Theme{
+    pub name: String,
+    ///Font of window titles and the top bar.
+    pub font: PixelFont,
+    ///Colour of text, outlines and window frames.
+    pub foreground: [u8; 4],
+    pub title_bar_background: [u8; 4],
+    ///Colour of the stripes either side of the window title, and of the line below each stripe
+    /// for a raised look, if any.
+    pub title_bar_stripes: [u8; 4],
+    pub title_bar_stripe_highlight: Option<[u8; 4]>,
+    pub close_box_background: [u8; 4],
+    pub close_box_pressed: [u8; 4],
+    pub top_bar_background: [u8; 4],
+    ///Desktop background MainWidget switches to along with the theme.
+    pub desktop: Background,
+    ///Height of window title bars, from the top of the window to the body.
+    pub title_bar_height: usize,
+    ///Offset of the title from the top of the window.
+    pub title_y: usize,
+    ///Number of stripes either side of the title and the distance between two of them.
+    pub title_bar_stripe_count: usize,
+    pub title_bar_stripe_spacing: usize,
+    ///Position and size of the close box in the title bar.
+    pub close_box_x: usize,
+    pub close_box_y: usize,
+    pub close_box_size: usize,
+    ///Offset of the first top bar button from the left edge, its labels from the top, and the
+    /// space between two buttons.
+    pub top_bar_x: usize,
+    pub top_bar_y: usize,
+    pub top_bar_spacing: usize,
+}
+
+impl Default for Theme{
+    fn default() -> Self{
+        Theme::system7()
+    }
+}
+
+impl Theme{
+    ///Black and white, with striped title bars and the grey desktop pattern of System 7.
+    pub fn system7() -> Self{
+        Theme{
+            name: String::from("System 7"),
+            font: PixelFont::default(),
+            foreground: [0u8, 0u8, 0u8, 255u8],
+            title_bar_background: [255u8; 4],
+            title_bar_stripes: [0u8, 0u8, 0u8, 255u8],
+            title_bar_stripe_highlight: None,
+            close_box_background: [255u8; 4],
+            close_box_pressed: [0u8, 0u8, 0u8, 255u8],
+            top_bar_background: [255u8; 4],
+            desktop: Background::classic_grey(),
+            title_bar_height: 30,
+            title_y: 7,
+            title_bar_stripe_count: 6,
+            title_bar_stripe_spacing: 3,
+            close_box_x: 4,
+            close_box_y: 7,
+            close_box_size: 16,
+            top_bar_x: 10,
+            top_bar_y: 7,
+            top_bar_spacing: 20,
+        }
+    }
+    ///The light grey, raised look of Mac OS 8, with lower title bars and a blue-grey desktop.
+    pub fn platinum() -> Self{
+        Theme{
+            name: String::from("Platinum"),
+            font: PixelFont::default(),
+            foreground: [0u8, 0u8, 0u8, 255u8],
+            title_bar_background: [221u8, 221u8, 221u8, 255u8],
+            title_bar_stripes: [136u8, 136u8, 136u8, 255u8],
+            title_bar_stripe_highlight: Some([255u8; 4]),
+            close_box_background: [221u8, 221u8, 221u8, 255u8],
+            close_box_pressed: [102u8, 102u8, 102u8, 255u8],
+            top_bar_background: [221u8, 221u8, 221u8, 255u8],
+            desktop: Background::Solid([102u8, 102u8, 153u8, 255u8]),
+            title_bar_height: 22,
+            title_y: 3,
+            title_bar_stripe_count: 5,
+            title_bar_stripe_spacing: 3,
+            close_box_x: 8,
+            close_box_y: 5,
+            close_box_size: 12,
+            top_bar_x: 12,
+            top_bar_y: 7,
+            top_bar_spacing: 24,
+        }
+    }
+    ///Get the themes built into the desktop.
+    pub fn get_builtin_themes() -> Vec<Theme>{
+        vec![Theme::system7(), Theme::platinum()]
+    }
+    ///Load a theme file, see Theme::parse for its format. Paths in the file are relative to the
+    /// directory holding it.
+    pub fn load(path: &Path) -> io::Result<Theme>{
+        Theme::load_with_files(path).map(|(theme, _)| theme)
+    }
+    ///Parse a theme from lines of `key = value`. Empty lines and lines starting with `#` are
+    /// ignored, every key may be set once. Keys left out keep the value of the `base` theme, the
+    /// name of a built-in theme, or of the default theme.
+    ///
+    ///- `palette.NAME` defines a colour other colours can refer to by NAME. Colours are written in
+    ///  hex (`#rgb`, `#rrggbb` or `#rrggbbaa`) or by their HTML name (e.g. `navy`).
+    ///- `name`, and `font`, the path of a BDF font. Characters it lacks are taken from the
+    ///  built-in font.
+    ///- The colours `foreground`, `title_bar_background`, `title_bar_stripes`,
+    ///  `title_bar_stripe_highlight` (may be `none`), `close_box_background`, `close_box_pressed`
+    ///  and `top_bar_background`.
+    ///- `desktop`, one of `solid COLOUR`, `pattern ROWS FOREGROUND BACKGROUND` with the 8 pattern
+    ///  rows as 16 hex digits (e.g. `aa55aa55aa55aa55`), or `image PLACEMENT FILL PATH` with
+    ///  PLACEMENT one of `tiled`, `centered` and `stretched`.
+    ///- The metrics `title_bar_height`, `title_y`, `title_bar_stripe_count`,
+    ///  `title_bar_stripe_spacing`, `close_box_x`, `close_box_y`, `close_box_size`, `top_bar_x`,
+    ///  `top_bar_y` and `top_bar_spacing`, in pixels.
+    ///
+    ///Errors name the line of the offending entry.
+    pub fn parse(text: &str, directory: &Path) -> io::Result<Theme>{
+        Theme::parse_with_files(text, directory).map(|(theme, _)| theme)
+    }
+    ///Load a theme file, also returning the font and image files it refers to.
+    fn load_with_files(path: &Path) -> io::Result<(Theme, Vec<PathBuf>)>{
+        let text = fs::read_to_string(path)?;
+        let directory = path.parent().unwrap_or(Path::new("."));
+        Theme::parse_with_files(&text, directory)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+    ///Parse a theme, also returning the font and image files it refers to.
+    fn parse_with_files(text: &str, directory: &Path) -> io::Result<(Theme, Vec<PathBuf>)>{
+        let error = |line: usize, message: String| invalid_data(&format!("line {}: {}", line + 1, message));
+        //split the lines into entries first, so the base theme and palette can be set up before the rest
+        let mut entries: BTreeMap<&str, (usize, &str)> = BTreeMap::new();
+        let mut order = vec![];
+        for (idx, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue
+            }
+            let (key, value) = match line.split_once('='){
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error(idx, format!("expected 'key = value', got '{}'", line)))
+            };
+            if key.is_empty(){
+                return Err(error(idx, String::from("missing key before '='")))
+            }
+            if let Some((first, _)) = entries.insert(key, (idx, value)){
+                return Err(error(idx, format!("'{}' is already set on line {}", key, first + 1)))
+            }
+            order.push(key);
+        }
+        let mut theme = match entries.get("base"){
+            Some((idx, name)) => Theme::get_builtin_themes().into_iter()
+                .find(|t| t.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| error(*idx, format!("unknown base theme '{}', expected one of {}", name,
+                    Theme::get_builtin_themes().iter().map(|t| format!("'{}'", t.name)).collect::<Vec<_>>().join(", "))))?,
+            None => Theme::default()
+        };
+        let mut palette = BTreeMap::new();
+        for key in order.iter(){
+            if let Some(name) = key.strip_prefix("palette."){
+                let (idx, value) = entries[key];
+                palette.insert(name, parse_color(value, &palette).map_err(|e| error(idx, e))?);
+            }
+        }
+        let mut files = vec![];
+        for key in order.iter(){
+            let (idx, value) = entries[key];
+            let color = || parse_color(value, &palette).map_err(|e| error(idx, format!("{}: {}", key, e)));
+            let number = || value.parse::<usize>()
+                .map_err(|_| error(idx, format!("{}: expected a whole number of pixels, got '{}'", key, value)));
+            match *key{
+                "base" => {},
+                _ if key.starts_with("palette.") => {},
+                "name" => theme.name = String::from(value),
+                "font" => {
+                    let path = directory.join(value);
+                    let font = PixelFont::load(&path)
+                        .map_err(|e| error(idx, format!("can't load font {}: {}", path.display(), e)))?;
+                    theme.font = with_builtin_fallback(font);
+                    files.push(path);
+                },
+                "foreground" => theme.foreground = color()?,
+                "title_bar_background" => theme.title_bar_background = color()?,
+                "title_bar_stripes" => theme.title_bar_stripes = color()?,
+                "title_bar_stripe_highlight" => theme.title_bar_stripe_highlight =
+                    if value == "none" {None} else {Some(color()?)},
+                "close_box_background" => theme.close_box_background = color()?,
+                "close_box_pressed" => theme.close_box_pressed = color()?,
+                "top_bar_background" => theme.top_bar_background = color()?,
+                "desktop" => {
+                    let (desktop, file) = parse_desktop(value, directory, &palette)
+                        .map_err(|e| error(idx, format!("desktop: {}", e)))?;
+                    theme.desktop = desktop;
+                    files.extend(file);
+                },
+                "title_bar_height" => theme.title_bar_height = number()?,
+                "title_y" => theme.title_y = number()?,
+                "title_bar_stripe_count" => theme.title_bar_stripe_count = number()?,
+                "title_bar_stripe_spacing" => theme.title_bar_stripe_spacing = number()?,
+                "close_box_x" => theme.close_box_x = number()?,
+                "close_box_y" => theme.close_box_y = number()?,
+                "close_box_size" => theme.close_box_size = number()?,
+                "top_bar_x" => theme.top_bar_x = number()?,
+                "top_bar_y" => theme.top_bar_y = number()?,
+                "top_bar_spacing" => theme.top_bar_spacing = number()?,
+                _ => return Err(error(idx, format!("unknown key '{}'", key)))
+            }
+        }
+        theme.check_metrics().map_err(|e| invalid_data(&e))?;
+        Ok((theme, files))
+    }
+    ///Make sure the title bar and top bar contents fit, since they are drawn without clipping.
+    fn check_metrics(&self) -> Result<(), String>{
+        let line_height = self.font.get_line_height();
+        let stripes_height = self.title_bar_stripe_count.saturating_sub(1) * self.title_bar_stripe_spacing
+            + if self.title_bar_stripe_highlight.is_some() {2} else {1};
+        if self.title_bar_height < 3 || (self.title_bar_stripe_count > 0 && stripes_height > self.title_bar_height){
+            return Err(format!("the stripes ({} pixels high) don't fit in the title bar ({} pixels high)",
+                stripes_height, self.title_bar_height))
+        }
+        if self.title_y + line_height > self.title_bar_height{
+            return Err(format!("the title at title_y {} ({} pixels high) doesn't fit in the title bar ({} pixels high)",
+                self.title_y, line_height, self.title_bar_height))
+        }
+        if self.close_box_size < 3 || self.close_box_y + self.close_box_size > self.title_bar_height{
+            return Err(format!("the close box at close_box_y {} (size {}) doesn't fit in the title bar ({} pixels high)",
+                self.close_box_y, self.close_box_size, self.title_bar_height))
+        }
+        if self.top_bar_y + line_height >= TOP_BAR_HEIGHT{
+            return Err(format!("the top bar labels at top_bar_y {} ({} pixels high) don't fit in the top bar ({} pixels high)",
+                self.top_bar_y, line_height, TOP_BAR_HEIGHT))
+        }
+        Ok(())
+    }
+}
+
+///Parse a colour written in hex, as a colour name or as the name of a palette entry.
+fn parse_color(value: &str, palette: &BTreeMap<&str, [u8; 4]>) -> Result<[u8; 4], String>{
+    match palette.get(value){
+        Some(color) => Ok(*color),
+        None => value.parse::<Color>().map(|c| c.into())
+    }
+}
+
+///Parse a desktop background, also returning the image file it shows, if any.
+fn parse_desktop(value: &str, directory: &Path, palette: &BTreeMap<&str, [u8; 4]>) -> Result<(Background, Option<PathBuf>), String>{
+    let words: Vec<&str> = value.split_whitespace().collect();
+    match words.as_slice(){
+        ["solid", color] => Ok((Background::Solid(parse_color(color, palette)?), None)),
+        ["pattern", rows, foreground, background] => {
+            if rows.len() != 16 || !rows.chars().all(|c| c.is_ascii_hexdigit()){
+                return Err(format!("expected 8 pattern rows as 16 hex digits, got '{}'", rows))
+            }
+            let mut pattern = [0u8; 8];
+            for (i, row) in pattern.iter_mut().enumerate(){
+                *row = u8::from_str_radix(&rows[i * 2..i * 2 + 2], 16).unwrap();
+            }
+            Ok((Background::Pattern{
+                rows: pattern,
+                foreground: parse_color(foreground, palette)?,
+                background: parse_color(background, palette)?,
+            }, None))
+        },
+        ["image", placement, fill, ..] if words.len() > 3 => {
+            let placement = match *placement{
+                "tiled" => ImagePlacement::Tiled,
+                "centered" => ImagePlacement::Centered,
+                "stretched" => ImagePlacement::Stretched,
+                _ => return Err(format!("expected tiled, centered or stretched, got '{}'", placement))
+            };
+            let fill = parse_color(fill, palette)?;
+            //the path is the rest of the line, so it may contain spaces
+            let path = directory.join(skip_words(value, 3));
+            let image = Image::load(&path).map_err(|e| format!("can't load image {}: {}", path.display(), e))?;
+            Ok((Background::Image{image, placement, fill}, Some(path)))
+        },
+        _ => Err(format!("expected 'solid COLOUR', 'pattern ROWS FOREGROUND BACKGROUND' or 'image PLACEMENT FILL PATH', got '{}'", value))
+    }
+}
+
+///Get what follows the given number of words (split like split_whitespace does), trimmed.
+fn skip_words(text: &str, count: usize) -> &str{
+    let mut rest = text.trim_start();
+    for _ in 0..count{
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[end..].trim_start();
+    }
+    rest.trim_end()
+}
+
+///Fill in the characters a font lacks from the built-in font, matched to its size.
+fn with_builtin_fallback(font: PixelFont) -> PixelFont{
+    let size = font.get_size();
+    let mut fonts = FontRegistry::default();
+    fonts.add_font(THEME_FAMILY, font);
+    fonts.get_font(THEME_FAMILY, size, SizeMatch::Nearest).expect("the theme font was just added")
+}
+
+///Reloads a theme file when it, or a font or image it refers to, changes on disk.
+pub struct ThemeWatcher{
+    path: PathBuf,
+    ///Files the theme was loaded from with their modification times at the time.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_check: Option<Instant>,
+}
+
+impl ThemeWatcher{
+    ///Create a watcher for the given theme file. The first call to poll loads it.
+    pub fn new(path: &Path) -> Self{
+        ThemeWatcher{
+            path: path.to_path_buf(),
+            files: vec![],
+            last_check: None,
+        }
+    }
+    ///Get the path of the theme file.
+    pub fn get_path(&self) -> &Path{
+        &self.path
+    }
+    ///Load the theme again if any of its files changed since the last load. Returns None if nothing
+    /// changed, or if the files were checked less than half a second ago. A theme that fails to
+    /// load is not retried until its files change again.
+    pub fn poll(&mut self) -> Option<io::Result<Theme>>{
+        let now = Instant::now();
+        if self.last_check.is_some_and(|last| now.duration_since(last) < WATCH_INTERVAL){
+            return None
+        }
+        self.last_check = Some(now);
+        let changed = self.files.is_empty() || self.files.iter().any(|(path, modified)| get_modified(path) != *modified);
+        if !changed{
+            return None
+        }
+        let result = Theme::load_with_files(&self.path);
+        let mut files = vec![self.path.clone()];
+        if let Ok((_, referenced)) = &result{
+            files.extend(referenced.iter().cloned());
+        }
+        self.files = files.into_iter().map(|path| {
+            let modified = get_modified(&path);
+            (path, modified)
+        }).collect();
+        Some(result.map(|(theme, _)| theme))
+    }
+}
+
+///Get the modification time of a file, None if it can't be read.
+fn get_modified(path: &Path) -> Option<SystemTime>{
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn parse(text: &str) -> io::Result<Theme>{
+        Theme::parse(text, Path::new("."))
+    }
+
+    ///Get the error of parsing the text, which must fail.
+    fn parse_error(text: &str) -> String{
+        match parse(text){
+            Ok(_) => panic!("parsed without an error: {:?}", text),
+            Err(e) => e.to_string()
+        }
+    }
+
+    #[test]
+    fn entries_override_the_base_theme(){
+        let theme = parse("
+            # a darker Platinum
+            base = platinum
+            name = Night
+            palette.ink = #102030
+            palette.paper = navy
+            foreground = ink
+            title_bar_background = paper
+            title_bar_stripe_highlight = none
+            desktop = pattern aa55aa55aa55aa55 ink #fff
+            title_bar_height = 24
+            close_box_size = 10
+        ").unwrap();
+        assert_eq!(theme.name, "Night");
+        assert_eq!(theme.foreground, [16u8, 32u8, 48u8, 255u8]);
+        assert_eq!(theme.title_bar_background, [0u8, 0u8, 128u8, 255u8]);
+        assert_eq!(theme.title_bar_stripe_highlight, None);
+        assert_eq!(theme.desktop, Background::Pattern{
+            rows: [0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55, 0xaa, 0x55],
+            foreground: [16u8, 32u8, 48u8, 255u8],
+            background: [255u8; 4],
+        });
+        assert_eq!((theme.title_bar_height, theme.close_box_size), (24, 10));
+        //keys left out keep the values of the base theme
+        let platinum = Theme::platinum();
+        assert_eq!((theme.title_y, theme.close_box_x), (platinum.title_y, platinum.close_box_x));
+        assert_eq!(theme.close_box_background, platinum.close_box_background);
+        assert!(parse("").unwrap() == Theme::default());
+    }
+
+    #[test]
+    fn errors_name_the_line(){
+        let error = parse_error("name = One\n\nname = Two");
+        assert!(error.starts_with("line 3:") && error.contains("already set on line 1"), "{}", error);
+        let error = parse_error("foreground = black\ncolour = red");
+        assert!(error.starts_with("line 2:") && error.contains("unknown key 'colour'"), "{}", error);
+        let error = parse_error("# colours\nforeground = #12345");
+        assert!(error.starts_with("line 2:") && error.contains("foreground"), "{}", error);
+        let error = parse_error("name = Tall\ntitle_bar_height = tall");
+        assert!(error.starts_with("line 2:") && error.contains("title_bar_height"), "{}", error);
+        let error = parse_error("desktop = pattern aa55 black white");
+        assert!(error.starts_with("line 1:") && error.contains("desktop"), "{}", error);
+        let error = parse_error("palette.ink = #12345\nforeground = ink");
+        assert!(error.starts_with("line 1:"), "{}", error);
+        let error = parse_error("base = Copland");
+        assert!(error.starts_with("line 1:") && error.contains("'System 7'"), "{}", error);
+        assert!(parse_error("just words").starts_with("line 1:"));
+    }
+
+    #[test]
+    fn metrics_must_fit_the_bars(){
+        assert!(parse_error("title_bar_height = 12").contains("title bar"));
+        assert!(parse_error("close_box_y = 20").contains("close box"));
+        assert!(parse_error("top_bar_y = 20").contains("top bar"));
+    }
+
+    #[test]
+    fn theme_fonts_are_completed_by_the_builtin_font(){
+        let directory = std::env::temp_dir().join(format!("modesto_theme_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("a.bdf"), "STARTFONT 2.1\nFONT a\nSIZE 12 75 75\nFONTBOUNDINGBOX 4 4 0 0\n\
+            CHARS 1\nSTARTCHAR A\nENCODING 65\nDWIDTH 5 0\nBBX 4 4 0 0\nBITMAP\nF0\n90\n90\nF0\nENDCHAR\nENDFONT\n").unwrap();
+        let theme = Theme::parse("font = a.bdf", &directory);
+        fs::remove_dir_all(&directory).unwrap();
+        let theme = theme.unwrap();
+        assert_eq!(theme.font.get_char('A').unwrap().get_advance(), 5);
+        assert!(theme.font.has_char('B'));
+    }
+}
@@ -1,13 +1,20 @@
 use std::collections::BTreeMap;
+use std::rc::Rc;
 use crate::pixel_font::PixelFont;
 use crate::widget;
+use crate::widget::event::{EditAction, WidgetEvent};
+use crate::widget::menu::{Menu, MenuItem};
 use crate::widget::text_widget::TextWidget;
+use crate::widget::theme::Theme;
 use crate::widget::{Color, Widget, WidgetBounds};
 
+///Height of the top bar/global menu.
+pub const TOP_BAR_HEIGHT: usize = 30;
+
 ///Widget representing buttons on the top bar/global menu.
 pub struct TopBarButton{
     text: Box<TextWidget>,
-    actions: Box<BTreeMap<Box<str>, Box<dyn Fn()>>>,
+    menu: Box<Menu>,
     opened: bool,
     cache: Box<Vec<[u8;4]>>,
     cache_width: usize,
@@ -17,16 +24,22 @@ pub struct TopBarButton{
 
 impl TopBarButton{
     ///Create a new TopBarButton with the given label and fold-down actions.
-    pub fn new(label: Box<&'static str>, actions: Box<BTreeMap<Box<str>, Box<dyn Fn()>>>) -> Self{
+    pub fn new(label: &str, actions: Box<BTreeMap<Box<str>, Box<dyn Fn()>>>) -> Self{
+        TopBarButton::with_menu(label, (*actions).into_iter()
+            .map(|(label, action)| MenuItem::new(&label, action))
+            .collect())
+    }
+    ///Create a new TopBarButton with the given label and fold-down menu items.
+    pub fn with_menu(label: &str, items: Vec<MenuItem>) -> Self{
         let mut tpb = TopBarButton{
             text: Box::new(TextWidget::new(
                 Box::new(PixelFont::default()),
                 false,
-                &label,
+                label,
                 Color::black(),
                 Color::white(),
             )),
-            actions,
+            menu: Box::new(Menu::new(items)),
             opened: false,
             cache: Box::new(vec![]),
             cache_width: 0,
@@ -42,48 +55,99 @@ impl TopBarButton{
         };
         tpb
     }
-    ///Calculate the needed with for the fold-out button box
-    fn get_max_action_box_width(&self) -> usize{
-        todo!()
+    ///Create the standard Edit menu, whose items act on the widget holding keyboard focus.
+    pub fn edit_menu() -> Self{
+        TopBarButton::with_menu("Edit", vec![
+            MenuItem::edit("Undo", EditAction::Undo).with_shortcut('z'),
+            MenuItem::edit("Redo", EditAction::Redo).with_shift_shortcut('z'),
+            MenuItem::separator(),
+            MenuItem::edit("Cut", EditAction::Cut).with_shortcut('x'),
+            MenuItem::edit("Copy", EditAction::Copy).with_shortcut('c'),
+            MenuItem::edit("Paste", EditAction::Paste).with_shortcut('v'),
+            MenuItem::edit("Clear", EditAction::Clear),
+            MenuItem::separator(),
+            MenuItem::edit("Select All", EditAction::SelectAll).with_shortcut('a'),
+        ])
     }
-    ///Calculate the height of the fold-out button box
-    fn get_max_action_box_height(&self) -> usize{
-        todo!()
+    ///Get the label of the button.
+    pub fn get_label(&self) -> &str{
+        self.text.get_text()
+    }
+    ///Change the label of the button. The top bar holding it is redrawn on the next render.
+    pub fn set_label(&mut self, label: &str){
+        self.text.set_text(label);
+    }
+    ///Get the fold-down menu of the button.
+    pub fn get_menu_mut(&mut self) -> &mut Menu{
+        &mut self.menu
+    }
+    ///Draw the label in the font and colours of the given theme.
+    fn set_theme(&mut self, theme: &Theme){
+        self.text = Box::new(TextWidget::new(
+            Box::new(theme.font.clone()),
+            false,
+            self.text.get_text(),
+            theme.foreground.into(),
+            theme.top_bar_background.into(),
+        ));
+        self.needs_redraw = true;
+    }
+    ///Open or close the fold-down menu. The label is shown inverted while it is open.
+    fn set_opened(&mut self, opened: bool){
+        if opened != self.opened{
+            self.opened = opened;
+            self.menu.set_highlighted(None);
+            self.needs_redraw = true;
+        }
     }
 }
 
 impl Widget for TopBarButton{
     fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
-        if !self.needs_redraw && self.cache.len() > 0{
+        if !self.needs_redraw() && self.cache.len() > 0{
             return None
-        } else if self.cache.len() == 0{
-            self.cache = Box::new(self.text.get_cache());
         }
+        let mut buf = match self.text.render(width, height){
+            Some(v) => v,
+            None => panic!("TextWidget should never return None for render")
+        };
+        if self.opened{
+            for p in buf.iter_mut(){
+                *p = [255 - p[0], 255 - p[1], 255 - p[2], 255u8];
+            }
+        }
+        self.cache = Box::new(buf.clone());
+        self.cache_width = width;
+        self.cache_height = height;
         self.needs_redraw = false;
-        self.text.render(width, height)
+        Some(buf)
     }
     fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
         todo!()
     }
     fn get_min_bounds(&self) -> WidgetBounds{
-        if !self.opened{
-            self.text.get_min_bounds()
-        } else{
-            WidgetBounds{
-                width: self.get_max_action_box_width(),
-                height: self.get_max_action_box_height(),
-            }
-        }
+        self.text.get_min_bounds()
     }
 
     fn get_cache(&mut self) -> Vec<[u8; 4]> {
         *self.cache.clone()
     }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.text.needs_redraw()
+    }
 }
 
 ///Widget representing the top bar/global menu.
 pub struct TopBarWidget {
-    buttons: Box<Vec<Box<dyn Widget>>>,
+    buttons: Box<Vec<Box<TopBarButton>>>,
+    ///Index of the button whose menu is open.
+    open: Option<usize>,
+    ///True if the open menu stays open after the mouse button is released.
+    sticky: bool,
+    ///Edit action chosen from a menu, waiting to be sent to the focused widget.
+    pending_action: Option<EditAction>,
+    theme: Rc<Theme>,
     cache: Box<Vec<[u8;4]>>,
     cache_width: usize,
     cache_height: usize,
@@ -91,25 +155,117 @@ pub struct TopBarWidget {
 }
 
 impl TopBarWidget {
-    ///Create a new top bar with the given set of buttons. Takes Box<Vec<Box<dyn Widget>>> but
-    /// should only be used with TopBarButton.
-    pub fn new(buttons: Box<Vec<Box<dyn Widget>>>) ->Self{
+    ///Create a new top bar with the given set of buttons.
+    pub fn new(buttons: Box<Vec<Box<TopBarButton>>>) ->Self{
         TopBarWidget {
             buttons,
+            open: None,
+            sticky: false,
+            pending_action: None,
+            theme: Rc::new(Theme::default()),
             cache: Box::new(vec![]),
             cache_width: 0,
             cache_height: 0,
             needs_redraw: true,
         }
     }
+    ///Get the button at the given index (left to right), if it exists.
+    pub fn get_button_mut(&mut self, index: usize) -> Option<&mut TopBarButton>{
+        self.buttons.get_mut(index).map(|b| &mut **b)
+    }
+    ///Draw the bar and its buttons in the given theme from the next render on.
+    pub fn set_theme(&mut self, theme: &Rc<Theme>){
+        if Rc::ptr_eq(&self.theme, theme){
+            return
+        }
+        self.theme = theme.clone();
+        for button in self.buttons.iter_mut(){
+            button.set_theme(theme);
+        }
+        self.needs_redraw = true;
+    }
+    ///Get the x offset of every button.
+    fn get_button_positions(&self) -> Vec<usize>{
+        let mut positions = vec![];
+        let mut xoff = self.theme.top_bar_x;
+        for button in self.buttons.iter(){
+            positions.push(xoff);
+            xoff += button.get_min_bounds().width + self.theme.top_bar_spacing;
+        }
+        positions
+    }
+    ///Get the index of the button at the given x offset, including the spacing around it.
+    fn get_button_at(&self, x: isize) -> Option<usize>{
+        let positions = self.get_button_positions();
+        let margin = (self.theme.top_bar_spacing / 2) as isize;
+        (0..self.buttons.len()).find(|i| {
+            let width = self.buttons[*i].get_min_bounds().width;
+            x >= positions[*i] as isize - margin && x < (positions[*i] + width) as isize + margin
+        })
+    }
+    ///Get the position of the menu of the given button.
+    fn get_menu_position(&self, index: usize) -> (usize, usize){
+        (self.get_button_positions()[index].saturating_sub(6), TOP_BAR_HEIGHT - 1)
+    }
+    fn open_menu(&mut self, index: Option<usize>){
+        if let Some(old) = self.open{
+            self.buttons[old].set_opened(false);
+        }
+        self.open = index;
+        if let Some(new) = index{
+            self.buttons[new].set_opened(true);
+        }
+    }
+    ///Returns true while a menu is open; it then takes all mouse events.
+    pub fn is_menu_open(&self) -> bool{
+        self.open.is_some()
+    }
+    ///Close the open menu, if any.
+    pub fn close_menu(&mut self){
+        self.open_menu(None);
+    }
+    ///Render the open menu, returning its position, size and pixels, to be drawn above everything
+    /// else.
+    pub fn render_open_menu(&mut self) -> Option<(usize, usize, WidgetBounds, Vec<[u8; 4]>)>{
+        let index = self.open?;
+        let (x, y) = self.get_menu_position(index);
+        let menu = &mut self.buttons[index].menu;
+        let bounds = menu.get_min_bounds();
+        let buf = match menu.render(bounds.width, bounds.height){
+            Some(v) => v,
+            None => menu.get_cache()
+        };
+        Some((x, y, bounds, buf))
+    }
+    ///Choose the menu item with the given shortcut key, pressed with shift or not, if there is one.
+    /// Returns true if an item was found.
+    pub fn trigger_shortcut(&mut self, c: char, shift: bool) -> bool{
+        for button in self.buttons.iter_mut(){
+            if let Some(idx) = button.menu.find_shortcut(c, shift){
+                self.pending_action = button.menu.activate(idx);
+                return true
+            }
+        }
+        false
+    }
+    ///Take the edit action chosen from a menu, if any.
+    pub fn take_edit_action(&mut self) -> Option<EditAction>{
+        self.pending_action.take()
+    }
+    ///Get the menu item under the given point, if the point is on the open menu.
+    fn get_menu_item_at(&self, x: isize, y: isize) -> Option<usize>{
+        let index = self.open?;
+        let (menu_x, menu_y) = self.get_menu_position(index);
+        self.buttons[index].menu.get_item_at(x - menu_x as isize, y - menu_y as isize)
+    }
 }
 
 impl Widget for TopBarWidget {
     fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
-        if !self.needs_redraw && self.cache.len() > 0{
+        if !self.needs_redraw() && self.cache.len() > 0{
             return None
         }
-        let mut buf = vec![[255u8;4]; width * height];
+        let mut buf = vec![self.theme.top_bar_background; width * height];
         let mut button_widths :Vec<usize> = Vec::new();
         let mut button_height = 0usize;
         let mut button_bufs : Vec<Vec<[u8;4]>> = Vec::new();
@@ -121,18 +277,17 @@ impl Widget for TopBarWidget {
             button_widths.push(bounds.width);
             button_height = bounds.height;
         }
-        let mut xoff = 10;
-        let yoff = 7;
+        let positions = self.get_button_positions();
+        let yoff = self.theme.top_bar_y;
         for (idx, button_buf) in button_bufs.iter().enumerate(){
             buf = widget::draw_on_top_at(
-                xoff, yoff,
+                positions[idx], yoff,
                 buf, width, height,
                 &button_buf, button_widths[idx], button_height);
-            xoff += button_widths[idx] + 20;
         }
         //add line at the bottom of the top bar
         for i in 0..width{
-            buf[(height - 1) * width + i] = [0u8, 0u8, 0u8, 255u8];
+            buf[(height - 1) * width + i] = self.theme.foreground;
         }
         self.cache_width = width;
         self.cache_height = height;
@@ -141,13 +296,85 @@ impl Widget for TopBarWidget {
         Some(buf)
     }
     fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
-        Some(&self.buttons)
+        None
     }
+    ///The width taken by the buttons, including the spacing after the last one.
     fn get_min_bounds(&self) -> WidgetBounds {
-        todo!()
+        let end = match (self.get_button_positions().last(), self.buttons.last()){
+            (Some(x), Some(button)) => x + button.get_min_bounds().width + self.theme.top_bar_spacing,
+            _ => self.theme.top_bar_x
+        };
+        WidgetBounds{
+            width: end,
+            height: TOP_BAR_HEIGHT,
+        }
     }
 
     fn get_cache(&mut self) -> Vec<[u8; 4]> {
         *self.cache.clone()
     }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.buttons.iter().any(|b| b.needs_redraw())
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        match *event{
+            WidgetEvent::MouseDown{x, y, ..} => {
+                if self.get_menu_item_at(x, y).is_some(){
+                    let index = self.open.unwrap();
+                    let (menu_x, menu_y) = self.get_menu_position(index);
+                    self.buttons[index].menu.handle_event(&event.translated(-(menu_x as isize), -(menu_y as isize)));
+                    return true
+                }
+                if y < TOP_BAR_HEIGHT as isize{
+                    let button = self.get_button_at(x);
+                    if button.is_some() && button == self.open && self.sticky{
+                        self.close_menu();
+                    } else {
+                        self.open_menu(button);
+                        self.sticky = false;
+                    }
+                    return true
+                }
+                self.close_menu();
+                false
+            },
+            WidgetEvent::MouseMove{x, y, ..} => {
+                let index = match self.open{
+                    Some(i) => i,
+                    None => return false
+                };
+                if y < TOP_BAR_HEIGHT as isize{
+                    if let Some(button) = self.get_button_at(x){
+                        if button != index{
+                            self.open_menu(Some(button));
+                        }
+                    }
+                    self.buttons[self.open.unwrap()].menu.set_highlighted(None);
+                } else {
+                    let (menu_x, menu_y) = self.get_menu_position(index);
+                    self.buttons[index].menu.handle_event(&event.translated(-(menu_x as isize), -(menu_y as isize)));
+                }
+                true
+            },
+            WidgetEvent::MouseUp{x, y, ..} => {
+                let index = match self.open{
+                    Some(i) => i,
+                    None => return false
+                };
+                if let Some(item) = self.get_menu_item_at(x, y){
+                    self.pending_action = self.buttons[index].menu.activate(item);
+                    self.close_menu();
+                } else if y < TOP_BAR_HEIGHT as isize && self.get_button_at(x) == Some(index) && !self.sticky{
+                    //released on the title it was opened with, keep the menu open until the next click
+                    self.sticky = true;
+                } else {
+                    self.close_menu();
+                }
+                true
+            },
+            _ => false
+        }
+    }
 }
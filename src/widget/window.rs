@@ -1,13 +1,35 @@
-use crate::pixel_font::PixelFont;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use crate::widget;
-use crate::widget::{Color, RectWidget, Widget, WidgetBounds};
+use crate::widget::{Widget, WidgetBounds};
+use crate::widget::application::AppId;
+use crate::widget::container::ContainerWidget;
+use crate::clipboard::Clipboard;
+use crate::widget::event::{EditAction, WidgetEvent};
+use crate::widget::menu::{MenuItem, PopupRequest};
 use crate::widget::text_widget::TextWidget;
-use crate::widget::top_bar::TopBarWidget;
+use crate::widget::theme::Theme;
+use crate::widget::top_bar::{TopBarWidget, TOP_BAR_HEIGHT};
+
+///Narrowest a window can be: its frame on both sides.
+const MIN_WINDOW_WIDTH: usize = 2;
+
+///Create the text widget showing a window title in the font and colours of the given theme.
+fn make_title(title: &str, theme: &Theme) -> TextWidget{
+    TextWidget::new(
+        Box::new(theme.font.clone()),
+        false,
+        title,
+        theme.foreground.into(),
+        theme.title_bar_background.into())
+}
 
 ///Widget representing the title bar of a window.
 pub struct WindowTopBarWidget{
     button: Box<WindowTopBarButton>,
+    has_close_box: bool,
     title: Box<TextWidget>,
+    theme: Rc<Theme>,
     cache: Box<Vec<[u8; 4]>>,
     cache_width: usize,
     cache_height: usize,
@@ -15,69 +37,96 @@ pub struct WindowTopBarWidget{
 }
 
 impl WindowTopBarWidget{
-    fn new(title: &'static str) -> Self{
+    fn new(title: &str, has_close_box: bool, theme: Rc<Theme>) -> Self{
         WindowTopBarWidget{
-            title: Box::new(TextWidget::new(
-                Box::new(PixelFont::default()),
-                false,
-                title,
-                Color::black(),
-                Color::white())),
+            has_close_box,
+            title: Box::new(make_title(title, &theme)),
             button: Box::new(WindowTopBarButton{
-                pressed: false
+                pressed: false,
+                theme: theme.clone(),
             }),
+            theme,
             cache: Box::new(vec![]),
             cache_height: 0,
             cache_width: 0,
             needs_redraw: true,
         }
     }
+    ///Change the title shown in the bar.
+    fn set_title(&mut self, title: &str){
+        self.title.set_text(title);
+    }
+    ///Draw the bar in the given theme from the next render on.
+    fn set_theme(&mut self, theme: Rc<Theme>){
+        self.title = Box::new(make_title(self.title.get_text(), &theme));
+        self.button.theme = theme.clone();
+        self.theme = theme;
+        self.needs_redraw = true;
+    }
+    ///Show the close box pressed or released.
+    fn set_close_box_pressed(&mut self, pressed: bool){
+        if self.button.pressed != pressed{
+            self.button.pressed = pressed;
+            self.needs_redraw = true;
+        }
+    }
 }
 
 impl Widget for WindowTopBarWidget{
     fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
-        if !self.needs_redraw && self.cache.len() > 1{
+        if !self.needs_redraw() && self.cache.len() > 1{
             if self.cache_height == height && self.cache_width == width{
                 return None
             }
         }
-        let mut buf = vec![[255u8; 4]; width * height];
-        //make the borders black
-        for i in 0..width{
-            buf[i] = [0u8, 0u8, 0u8, 255u8];
-            for j in 0..16{
-                if j % 3 == 0 && i > 4 && i < width-4{
-                    buf[(j+(height/4)) * width + i] = [0u8, 0u8, 0u8, 255u8];
+        let theme = self.theme.clone();
+        let mut buf = vec![theme.title_bar_background; width * height];
+        //stripes centered vertically, either side of the title
+        let stripes_height = theme.title_bar_stripe_count.saturating_sub(1) * theme.title_bar_stripe_spacing + 1;
+        let stripes_top = height.saturating_sub(stripes_height) / 2;
+        for stripe in 0..theme.title_bar_stripe_count{
+            let y = stripes_top + stripe * theme.title_bar_stripe_spacing;
+            for x in 5..width.saturating_sub(4){
+                buf[y * width + x] = theme.title_bar_stripes;
+                if let Some(highlight) = theme.title_bar_stripe_highlight{
+                    buf[(y + 1) * width + x] = highlight;
                 }
             }
-            buf[(height - 1) * width + i] = [0u8, 0u8, 0u8, 255u8];
+        }
+        //make the borders black
+        for i in 0..width{
+            buf[i] = theme.foreground;
+            buf[(height - 1) * width + i] = theme.foreground;
         }
         for i in 0..height{
-            buf[i * width + 0] = [0u8, 0u8, 0u8, 255u8];
-            buf[i * width + (width - 1)] = [0u8, 0u8, 0u8, 255u8];
+            buf[i * width + 0] = theme.foreground;
+            buf[i * width + (width - 1)] = theme.foreground;
+        }
+        if self.has_close_box{
+            let button_bounds = self.button.get_min_bounds();
+            buf = widget::draw_on_top_at(
+                theme.close_box_x, theme.close_box_y,
+                buf, width, height,
+                &match self.button.render(button_bounds.width, button_bounds.height){
+                    Some(v) => v,
+                    None => todo!()
+                },
+                button_bounds.width, button_bounds.height);
         }
-        let button_bounds = self.button.get_min_bounds();
-        buf = widget::draw_on_top_at(
-            4, 7,
-            buf, width, height,
-            &match self.button.render(button_bounds.width, button_bounds.height){
-                Some(v) => v,
-                None => todo!()
-            },
-            button_bounds.width, button_bounds.height);
         let text_bounds = self.title.get_min_bounds();
         let title = match self.title.render(text_bounds.width, text_bounds.height){
             Some(v) => v,
             None => todo!()
         };
-        let title_x_offset = width/2 - text_bounds.width / 2;
+        let title_x_offset = (width/2).saturating_sub(text_bounds.width / 2);
         let out = widget::draw_on_top_at(
-            title_x_offset, 7,
+            title_x_offset, theme.title_y,
             buf, width, height,
             &title, text_bounds.width, text_bounds.height);
         self.cache = Box::new(out.clone());
         self.cache_height = height;
         self.cache_width = width;
+        self.needs_redraw = false;
         Some(out)
     }
     fn get_children(&self) -> Option<&Box<Vec<Box<dyn Widget>>>> {
@@ -90,29 +139,32 @@ impl Widget for WindowTopBarWidget{
     fn get_cache(&mut self) -> Vec<[u8; 4]> {
         *self.cache.clone()
     }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.title.needs_redraw()
+    }
 }
 
 ///Widget representing the singular button in a window top bar (close)
 pub struct WindowTopBarButton{
     pressed: bool,
+    theme: Rc<Theme>,
 }
 
 impl Widget for WindowTopBarButton{
     fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
-        if self.pressed {
-            Some(vec![[0u8, 0u8, 0u8, 255u8]; width * height])
-        } else {
-            let mut buf = vec![[255u8; 4]; width * height];
-            for i in 0..width {
-                buf[i] = [0u8, 0u8, 0u8, 255u8];
-                buf[(height - 1) * width + i] = [0u8, 0u8, 0u8, 255u8]
-            }
-            for i in 1..(height - 1) {
-                buf[i * width] = [0u8, 0u8, 0u8, 255u8];
-                buf[i * width + (width - 1)] = [0u8, 0u8, 0u8, 255u8];
-            }
-            Some(buf)
+        let frame = self.theme.foreground;
+        let fill = if self.pressed {self.theme.close_box_pressed} else {self.theme.close_box_background};
+        let mut buf = vec![fill; width * height];
+        for i in 0..width {
+            buf[i] = frame;
+            buf[(height - 1) * width + i] = frame
+        }
+        for i in 1..(height - 1) {
+            buf[i * width] = frame;
+            buf[i * width + (width - 1)] = frame;
         }
+        Some(buf)
 
     }
 
@@ -122,8 +174,8 @@ impl Widget for WindowTopBarButton{
 
     fn get_min_bounds(&self) -> WidgetBounds {
         WidgetBounds{
-            width: 16,
-            height: 16
+            width: self.theme.close_box_size,
+            height: self.theme.close_box_size
         }
     }
 
@@ -132,6 +184,34 @@ impl Widget for WindowTopBarButton{
     }
 }
 
+///Handle asking a window to close, for callbacks of widgets inside it (e.g. the buttons of a
+/// dialog). The window is removed by MainWidget after the current event.
+#[derive(Clone)]
+pub struct WindowCloser{
+    requested: Rc<Cell<bool>>,
+}
+
+impl WindowCloser{
+    ///Ask the window to close.
+    pub fn close(&self){
+        self.requested.set(true);
+    }
+}
+
+///Handle opening new windows from callbacks of widgets inside a window (e.g. a dialog asking for
+/// confirmation). MainWidget adds them to the desktop after the current event.
+#[derive(Clone)]
+pub struct WindowOpener{
+    opened: Rc<RefCell<Vec<WindowWidget>>>,
+}
+
+impl WindowOpener{
+    ///Ask for the given window to be opened. Modal windows are put in front of all others.
+    pub fn open(&self, window: WindowWidget){
+        self.opened.borrow_mut().push(window);
+    }
+}
+
 ///Widget representing a window in Modesto Desktop.
 pub struct WindowWidget{
     pub is_moving: bool,
@@ -141,16 +221,29 @@ pub struct WindowWidget{
     pub x_position: usize,
     pub y_position: usize,
     width: usize,
-    height: usize,
+    ///Height of the body; the height of the window depends on the title bar of the theme.
+    body_height: usize,
+    theme: Rc<Theme>,
     cache: Box<Vec<[u8;4]>>,
     cache_width: usize,
     cache_height: usize,
     needs_redraw : bool,
+    ///True while the mouse button pressed inside the body is held.
+    body_captured: bool,
+    ///True while the mouse button pressed on the close box is held.
+    close_box_tracking: bool,
+    close_requested: Rc<Cell<bool>>,
+    ///Windows waiting to be opened by MainWidget.
+    opened: Rc<RefCell<Vec<WindowWidget>>>,
+    ///Modal windows have no close box and block all other windows while open.
+    modal: bool,
+    ///Application the window belongs to, None for windows of the desktop itself.
+    app: Option<AppId>,
 }
 
 impl Widget for WindowWidget{
     fn render(&mut self, width: usize, height: usize) -> Option<Vec<[u8; 4]>> {
-        if !self.needs_redraw && self.cache.len() > 0
+        if !self.needs_redraw() && self.cache.len() > 0
             && self.cache_width == width && self.cache_height == height{
             return Some(*self.cache.clone())
         }
@@ -171,15 +264,25 @@ impl Widget for WindowWidget{
             self.needs_redraw = false;
             return Some(buf)
         }
-        let buf = widget::draw_on_top_at(
+        let title_height = self.theme.title_bar_height;
+        let mut buf = widget::draw_on_top_at(
             0, 0,
-            vec![[0u8, 0u8, 0u8, 255u8];width * height],
+            vec![self.theme.foreground;width * height],
             width, height,
-            &match self.window_top_bar.render(width, 30){
+            &match self.window_top_bar.render(width, title_height){
                 Some(v) => v,
                 None => self.window_top_bar.get_cache()
             },
-            width, 30);
+            width, title_height);
+        let (body_width, body_height) = (width.saturating_sub(2), height.saturating_sub(title_height + 1));
+        let body = match self.window_body.render(body_width, body_height){
+            Some(v) => v,
+            None => self.window_body.get_cache()
+        };
+        buf = widget::draw_on_top_at(
+            1, title_height,
+            buf, width, height,
+            &body, body_width, body_height);
         self.cache_height = height;
         self.cache_width = width;
         self.cache = Box::new(buf.clone());
@@ -191,35 +294,202 @@ impl Widget for WindowWidget{
     }
     fn get_min_bounds(&self) -> WidgetBounds {
         WidgetBounds{
-            width: self.width, height : self.height,
+            width: self.width, height : self.body_height + self.theme.title_bar_height + 1,
         }
     }
 
     fn get_cache(&mut self) -> Vec<[u8; 4]> {
         todo!()
     }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw || self.window_top_bar.needs_redraw() || self.window_body.needs_redraw()
+    }
+
+    fn handle_event(&mut self, event: &WidgetEvent) -> bool {
+        let title_height = self.theme.title_bar_height as isize;
+        match *event{
+            WidgetEvent::MouseDown{x, y, ..} => {
+                if y < title_height{
+                    if !self.modal && self.in_close_box(x, y){
+                        self.close_box_tracking = true;
+                        self.window_top_bar.set_close_box_pressed(true);
+                        return true
+                    }
+                    return false
+                }
+                self.body_captured = true;
+                self.window_body.handle_event(&event.translated(-1, -title_height))
+            },
+            WidgetEvent::MouseUp{x, y, ..} if self.close_box_tracking => {
+                self.close_box_tracking = false;
+                self.window_top_bar.set_close_box_pressed(false);
+                if self.in_close_box(x, y){
+                    self.close_requested.set(true);
+                }
+                true
+            },
+            WidgetEvent::MouseMove{x, y, ..} if self.close_box_tracking => {
+                //the close box is only shown pressed while the mouse is over it
+                let pressed = self.in_close_box(x, y);
+                self.window_top_bar.set_close_box_pressed(pressed);
+                true
+            },
+            WidgetEvent::MouseUp{..} => {
+                if !self.body_captured{
+                    return false
+                }
+                self.body_captured = false;
+                self.window_body.handle_event(&event.translated(-1, -title_height))
+            },
+            WidgetEvent::MouseMove{..} => {
+                //also sent when the mouse is outside of the body, so widgets can clear their hover state
+                self.window_body.handle_event(&event.translated(-1, -title_height))
+            },
+            WidgetEvent::Scroll{y, ..} => {
+                if y < title_height{
+                    return false
+                }
+                self.window_body.handle_event(&event.translated(-1, -title_height))
+            },
+            _ => self.window_body.handle_event(event)
+        }
+    }
+
+    fn handle_edit_action(&mut self, action: EditAction, clipboard: &mut Clipboard) -> bool {
+        self.window_body.handle_edit_action(action, clipboard)
+    }
+    fn handle_default_action(&mut self) -> bool {
+        self.window_body.handle_default_action()
+    }
+    fn handle_cancel_action(&mut self) -> bool {
+        self.window_body.handle_cancel_action()
+    }
+
+    fn get_context_menu(&mut self, x: isize, y: isize) -> Option<Vec<MenuItem>> {
+        let title_height = self.theme.title_bar_height as isize;
+        if y < title_height{
+            return None
+        }
+        self.window_body.get_context_menu(x - 1, y - title_height)
+    }
+
+    fn take_popup_request(&mut self) -> Option<PopupRequest> {
+        let title_height = self.theme.title_bar_height as isize;
+        self.window_body.take_popup_request().map(|r| r.translated(1, title_height))
+    }
 }
 
 impl WindowWidget{
-    ///Create a new WindowWidget with the given title, dimensions and position
-    pub fn new(title: &'static str, width: usize, height: usize, xpos: usize, ypos: usize)-> Self{
+    ///Create a new WindowWidget with the given title, dimensions and position. The dimensions are
+    /// those in the default theme; the body keeps its size when the theme changes. Windows are at
+    /// least wide enough for their frame and tall enough for the title bar.
+    pub fn new(title: &str, width: usize, height: usize, xpos: usize, ypos: usize)-> Self{
+        let theme = Rc::new(Theme::default());
+        let width = width.max(MIN_WINDOW_WIDTH);
+        let body_height = height.saturating_sub(theme.title_bar_height + 1);
         WindowWidget{
             is_moving: false,
             top_bar: Box::new(TopBarWidget::new(
                 Box::new(vec![])
             )),
-            window_top_bar: WindowTopBarWidget::new(title),
-            window_body: Box::new(RectWidget{
-                rec_height: height, rec_width: width
-            }),
+            window_top_bar: WindowTopBarWidget::new(title, true, theme.clone()),
+            window_body: Box::new(ContainerWidget::new(width - 2, body_height)),
             width,
-            height,
+            body_height,
+            theme,
             x_position: xpos,
             y_position: ypos,
             cache: Box::new(vec![]),
             cache_height: 0,
             cache_width: 0,
             needs_redraw: true,
+            body_captured: false,
+            close_box_tracking: false,
+            close_requested: Rc::new(Cell::new(false)),
+            opened: Rc::new(RefCell::new(vec![])),
+            modal: false,
+            app: None,
+        }
+    }
+    ///Create a new modal window (a dialog) around a body of the given size, centered on a screen of
+    /// the given size below the global top bar. Modal windows have no close box; MainWidget routes
+    /// all events to them until they are closed.
+    pub fn new_modal(title: &str, body_width: usize, body_height: usize, screen_width: usize, screen_height: usize) -> Self{
+        let width = body_width + 2;
+        let height = body_height + Theme::default().title_bar_height + 1;
+        let xpos = screen_width.saturating_sub(width) / 2;
+        let ypos = TOP_BAR_HEIGHT + screen_height.saturating_sub(TOP_BAR_HEIGHT + height) / 2;
+        let mut window = WindowWidget::new(title, width, height, xpos, ypos);
+        window.window_top_bar = WindowTopBarWidget::new(title, false, window.theme.clone());
+        window.modal = true;
+        window
+    }
+    ///Returns true if the window is modal.
+    pub fn is_modal(&self) -> bool{
+        self.modal
+    }
+    ///Get the application the window belongs to, None for windows of the desktop itself.
+    pub fn get_app(&self) -> Option<AppId>{
+        self.app
+    }
+    ///Set the application the window belongs to. MainWidget sets it when an application opens the
+    /// window.
+    pub(crate) fn set_app(&mut self, app: Option<AppId>){
+        self.app = app;
+    }
+    ///Get a handle closing the window, to be moved into callbacks.
+    pub fn get_closer(&self) -> WindowCloser{
+        WindowCloser{
+            requested: self.close_requested.clone(),
+        }
+    }
+    ///Get a handle opening new windows, to be moved into callbacks.
+    pub fn get_opener(&self) -> WindowOpener{
+        WindowOpener{
+            opened: self.opened.clone(),
+        }
+    }
+    ///Take the windows asked to be opened through a WindowOpener.
+    pub(crate) fn take_opened_windows(&mut self) -> Vec<WindowWidget>{
+        self.opened.borrow_mut().drain(..).collect()
+    }
+    ///Returns true if the window was asked to close, through its close box or a WindowCloser.
+    pub fn is_close_requested(&self) -> bool{
+        self.close_requested.get()
+    }
+    ///Returns true if the given point (relative to the window) is on the close box.
+    fn in_close_box(&self, x: isize, y: isize) -> bool{
+        let (left, top) = (self.theme.close_box_x as isize, self.theme.close_box_y as isize);
+        let size = self.theme.close_box_size as isize;
+        x >= left && x < left + size && y >= top && y < top + size
+    }
+    ///Get the theme the window is drawn in.
+    pub fn get_theme(&self) -> &Theme{
+        &self.theme
+    }
+    ///Draw the window and its top bar in the given theme from the next render on. MainWidget
+    /// hands its theme to every window before rendering it.
+    pub fn set_theme(&mut self, theme: &Rc<Theme>){
+        if Rc::ptr_eq(&self.theme, theme){
+            return
+        }
+        self.theme = theme.clone();
+        self.window_top_bar.set_theme(theme.clone());
+        self.top_bar.set_theme(theme);
+        self.needs_redraw = true;
+    }
+    ///Set the widget filling the window below the title bar. It is rendered to the size given by
+    /// get_body_bounds.
+    pub fn set_body(&mut self, body: Box<dyn Widget>){
+        self.window_body = body;
+        self.needs_redraw = true;
+    }
+    ///Get the size of the area below the title bar available to the body.
+    pub fn get_body_bounds(&self) -> WidgetBounds{
+        WidgetBounds{
+            width: self.width.saturating_sub(2),
+            height: self.body_height,
         }
     }
     ///Register a top bar/global menu for the window.
@@ -233,6 +503,22 @@ impl WindowWidget{
             None => self.top_bar.get_cache()
         }
     }
+    ///Get the current window title.
+    pub fn get_title(&self) -> &str{
+        self.window_top_bar.title.get_text()
+    }
+    ///Change the window title. The title bar is redrawn on the next render.
+    pub fn set_title(&mut self, title: &str){
+        self.window_top_bar.set_title(title);
+    }
+    ///Get the top bar/global menu registered for the window, e.g. to relabel its buttons.
+    pub fn get_top_bar_mut(&mut self) -> &mut TopBarWidget{
+        &mut self.top_bar
+    }
+    ///Returns true if a menu of the window's top bar is open.
+    pub fn top_bar_menu_open(&self) -> bool{
+        self.top_bar.is_menu_open()
+    }
     pub fn set_moving(&mut self, new_status: bool){
         self.is_moving = new_status;
         self.needs_redraw = true;